@@ -0,0 +1,173 @@
+//! How fast scanning and laying out big trees is, without the disk, and how much memory what
+//! they build takes: run with `cargo bench`.
+//!
+//! There's no library target, so the modules these need are compiled in again here.
+
+#![allow(dead_code)]
+// Checking all targets builds this with `cfg(test)` but leaves out the tests themselves, so what
+// the included modules' tests import goes unused.
+#![cfg_attr(test, allow(unused_imports))]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    ffi::OsStr,
+    hint::black_box,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[path = "../src/analyze.rs"]
+mod analyze;
+#[cfg(feature = "archives")]
+#[path = "../src/archive.rs"]
+mod archive;
+#[path = "../src/choice.rs"]
+mod choice;
+// `partition_view` finds this as its sibling, the way it does under `gui`.
+#[path = "../src/gui/config.rs"]
+mod config;
+#[path = "../src/format.rs"]
+mod format;
+// `analyze` brings its own along under `cfg(test)`.
+#[cfg(not(test))]
+#[path = "../src/analyze/mem_fs.rs"]
+mod mem_fs;
+#[path = "../src/gui/partition_view.rs"]
+mod partition_view;
+#[cfg(feature = "sftp")]
+#[path = "../src/sftp.rs"]
+mod sftp;
+
+// `mem_fs` reaches for these in its parent, which is `analyze` in the app.
+#[cfg(not(test))]
+use analyze::{FileKind, FileSystem, Metadata};
+
+#[cfg(test)]
+use analyze::mem_fs;
+use analyze::{analyze_dir_with, AnalyzedDir, AnalyzedItem, Context, Metric};
+use mem_fs::MemFs;
+use partition_view::{recursive_box, LayoutParams, StateBox};
+
+/// What the GUI lays the treemap out with at its default header height.
+const PARAMS: LayoutParams = LayoutParams {
+    minimum_area: 64.0,
+    minimum_aspect: 0.05,
+    header_height: 11.2,
+    metric: Metric::Bytes,
+};
+
+/// Bytes allocated and not yet freed. Counting them costs the timings an atomic add per
+/// allocation, which is small next to the allocation itself.
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+struct Counting;
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Runs `f`, along with how many bytes what it returns still holds on to.
+fn held<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LIVE.load(Ordering::Relaxed);
+    let value = f();
+    (value, LIVE.load(Ordering::Relaxed).saturating_sub(before))
+}
+
+fn scan(fs: &MemFs) -> AnalyzedDir {
+    analyze_dir_with(fs, Path::new("/r"), &Context::default()).unwrap()
+}
+
+/// Number of boxes in `boxes`, counting the ones inside them.
+fn count(boxes: &[StateBox]) -> usize {
+    let mut n = 0;
+    for f in boxes {
+        f.for_each_tile((0.0, 0.0), 0, &mut |_, _, _| n += 1);
+    }
+    n
+}
+
+/// Prints what the scanned trees take, what storing names under a shared parent saves over a
+/// full path per item, and how many boxes are laid out over them at a few window sizes and what
+/// those take next to that. There's nothing
+/// to time, so it goes ahead of the groups that do.
+fn memory(_: &mut Criterion) {
+    for files in [10_000, 100_000] {
+        let fs = MemFs::synthetic(files);
+        let (dir, bytes) = held(|| scan(&fs));
+        let (mut items, mut names, mut paths) = (0, 0, 0);
+        dir.walk(&mut |f, _| {
+            items += 1;
+            names += f.name().map_or(0, OsStr::len);
+            paths += f.path().as_os_str().len();
+        });
+        println!(
+            "{files} files: {items} items of {} bytes each take {bytes} bytes, {names} of them \
+             names, where full paths would take {paths}",
+            size_of::<AnalyzedItem>(),
+        );
+        // Boxes keep a path to what they show rather than a copy of it, and small directories
+        // are left unbuilt, so they should take a fraction of the tree.
+        for space in [(640.0, 480.0), (1920.0, 1080.0), (3840.0, 2160.0)] {
+            let (boxes, bytes) = held(|| recursive_box(space, &PARAMS, &dir));
+            println!(
+                "  {}x{}: {} boxes for {items} items, {} bytes each, take {bytes} bytes",
+                space.0,
+                space.1,
+                count(&boxes),
+                size_of::<StateBox>(),
+            );
+        }
+    }
+}
+
+fn scanning(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+    group.sample_size(10);
+    for files in [10_000, 100_000, 500_000] {
+        let fs = MemFs::synthetic(files);
+        group.throughput(Throughput::Elements(files as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(files), &fs, |b, fs| {
+            b.iter(|| scan(fs));
+        });
+    }
+    group.finish();
+}
+
+fn layout(c: &mut Criterion) {
+    let dir = scan(&MemFs::synthetic(100_000));
+    let mut group = c.benchmark_group("layout");
+    group.bench_function("top level", |b| {
+        b.iter(|| {
+            analyze::partition(
+                (1920.0, 1080.0),
+                PARAMS.minimum_area,
+                PARAMS.minimum_aspect,
+                PARAMS.metric,
+                black_box(&dir),
+            )
+            .len()
+        });
+    });
+    for space in [(640.0, 480.0), (1920.0, 1080.0), (3840.0, 2160.0)] {
+        let id = BenchmarkId::new("recursive", format!("{}x{}", space.0, space.1));
+        group.bench_with_input(id, &space, |b, &space| {
+            b.iter(|| recursive_box(space, &PARAMS, black_box(&dir)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, memory, scanning, layout);
+criterion_main!(benches);
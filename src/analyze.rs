@@ -1,33 +1,786 @@
 use std::{
-    ffi::OsStr,
-    os::unix::fs::MetadataExt,
+    collections::HashMap,
+    ffi::{CString, OsStr, OsString},
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::SystemTime,
 };
 
-#[derive(Debug)]
-pub struct Context {}
+use crate::choice::Choice;
+
+#[cfg(test)]
+// Spelled out so the bench, which includes this file by path, finds it too.
+#[path = "analyze/mem_fs.rs"]
+pub(crate) mod mem_fs;
+
+#[derive(Debug, Default)]
+pub struct Context {
+    errors: Mutex<Vec<ScanError>>,
+    min_file_size: u64,
+    skip_empty_files: bool,
+    max_entries_per_dir: Option<usize>,
+    detail_threshold: Option<u64>,
+    #[cfg(feature = "archives")]
+    scan_archives: bool,
+    hidden: HiddenMode,
+    dedup_dirs: bool,
+    /// Where each directory was first found, by `(dev, ino)`, if [`Self::with_dedup_dirs`] is on.
+    seen_dirs: Mutex<HashMap<(u64, u64), PathBuf>>,
+    partial_depth: Option<usize>,
+    /// The scanned directory with the directories in it that are finished so far, if
+    /// [`Self::with_partial_results`] is on.
+    partial: Mutex<Option<AnalyzedDir>>,
+    partial_changed: AtomicBool,
+    files: AtomicU64,
+    bytes: AtomicU64,
+    /// Paths of every file with more than one link, by `(dev, ino)`.
+    hardlinks: Mutex<HashMap<(u64, u64), Vec<PathBuf>>>,
+    control: Mutex<ScanControl>,
+    control_changed: Condvar,
+}
+impl Context {
+    /// Blocks the scan until [`Self::resume`] or [`Self::cancel`] is called.
+    pub fn pause(&self) {
+        self.set_control(ScanControl::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.set_control(ScanControl::Run);
+    }
+
+    /// Stops the scan, which then fails with [`std::io::ErrorKind::Interrupted`].
+    pub fn cancel(&self) {
+        self.set_control(ScanControl::Cancel);
+    }
+
+    fn set_control(&self, control: ScanControl) {
+        let mut current = self.control.lock().unwrap();
+        // A cancelled scan stays cancelled.
+        if *current != ScanControl::Cancel {
+            *current = control;
+        }
+        self.control_changed.notify_all();
+    }
+
+    /// Waits while the scan is paused, and fails once it's cancelled.
+    fn checkpoint(&self) -> std::io::Result<()> {
+        let control = self
+            .control_changed
+            .wait_while(self.control.lock().unwrap(), |f| *f == ScanControl::Pause)
+            .unwrap();
+        match *control {
+            ScanControl::Cancel => Err(std::io::ErrorKind::Interrupted.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Leaves files smaller than `size` out of the scanned tree. Their bytes are still counted in
+    /// the size of their directory, as [`AnalyzedDir::skipped_size`], but not in its file count.
+    #[must_use]
+    pub const fn with_min_file_size(mut self, size: u64) -> Self {
+        self.min_file_size = size;
+        self
+    }
+
+    /// Keeps only the `max` largest entries of each directory as children and lumps the rest in
+    /// with its skipped files, which bounds the work of laying out huge directories. Lumped
+    /// directories are still scanned in full, so sizes stay exact, but what's in them isn't kept
+    /// or counted.
+    #[must_use]
+    pub const fn with_max_entries_per_dir(mut self, max: Option<usize>) -> Self {
+        self.max_entries_per_dir = max;
+        self
+    }
+
+    /// Only keeps what's in directories taking up at least `size` bytes, for finding what's big
+    /// quickly. The scan first adds up every directory without keeping anything, then reads only
+    /// the directories over `size` again to keep their entries; smaller ones are kept as
+    /// [`AnalyzedDir::summarized`] directories with nothing in them. Sizes and file counts stay
+    /// exact, but what's in small directories can't be seen without scanning them on their own,
+    /// and errors inside them aren't reported. Directories over `size` are read twice, so this
+    /// is only faster when most of the tree is in small directories.
+    #[must_use]
+    pub const fn with_detail_threshold(mut self, size: Option<u64>) -> Self {
+        self.detail_threshold = size;
+        self
+    }
+
+    /// Lists the contents of zip and tar archives as directories of their own, sized by how much
+    /// of the archive each entry takes up. This opens every archive found, so it's off by default.
+    #[cfg(feature = "archives")]
+    #[must_use]
+    pub const fn with_scan_archives(mut self, scan: bool) -> Self {
+        self.scan_archives = scan;
+        self
+    }
+
+    /// Counts each directory once however many paths it's found at, going by its device and
+    /// inode, so bind mounts don't add the same bytes up twice. Later paths are left out and
+    /// reported as [`ScanErrorKind::AlreadyCounted`]. Only directories are compared, so a file
+    /// bind-mounted on its own still counts at each path. The merged view of an overlay has a
+    /// device of its own, so scanning it along with the layers under it still counts them twice.
+    #[must_use]
+    pub const fn with_dedup_dirs(mut self, dedup: bool) -> Self {
+        self.dedup_dirs = dedup;
+        self
+    }
+
+    /// Where the directory at `path` was first found, if directories are deduplicated and it
+    /// was somewhere else. Finding it at the same path again, as both passes of a scan with a
+    /// detail threshold do, doesn't count.
+    fn counted_elsewhere(&self, path: &Path, metadata: &Metadata) -> Option<PathBuf> {
+        if !self.dedup_dirs {
+            return None;
+        }
+        let mut seen = self.seen_dirs.lock().unwrap();
+        let first = seen
+            .entry((metadata.dev, metadata.ino))
+            .or_insert_with(|| path.to_owned());
+        (**first != *path).then(|| first.clone())
+    }
+
+    /// What to do with entries whose name starts with a dot.
+    #[must_use]
+    pub const fn with_hidden(mut self, hidden: HiddenMode) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Whether files with no contents are kept in the scanned tree, which they are by default.
+    #[must_use]
+    pub const fn with_include_empty_files(mut self, include: bool) -> Self {
+        self.skip_empty_files = !include;
+        self
+    }
+
+    /// Keeps what's been scanned of the directory as it goes, for showing it before the scan is
+    /// done. Each directory in it is added once it's finished, with only `depth` levels below it
+    /// kept so taking the result stays cheap. See [`Self::take_partial`].
+    #[must_use]
+    pub const fn with_partial_results(mut self, depth: Option<usize>) -> Self {
+        self.partial_depth = depth;
+        self
+    }
+
+    /// What's been scanned so far, if [`Self::with_partial_results`] is on and more was finished
+    /// since the last call. Can be called from another thread while scanning.
+    pub fn take_partial(&self) -> Option<AnalyzedDir> {
+        if !self.partial_changed.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        self.partial.lock().unwrap().clone()
+    }
+
+    fn record_partial(&self, dir: &AnalyzedDir) {
+        let Some(depth) = self.partial_depth else {
+            return;
+        };
+        if let Some(partial) = self.partial.lock().unwrap().as_mut() {
+            partial.children.push(AnalyzedItem::Dir(dir.pruned(depth)));
+            partial.recount();
+            self.partial_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn record_file(&self, size: u64) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// How much has been scanned so far. Can be read from another thread while scanning.
+    pub fn progress(&self) -> ScanProgress {
+        ScanProgress {
+            files: self.files.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn report(&self, error: ScanError) {
+        self.errors.lock().unwrap().push(error);
+    }
+
+    /// Takes the errors encountered so far, in the order they happened.
+    pub fn take_errors(&self) -> Vec<ScanError> {
+        std::mem::take(&mut *self.errors.lock().unwrap())
+    }
+
+    fn record_hardlink(&self, metadata: &Metadata, path: &Path) {
+        self.hardlinks
+            .lock()
+            .unwrap()
+            .entry((metadata.dev, metadata.ino))
+            .or_default()
+            .push(path.to_path_buf());
+    }
+
+    /// Takes the hard-linked files found so far.
+    pub fn take_hardlinks(&self) -> HardLinks {
+        let groups = std::mem::take(&mut *self.hardlinks.lock().unwrap());
+        let mut links = HardLinks::default();
+        links.extend(groups);
+        links
+    }
+}
+
+/// Files sharing an inode with others in the scanned tree.
+#[derive(Debug, Clone, Default)]
+pub struct HardLinks {
+    groups: HashMap<(u64, u64), Vec<PathBuf>>,
+    by_path: HashMap<PathBuf, (u64, u64)>,
+}
+impl HardLinks {
+    /// Every path of the file at `path`, including itself, or `None` if no other link to it was
+    /// scanned.
+    pub fn of(&self, path: &Path) -> Option<&[PathBuf]> {
+        let paths = self.groups.get(self.by_path.get(path)?)?;
+        (paths.len() > 1).then_some(paths.as_slice())
+    }
+
+    /// Replaces the links found under `dir` with those of a new scan of it.
+    pub fn splice(&mut self, dir: &Path, new: Self) {
+        self.by_path.retain(|path, _| !path.starts_with(dir));
+        self.groups.retain(|_, paths| {
+            paths.retain(|f| !f.starts_with(dir));
+            !paths.is_empty()
+        });
+        self.extend(new.groups);
+    }
+
+    fn extend(&mut self, groups: HashMap<(u64, u64), Vec<PathBuf>>) {
+        for (inode, paths) in groups {
+            for path in &paths {
+                self.by_path.insert(path.clone(), inode);
+            }
+            self.groups.entry(inode).or_default().extend(paths);
+        }
+    }
+}
+
+/// How entries whose name starts with a dot are scanned.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    clap::ValueEnum,
+)]
+pub enum HiddenMode {
+    /// Like every other entry.
+    #[default]
+    Show,
+    /// Not at all, so they aren't counted in any size.
+    Hide,
+    /// Lumped in with the skipped files of their directory, so they still count toward its size
+    /// but don't clutter it.
+    Collapse,
+}
+impl Choice for HiddenMode {
+    const ALL: &'static [Self] = &[Self::Show, Self::Hide, Self::Collapse];
+    const LABELS: &'static [&'static str] = &["Show", "Hide", "Collapse"];
+}
+
+/// What the treemap sizes tiles by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Metric {
+    /// Bytes allocated on disk.
+    #[default]
+    Bytes,
+    /// Files and symlinks, each taking up an inode however small it is.
+    FileCount,
+}
+impl Choice for Metric {
+    const ALL: &'static [Self] = &[Self::Bytes, Self::FileCount];
+    const LABELS: &'static [&'static str] = &["Bytes", "File count"];
+}
+impl Metric {
+    /// How much of this metric `item` takes up.
+    pub const fn of(self, item: &AnalyzedItem) -> u64 {
+        match self {
+            Self::Bytes => item.size(),
+            Self::FileCount => item.num_files(),
+        }
+    }
+
+    /// How much of this metric `dir` takes up, including the entries it didn't keep.
+    pub const fn of_dir(self, dir: &AnalyzedDir) -> u64 {
+        match self {
+            Self::Bytes => dir.size,
+            Self::FileCount => dir.num_files + dir.skipped_files,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ScanControl {
+    #[default]
+    Run,
+    Pause,
+    Cancel,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanProgress {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// A problem with one path that was skipped or flagged while the rest of the scan continued.
+#[derive(Debug, Clone)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub kind: ScanErrorKind,
+}
+impl ScanError {
+    fn io(path: &Path, e: &std::io::Error) -> Self {
+        Self {
+            path: path.to_owned(),
+            kind: ScanErrorKind::Io {
+                kind: e.kind(),
+                message: e.to_string(),
+            },
+        }
+    }
+}
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ScanErrorKind::Io { message, .. } => write!(f, "{}: {message}", self.path.display()),
+            ScanErrorKind::SymlinkCycle => write!(f, "{}: symlink cycle", self.path.display()),
+            ScanErrorKind::AlreadyCounted { first } => write!(
+                f,
+                "{}: already counted at {}",
+                self.path.display(),
+                first.display()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ScanErrorKind {
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    SymlinkCycle,
+    /// A directory that was already scanned at `first`, through a bind mount or the like.
+    AlreadyCounted {
+        first: PathBuf,
+    },
+}
+impl ScanErrorKind {
+    /// Describes the kind of problem without the details of the one occurrence, for grouping
+    /// errors of the same kind together.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Io { kind, .. } => kind.to_string(),
+            Self::SymlinkCycle => "symlink cycle".into(),
+            Self::AlreadyCounted { .. } => "already counted elsewhere".into(),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AnalyzedDir {
     pub children: Vec<AnalyzedItem>,
-    pub path: PathBuf,
+    /// Shared with the children, which only store their own name.
+    pub path: Arc<Path>,
+    /// Bytes allocated on disk.
     pub size: u64,
+    /// Sum of the lengths of the files below this directory, which differs from `size` by block
+    /// slack, sparse files and compression.
+    pub apparent_size: u64,
     pub num_symlinks: u64,
     pub num_files: u64,
     pub num_dirs: u64,
+    pub modified: Option<SystemTime>,
+    /// Bytes of the entries directly in this directory that weren't kept as children: files too
+    /// small to keep, collapsed hidden entries, and entries lumped together because there were
+    /// too many. Included in `size`.
+    pub skipped_size: u64,
+    /// Apparent size of those entries. Included in `apparent_size`.
+    pub skipped_apparent_size: u64,
+    /// Number of those entries. Not included in `num_files`.
+    pub skipped_files: u64,
+    /// Whether entries of this directory were lumped together because there were too many.
+    pub truncated: bool,
+    /// Whether this directory was smaller than the detail threshold of its scan, so only its
+    /// sizes and counts were kept, with no children. What's in it is all `skipped_size`.
+    pub summarized: bool,
+}
+impl AnalyzedDir {
+    /// Replaces the directory at `dir.path` somewhere in this tree with `dir`, updating the sizes
+    /// and counts of every ancestor. Returns `false` if the tree has no directory at that path.
+    pub fn splice(&mut self, dir: Self) -> bool {
+        if dir.path == self.path {
+            *self = dir;
+            return true;
+        }
+
+        let Some(child) = self.children.iter_mut().find_map(|f| match f {
+            AnalyzedItem::Dir(d) if dir.path.starts_with(&d.path) => Some(d),
+            _ => None,
+        }) else {
+            return false;
+        };
+        if !child.splice(dir) {
+            return false;
+        }
+
+        self.recount();
+        true
+    }
+
+    /// Removes the item at `path` from this tree, updating the sizes and counts of every ancestor.
+    pub fn remove_path(&mut self, path: &Path) -> Option<AnalyzedItem> {
+        if path.parent() == Some(&*self.path) {
+            let idx = self
+                .children
+                .iter()
+                .position(|f| f.name() == path.file_name())?;
+            let removed = self.children.remove(idx);
+            self.recount();
+            return Some(removed);
+        }
+
+        let child = self.children.iter_mut().find_map(|f| match f {
+            AnalyzedItem::Dir(d) if path.starts_with(&d.path) => Some(d),
+            _ => None,
+        })?;
+        let removed = child.remove_path(path)?;
+        self.recount();
+        Some(removed)
+    }
+
+    /// Builds a synthetic directory whose children are `roots`, rooted at their deepest common
+    /// ancestor so path lookups keep working.
+    pub fn combined(roots: &[Arc<Self>]) -> Self {
+        let mut path = roots.first().map_or(Path::new(""), |f| &*f.path);
+        while !roots.iter().all(|f| f.path.starts_with(path)) {
+            path = path.parent().unwrap_or(Path::new(""));
+        }
+        let mut dir = Self {
+            children: roots
+                .iter()
+                .map(|f| AnalyzedItem::Dir(Self::clone(f)))
+                .collect(),
+            path: Arc::from(path),
+            size: 0,
+            apparent_size: 0,
+            num_symlinks: 0,
+            num_files: 0,
+            num_dirs: 0,
+            modified: None,
+            skipped_size: 0,
+            skipped_apparent_size: 0,
+            skipped_files: 0,
+            truncated: false,
+            summarized: false,
+        };
+        dir.recount();
+        dir
+    }
+
+    /// Returns a copy of this tree holding only the files `keep` accepts, with directories sized
+    /// by what's left in them. Directories left with nothing are dropped.
+    pub fn filter_files(&self, keep: &impl Fn(&AnalyzedFile) -> bool) -> Self {
+        let mut dir = Self {
+            children: self
+                .children
+                .iter()
+                .filter_map(|f| match f {
+                    AnalyzedItem::Dir(d) => {
+                        let d = d.filter_files(keep);
+                        (!d.children.is_empty()).then_some(AnalyzedItem::Dir(d))
+                    }
+                    AnalyzedItem::File(f) if keep(f) => Some(AnalyzedItem::File(f.clone())),
+                    _ => None,
+                })
+                .collect(),
+            path: self.path.clone(),
+            size: 0,
+            apparent_size: 0,
+            num_symlinks: 0,
+            num_files: 0,
+            num_dirs: 0,
+            modified: self.modified,
+            skipped_size: 0,
+            skipped_apparent_size: 0,
+            skipped_files: 0,
+            truncated: false,
+            summarized: false,
+        };
+        dir.recount();
+        dir
+    }
+
+    /// Returns a copy of this tree with nothing more than `depth` levels below it. Directories at
+    /// that depth keep only their totals, as [`Self::summarized`] ones.
+    fn pruned(&self, depth: usize) -> Self {
+        let Some(depth) = depth.checked_sub(1) else {
+            return Self {
+                children: Vec::new(),
+                path: self.path.clone(),
+                size: self.size,
+                apparent_size: self.apparent_size,
+                num_symlinks: self.num_symlinks,
+                num_files: self.num_files,
+                num_dirs: self.num_dirs,
+                modified: self.modified,
+                skipped_size: self.size,
+                skipped_apparent_size: self.apparent_size,
+                skipped_files: 0,
+                truncated: false,
+                summarized: true,
+            };
+        };
+        Self {
+            children: self
+                .children
+                .iter()
+                .map(|f| match f {
+                    AnalyzedItem::Dir(d) => AnalyzedItem::Dir(d.pruned(depth)),
+                    _ => f.clone(),
+                })
+                .collect(),
+            path: self.path.clone(),
+            size: self.size,
+            apparent_size: self.apparent_size,
+            num_symlinks: self.num_symlinks,
+            num_files: self.num_files,
+            num_dirs: self.num_dirs,
+            modified: self.modified,
+            skipped_size: self.skipped_size,
+            skipped_apparent_size: self.skipped_apparent_size,
+            skipped_files: self.skipped_files,
+            truncated: self.truncated,
+            summarized: self.summarized,
+        }
+    }
+
+    /// Calls `f` with every item below this directory and its depth, 0 for direct children.
+    /// Directories come before their contents.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a AnalyzedItem, usize)) {
+        fn recurse<'a>(
+            dir: &'a AnalyzedDir,
+            depth: usize,
+            f: &mut impl FnMut(&'a AnalyzedItem, usize),
+        ) {
+            for child in &dir.children {
+                f(child, depth);
+                if let AnalyzedItem::Dir(d) = child {
+                    recurse(d, depth + 1, f);
+                }
+            }
+        }
+        recurse(self, 0, f);
+    }
+
+    /// Totals the files below this directory by extension. Files without one are left out.
+    pub fn extension_stats(&self) -> HashMap<OsString, ExtStat> {
+        let mut stats = HashMap::<OsString, ExtStat>::new();
+        self.walk(&mut |f, _| {
+            if let AnalyzedItem::File(f) = f {
+                if let Some(ext) = f.extension() {
+                    let stat = stats.entry(ext.to_os_string()).or_default();
+                    stat.bytes += f.size;
+                    stat.count += 1;
+                }
+            }
+        });
+        stats
+    }
+
+    /// Returns the directory at `path` in this tree, which may be this directory itself.
+    pub fn find_dir(&self, path: &Path) -> Option<&Self> {
+        if path == &*self.path {
+            return Some(self);
+        }
+        self.children.iter().find_map(|f| match f {
+            AnalyzedItem::Dir(d) if path.starts_with(&d.path) => d.find_dir(path),
+            _ => None,
+        })
+    }
+
+    /// Returns what's at `path` in this tree, which may be this directory itself.
+    pub fn find(&self, path: &Path) -> Option<ItemRef<'_>> {
+        if path == &*self.path {
+            return Some(ItemRef::Dir(self));
+        }
+        let name = path.file_name();
+        let in_parent = path
+            .parent()
+            .and_then(|p| self.find_dir(p))
+            .and_then(|d| d.children.iter().find(|f| f.name() == name));
+        // The roots of a combined tree aren't directly inside its path.
+        in_parent
+            .or_else(|| {
+                self.children
+                    .iter()
+                    .find(|f| matches!(f, AnalyzedItem::Dir(d) if *d.path == *path))
+            })
+            .map(ItemRef::from)
+    }
+
+    /// Returns a copy of this directory holding only what its aggregate partition element of
+    /// `count` items stands for: its smallest children by `metric` and the files too small to
+    /// have been kept.
+    pub fn tail(&self, count: usize, metric: Metric) -> Self {
+        // Ordered the same way as in `partition_into`, so the tail is what was rolled up.
+        let mut children = self.children.iter().collect::<Vec<_>>();
+        if metric != Metric::Bytes {
+            children.sort_by_key(|f| std::cmp::Reverse(metric.of(f)));
+        }
+        let kept = count.saturating_sub(self.skipped_files as usize);
+        let start = children.len().saturating_sub(kept);
+        let mut tail = Self {
+            children: children[start..].iter().map(|f| (*f).clone()).collect(),
+            path: self.path.clone(),
+            size: 0,
+            apparent_size: 0,
+            num_symlinks: 0,
+            num_files: 0,
+            num_dirs: 0,
+            modified: self.modified,
+            skipped_size: self.skipped_size,
+            skipped_apparent_size: self.skipped_apparent_size,
+            skipped_files: self.skipped_files,
+            truncated: self.truncated,
+            summarized: self.summarized,
+        };
+        tail.recount();
+        tail
+    }
+
+    /// Recomputes the size and counts of this directory from its direct children.
+    fn recount(&mut self) {
+        self.children
+            .sort_unstable_by_key(|b| std::cmp::Reverse(b.size()));
+        self.size = self.children.iter().map(AnalyzedItem::size).sum::<u64>() + self.skipped_size;
+        self.apparent_size = self
+            .children
+            .iter()
+            .map(AnalyzedItem::apparent_size)
+            .sum::<u64>()
+            + self.skipped_apparent_size;
+        self.num_symlinks = 0;
+        self.num_files = 0;
+        self.num_dirs = 0;
+        for child in &self.children {
+            match child {
+                AnalyzedItem::Dir(d) => {
+                    self.num_symlinks += d.num_symlinks;
+                    self.num_files += d.num_files;
+                    self.num_dirs += d.num_dirs + 1;
+                }
+                AnalyzedItem::File(_) => self.num_files += 1,
+                AnalyzedItem::Symlink(_) => {
+                    self.num_files += 1;
+                    self.num_symlinks += 1;
+                }
+            }
+        }
+    }
+}
+/// Something found in a tree by [`AnalyzedDir::find`]: one of its items, or the directory the
+/// tree starts at, which isn't an item of its own.
+#[derive(Debug, Clone, Copy)]
+pub enum ItemRef<'a> {
+    Dir(&'a AnalyzedDir),
+    File(&'a AnalyzedFile),
+    Symlink(&'a AnalyzedSymlink),
+}
+impl<'a> ItemRef<'a> {
+    pub const fn size(self) -> u64 {
+        match self {
+            Self::Dir(d) => d.size,
+            Self::File(f) => f.size,
+            Self::Symlink(s) => s.size,
+        }
+    }
+
+    pub const fn apparent_size(self) -> u64 {
+        match self {
+            Self::Dir(d) => d.apparent_size,
+            Self::File(f) => f.apparent_size,
+            Self::Symlink(s) => s.apparent_size,
+        }
+    }
+
+    pub const fn modified(self) -> Option<SystemTime> {
+        match self {
+            Self::Dir(d) => d.modified,
+            Self::File(f) => f.modified,
+            Self::Symlink(s) => s.modified,
+        }
+    }
+
+    pub const fn dir(self) -> Option<&'a AnalyzedDir> {
+        match self {
+            Self::Dir(d) => Some(d),
+            _ => None,
+        }
+    }
+}
+impl<'a> From<&'a AnalyzedItem> for ItemRef<'a> {
+    fn from(item: &'a AnalyzedItem) -> Self {
+        match item {
+            AnalyzedItem::Dir(d) => Self::Dir(d),
+            AnalyzedItem::File(f) => Self::File(f),
+            AnalyzedItem::Symlink(s) => Self::Symlink(s),
+        }
+    }
 }
+
+/// Bytes and number of the files with one extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExtStat {
+    pub bytes: u64,
+    pub count: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct AnalyzedFile {
     pub hardlink_count: u64,
     pub size: u64,
-    pub path: PathBuf,
+    /// Split between hard links like `size`.
+    pub apparent_size: u64,
+    pub parent: Arc<Path>,
+    pub name: Box<OsStr>,
+    pub modified: Option<SystemTime>,
+}
+impl AnalyzedFile {
+    pub fn path(&self) -> PathBuf {
+        self.parent.join(&*self.name)
+    }
+
+    pub fn extension(&self) -> Option<&OsStr> {
+        Path::new(&self.name).extension()
+    }
 }
 #[derive(Debug, Clone)]
 pub struct AnalyzedSymlink {
     pub hardlink_count: u64,
     pub size: u64,
-    pub path: PathBuf,
+    pub apparent_size: u64,
+    pub parent: Arc<Path>,
+    pub name: Box<OsStr>,
     pub link: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+impl AnalyzedSymlink {
+    pub fn path(&self) -> PathBuf {
+        self.parent.join(&*self.name)
+    }
 }
 #[derive(Debug, Clone)]
 pub enum AnalyzedItem {
@@ -44,85 +797,468 @@ impl AnalyzedItem {
         }
     }
 
+    pub const fn apparent_size(&self) -> u64 {
+        match self {
+            Self::Dir(d) => d.apparent_size,
+            Self::File(f) => f.apparent_size,
+            Self::Symlink(s) => s.apparent_size,
+        }
+    }
+
     pub fn name(&self) -> Option<&OsStr> {
         match self {
             Self::Dir(d) => d.path.file_name(),
-            Self::File(f) => f.path.file_name(),
-            Self::Symlink(s) => s.path.file_name(),
+            Self::File(f) => Some(&f.name),
+            Self::Symlink(s) => Some(&s.name),
         }
     }
 
-    pub fn path(&self) -> &Path {
+    pub const fn modified(&self) -> Option<SystemTime> {
         match self {
-            Self::Dir(d) => &d.path,
-            Self::File(f) => &f.path,
-            Self::Symlink(s) => &s.path,
+            Self::Dir(d) => d.modified,
+            Self::File(f) => f.modified,
+            Self::Symlink(s) => s.modified,
+        }
+    }
+
+    /// Number of files in this item, counting itself if it's a file or symlink.
+    pub const fn num_files(&self) -> u64 {
+        match self {
+            Self::Dir(d) => d.num_files,
+            Self::File(_) | Self::Symlink(_) => 1,
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Self::Dir(d) => d.path.to_path_buf(),
+            Self::File(f) => f.path(),
+            Self::Symlink(s) => s.path(),
+        }
+    }
+}
+
+/// The parts of a filesystem a scan reads, so it can be run against something other than the
+/// real one. Archives are always read from the real filesystem.
+pub trait FileSystem {
+    /// Paths of the entries of `dir`.
+    fn read_dir(
+        &self,
+        dir: &Path,
+    ) -> std::io::Result<impl Iterator<Item = std::io::Result<PathBuf>>>;
+    /// Metadata of what `path` points to, following symlinks.
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata>;
+    /// Metadata of `path` itself, even if it's a symlink.
+    fn symlink_metadata(&self, path: &Path) -> std::io::Result<Metadata>;
+    fn read_link(&self, path: &Path) -> std::io::Result<PathBuf>;
+}
+
+/// The filesystem the OS provides.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFs;
+impl FileSystem for RealFs {
+    fn read_dir(
+        &self,
+        dir: &Path,
+    ) -> std::io::Result<impl Iterator<Item = std::io::Result<PathBuf>>> {
+        Ok(std::fs::read_dir(dir)?.map(|f| f.map(|f| f.path())))
+    }
+
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        std::fs::metadata(path).map(Metadata::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        std::fs::symlink_metadata(path).map(Metadata::from)
+    }
+
+    fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+        std::fs::read_link(path)
+    }
+}
+
+/// What a scan needs to know about one path.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub kind: FileKind,
+    pub dev: u64,
+    pub ino: u64,
+    /// Number of 512-byte blocks allocated, or `None` if the filesystem doesn't say.
+    pub blocks: Option<u64>,
+    pub nlink: u64,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+impl Metadata {
+    /// Bytes allocated, or the length where that isn't known.
+    pub fn allocated(&self) -> u64 {
+        self.blocks.map_or(self.len, |f| f * 512)
+    }
+}
+impl From<std::fs::Metadata> for Metadata {
+    fn from(m: std::fs::Metadata) -> Self {
+        let kind = if m.is_dir() {
+            FileKind::Dir
+        } else if m.is_file() {
+            FileKind::File
+        } else if m.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        };
+        Self {
+            kind,
+            dev: m.dev(),
+            ino: m.ino(),
+            blocks: Some(m.blocks()),
+            nlink: m.nlink(),
+            len: m.len(),
+            modified: m.modified().ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileKind {
+    Dir,
+    File,
+    Symlink,
+    /// Sockets, fifos and device nodes.
+    #[default]
+    Other,
+}
+
+/// Scans `dir`, which may also be an `sftp://` URL of a directory on another machine with the
+/// `sftp` feature.
+pub fn analyze_dir(dir: &Path, ctx: &Context) -> std::io::Result<AnalyzedDir> {
+    #[cfg(feature = "sftp")]
+    if let Some(remote) = crate::sftp::Remote::parse(dir) {
+        return analyze_dir_with(&crate::sftp::SftpFs::connect(remote)?, dir, ctx);
+    }
+    analyze_dir_with(&RealFs, dir, ctx)
+}
+
+/// Like [`analyze_dir`], but reads `fs` instead of the real filesystem.
+pub fn analyze_dir_with(
+    fs: &impl FileSystem,
+    dir: &Path,
+    ctx: &Context,
+) -> std::io::Result<AnalyzedDir> {
+    #[cfg(feature = "archives")]
+    if ctx.scan_archives {
+        if let Some(dir) = analyze_in_archive(dir)? {
+            return Ok(dir);
+        }
+    }
+    let metadata = fs.metadata(dir)?;
+    ctx.counted_elsewhere(dir, &metadata);
+    if ctx.partial_depth.is_some() {
+        *ctx.partial.lock().unwrap() = Some(AnalyzedDir {
+            children: Vec::new(),
+            size: 0,
+            apparent_size: 0,
+            path: Arc::from(dir),
+            num_symlinks: 0,
+            num_files: 0,
+            num_dirs: 0,
+            modified: metadata.modified,
+            skipped_size: 0,
+            skipped_apparent_size: 0,
+            skipped_files: 0,
+            truncated: false,
+            summarized: false,
+        });
+    }
+    let totals = match ctx.detail_threshold {
+        Some(_) => Some(dir_totals(fs, dir, ctx)?),
+        None => None,
+    };
+    analyze_dir_inner(
+        fs,
+        dir,
+        metadata.modified,
+        ctx,
+        totals.as_ref(),
+        &mut vec![(metadata.dev, metadata.ino)],
+    )
+}
+
+/// What the first pass of a scan with a detail threshold keeps of a directory.
+#[derive(Debug, Default)]
+struct DirTotals {
+    size: u64,
+    apparent_size: u64,
+    num_files: u64,
+    num_dirs: u64,
+    num_symlinks: u64,
+    /// By name.
+    dirs: HashMap<OsString, DirTotals>,
+}
+
+/// Adds up `dir` like [`analyze_dir_inner`] would, keeping nothing but the totals of each
+/// directory. Entries are counted by the same rules, so a summarized directory has the counts it
+/// would have had in full. Anything that can't be read is left out, to be reported by the second
+/// pass.
+fn dir_totals(fs: &impl FileSystem, dir: &Path, ctx: &Context) -> std::io::Result<DirTotals> {
+    let mut totals = DirTotals::default();
+    for entry in fs.read_dir(dir)? {
+        ctx.checkpoint()?;
+        let Ok(path) = entry else {
+            continue;
+        };
+        let name = path.file_name().unwrap_or_default();
+        let hidden = name.as_bytes().starts_with(b".");
+        if hidden && ctx.hidden == HiddenMode::Hide {
+            continue;
+        }
+        // Collapsed entries only add to the size.
+        let collapsed = hidden && ctx.hidden == HiddenMode::Collapse;
+        let Ok(metadata) = fs.symlink_metadata(&path) else {
+            continue;
+        };
+        match metadata.kind {
+            // Reported by the second pass.
+            FileKind::Dir if ctx.counted_elsewhere(&path, &metadata).is_some() => {}
+            FileKind::Dir => match dir_totals(fs, &path, ctx) {
+                Ok(sub) => {
+                    totals.size += sub.size;
+                    totals.apparent_size += sub.apparent_size;
+                    if !collapsed {
+                        totals.num_files += sub.num_files;
+                        totals.num_dirs += sub.num_dirs + 1;
+                        totals.num_symlinks += sub.num_symlinks;
+                    }
+                    totals.dirs.insert(name.to_os_string(), sub);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => return Err(e),
+                Err(_) => {}
+            },
+            FileKind::Other => {}
+            FileKind::File if metadata.len == 0 && ctx.skip_empty_files => {}
+            FileKind::File | FileKind::Symlink => {
+                let size = metadata.allocated() / metadata.nlink;
+                let apparent_size = metadata.len / metadata.nlink;
+                let is_file = metadata.kind == FileKind::File;
+                ctx.record_file(size);
+                if (is_file && size < ctx.min_file_size) || collapsed {
+                    totals.size += size;
+                    totals.apparent_size += apparent_size;
+                    continue;
+                }
+                #[cfg(feature = "archives")]
+                if ctx.scan_archives && is_file {
+                    if let Ok(Some(entries)) = crate::archive::read(&path) {
+                        let archive = archive_dir(&path, entries, size, None);
+                        totals.size += archive.size;
+                        totals.apparent_size += archive.apparent_size;
+                        totals.num_files += archive.num_files;
+                        totals.num_dirs += archive.num_dirs + 1;
+                        continue;
+                    }
+                }
+                totals.size += size;
+                totals.apparent_size += apparent_size;
+                totals.num_files += 1;
+                if !is_file {
+                    totals.num_symlinks += 1;
+                }
+            }
         }
     }
+    Ok(totals)
 }
 
-pub fn analyze_dir(dir: &Path, _ctx: &Context) -> std::io::Result<AnalyzedDir> {
-    let entries = std::fs::read_dir(dir)?;
+/// `ancestors` holds the `(dev, ino)` of `dir` and every directory above it. `totals` is what the
+/// first pass of a scan with a detail threshold found in `dir`, whose files were already counted
+/// in the progress then.
+fn analyze_dir_inner(
+    fs: &impl FileSystem,
+    dir: &Path,
+    modified: Option<SystemTime>,
+    ctx: &Context,
+    totals: Option<&DirTotals>,
+    ancestors: &mut Vec<(u64, u64)>,
+) -> std::io::Result<AnalyzedDir> {
+    let entries = fs.read_dir(dir)?;
+    let parent: Arc<Path> = Arc::from(dir);
     let mut children = Vec::new();
     let mut num_symlinks = 0;
     let mut num_files = 0;
     let mut num_dirs = 0;
+    let mut skipped_size = 0;
+    let mut skipped_apparent_size = 0;
+    let mut skipped_files = 0;
     for entry in entries {
-        let entry = match entry {
-            Ok(e) => e,
+        ctx.checkpoint()?;
+        let path = match entry {
+            Ok(p) => p,
             Err(e) => {
-                eprintln!("Error: {e}");
+                ctx.report(ScanError::io(dir, &e));
                 continue;
             }
         };
-        let metadata = match entry.metadata() {
+        let hidden = path
+            .file_name()
+            .is_some_and(|f| f.as_bytes().starts_with(b"."));
+        if hidden && ctx.hidden == HiddenMode::Hide {
+            continue;
+        }
+        let collapsed = hidden && ctx.hidden == HiddenMode::Collapse;
+        let metadata = match fs.symlink_metadata(&path) {
             Ok(m) => m,
             Err(e) => {
-                eprintln!("Error: {e}");
+                ctx.report(ScanError::io(&path, &e));
                 continue;
             }
         };
-        let path = entry.path();
 
-        if metadata.is_dir() {
-            let analyzed = match analyze_dir(&path, _ctx) {
+        if metadata.kind == FileKind::Dir {
+            if let Some(first) = ctx.counted_elsewhere(&path, &metadata) {
+                ctx.report(ScanError {
+                    path,
+                    kind: ScanErrorKind::AlreadyCounted { first },
+                });
+                continue;
+            }
+            let sub = totals.and_then(|f| f.dirs.get(path.file_name().unwrap_or_default()));
+            if let Some(sub) = sub {
+                if collapsed {
+                    skipped_size += sub.size;
+                    skipped_apparent_size += sub.apparent_size;
+                    skipped_files += 1;
+                    continue;
+                }
+                if ctx.detail_threshold.is_some_and(|f| sub.size < f) {
+                    children.push(AnalyzedItem::Dir(AnalyzedDir {
+                        children: Vec::new(),
+                        size: sub.size,
+                        apparent_size: sub.apparent_size,
+                        path: Arc::from(path),
+                        num_symlinks: sub.num_symlinks,
+                        num_files: sub.num_files,
+                        num_dirs: sub.num_dirs,
+                        modified: metadata.modified,
+                        skipped_size: sub.size,
+                        skipped_apparent_size: sub.apparent_size,
+                        skipped_files: 0,
+                        truncated: false,
+                        summarized: true,
+                    }));
+                    num_symlinks += sub.num_symlinks;
+                    num_files += sub.num_files;
+                    num_dirs += sub.num_dirs + 1;
+                    continue;
+                }
+            }
+            ancestors.push((metadata.dev, metadata.ino));
+            let analyzed = analyze_dir_inner(fs, &path, metadata.modified, ctx, sub, ancestors);
+            ancestors.pop();
+            let analyzed = match analyzed {
                 Ok(a) => a,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => return Err(e),
                 Err(e) => {
-                    eprintln!("Error: {e}");
+                    ctx.report(ScanError::io(&path, &e));
                     continue;
                 }
             };
+            if collapsed {
+                skipped_size += analyzed.size;
+                skipped_apparent_size += analyzed.apparent_size;
+                skipped_files += 1;
+                continue;
+            }
+            // Only the directories straight in the scanned one are shown before it's done.
+            if ancestors.len() == 1 {
+                ctx.record_partial(&analyzed);
+            }
             num_symlinks += analyzed.num_symlinks;
             num_dirs += analyzed.num_dirs + 1;
             num_files += analyzed.num_files;
             children.push(AnalyzedItem::Dir(analyzed));
+        } else if metadata.kind == FileKind::Other {
+            // Sockets, fifos and device nodes take up no space of their own, and whatever size
+            // they report means something else.
+            continue;
         } else {
-            // let name = entry.file_name();
-            let hardlink_count = metadata.nlink();
-            let size = metadata.blocks() * 512 / hardlink_count;
+            let name = path.file_name().unwrap_or_default().into();
+            let modified = metadata.modified;
+            let hardlink_count = metadata.nlink;
+            let size = metadata.allocated() / hardlink_count;
+            let apparent_size = metadata.len / hardlink_count;
+            let is_file = metadata.kind == FileKind::File;
+            if is_file && metadata.len == 0 && ctx.skip_empty_files {
+                continue;
+            }
+            // Files too small to keep still add to the size, so they count as scanned.
+            if totals.is_none() {
+                ctx.record_file(size);
+            }
+            if (is_file && size < ctx.min_file_size) || collapsed {
+                skipped_size += size;
+                skipped_apparent_size += apparent_size;
+                skipped_files += 1;
+                continue;
+            }
+            #[cfg(feature = "archives")]
+            if ctx.scan_archives && is_file {
+                match crate::archive::read(&path) {
+                    Ok(Some(entries)) => {
+                        let archive = archive_dir(&path, entries, size, modified);
+                        num_dirs += archive.num_dirs + 1;
+                        num_files += archive.num_files;
+                        children.push(AnalyzedItem::Dir(archive));
+                        continue;
+                    }
+                    Ok(None) => {}
+                    // It's still counted as a plain file.
+                    Err(e) => ctx.report(ScanError::io(&path, &e)),
+                }
+            }
+            if is_file && hardlink_count > 1 {
+                ctx.record_hardlink(&metadata, &path);
+            }
             num_files += 1;
 
-            if metadata.is_symlink() {
-                let link = match std::fs::read_link(&path) {
+            if metadata.kind == FileKind::Symlink {
+                let link = match fs.read_link(&path) {
                     Ok(l) => l,
                     Err(e) => {
-                        eprintln!("Error: {e}");
+                        ctx.report(ScanError::io(&path, &e));
                         continue;
                     }
                 };
                 num_symlinks += 1;
 
+                // Symlinks aren't followed, but one that loops back on itself or an ancestor
+                // would be mis-sized by anything that does, so it's reported.
+                let cycle = match fs.metadata(&path) {
+                    Ok(m) => ancestors.contains(&(m.dev, m.ino)),
+                    Err(e) => e.raw_os_error() == Some(libc::ELOOP),
+                };
+                if cycle {
+                    ctx.report(ScanError {
+                        path: path.clone(),
+                        kind: ScanErrorKind::SymlinkCycle,
+                    });
+                }
+
                 children.push(AnalyzedItem::Symlink(AnalyzedSymlink {
                     hardlink_count,
                     size,
-                    path,
+                    apparent_size,
+                    parent: parent.clone(),
+                    name,
                     link,
+                    modified,
                 }));
             } else {
                 children.push(AnalyzedItem::File(AnalyzedFile {
                     hardlink_count,
                     size,
-                    path,
+                    apparent_size,
+                    parent: parent.clone(),
+                    name,
+                    modified,
                 }));
             }
         }
@@ -130,22 +1266,299 @@ pub fn analyze_dir(dir: &Path, _ctx: &Context) -> std::io::Result<AnalyzedDir> {
 
     children.sort_unstable_by_key(|b| std::cmp::Reverse(b.size()));
 
-    let size: u64 = children.iter().map(AnalyzedItem::size).sum();
+    let lumped = match ctx.max_entries_per_dir {
+        Some(max) if children.len() > max => children.split_off(max),
+        _ => Vec::new(),
+    };
+    let truncated = !lumped.is_empty();
+    for item in lumped {
+        skipped_size += item.size();
+        skipped_apparent_size += item.apparent_size();
+        skipped_files += 1;
+        match item {
+            AnalyzedItem::Dir(d) => {
+                num_symlinks -= d.num_symlinks;
+                num_files -= d.num_files;
+                num_dirs -= d.num_dirs + 1;
+            }
+            AnalyzedItem::File(_) => num_files -= 1,
+            AnalyzedItem::Symlink(_) => {
+                num_files -= 1;
+                num_symlinks -= 1;
+            }
+        }
+    }
+
+    let size = children.iter().map(AnalyzedItem::size).sum::<u64>() + skipped_size;
+    let apparent_size = children
+        .iter()
+        .map(AnalyzedItem::apparent_size)
+        .sum::<u64>()
+        + skipped_apparent_size;
 
     Ok(AnalyzedDir {
         children,
         size,
-        path: dir.to_owned(),
+        apparent_size,
+        path: parent,
         num_symlinks,
         num_files,
         num_dirs,
+        modified,
+        skipped_size,
+        skipped_apparent_size,
+        skipped_files,
+        truncated,
+        summarized: false,
+    })
+}
+
+/// Scans the archive `path` is, or is a directory inside of, or returns `None` if it isn't in one.
+#[cfg(feature = "archives")]
+fn analyze_in_archive(path: &Path) -> std::io::Result<Option<AnalyzedDir>> {
+    // Directories inside an archive don't exist on disk, but the archive itself does.
+    let Some(archive) = path.ancestors().find(|f| f.is_file()) else {
+        return Ok(None);
+    };
+    let metadata = std::fs::metadata(archive)?;
+    let Some(entries) = crate::archive::read(archive)? else {
+        return Ok(None);
+    };
+    let dir = archive_dir(
+        archive,
+        entries,
+        metadata.blocks() * 512,
+        metadata.modified().ok(),
+    );
+    match dir.find_dir(path) {
+        Some(d) => Ok(Some(d.clone())),
+        None => Err(std::io::ErrorKind::NotFound.into()),
+    }
+}
+
+/// Builds the directory for an archive at `path` taking up `size` bytes. What the entries don't
+/// account for, like headers and the index, is skipped size so the archive keeps its real size.
+#[cfg(feature = "archives")]
+fn archive_dir(
+    path: &Path,
+    entries: Vec<crate::archive::Entry>,
+    size: u64,
+    modified: Option<SystemTime>,
+) -> AnalyzedDir {
+    // Nothing inside an archive can be trashed on its own, so entries have no modification time
+    // for the cleanup lists to go by.
+    fn build(path: Arc<Path>, entries: Vec<crate::archive::Entry>) -> AnalyzedDir {
+        let mut children = Vec::new();
+        let mut subdirs = std::collections::BTreeMap::<OsString, Vec<_>>::new();
+        for entry in entries {
+            let mut components = entry.path.iter();
+            let Some(first) = components.next() else {
+                continue;
+            };
+            let rest = components.as_path();
+            if rest.as_os_str().is_empty() {
+                children.push(AnalyzedItem::File(AnalyzedFile {
+                    hardlink_count: 1,
+                    size: entry.size,
+                    apparent_size: entry.apparent_size,
+                    parent: path.clone(),
+                    name: first.into(),
+                    modified: None,
+                }));
+            } else {
+                let entry = crate::archive::Entry {
+                    path: rest.to_path_buf(),
+                    ..entry
+                };
+                subdirs.entry(first.to_os_string()).or_default().push(entry);
+            }
+        }
+        for (name, entries) in subdirs {
+            let dir = build(Arc::from(path.join(name)), entries);
+            children.push(AnalyzedItem::Dir(dir));
+        }
+
+        let mut dir = AnalyzedDir {
+            children,
+            path,
+            size: 0,
+            apparent_size: 0,
+            num_symlinks: 0,
+            num_files: 0,
+            num_dirs: 0,
+            modified: None,
+            skipped_size: 0,
+            skipped_apparent_size: 0,
+            skipped_files: 0,
+            truncated: false,
+            summarized: false,
+        };
+        dir.recount();
+        dir
+    }
+
+    let mut dir = build(Arc::from(path), entries);
+    dir.modified = modified;
+    dir.skipped_size = size.saturating_sub(dir.size);
+    dir.recount();
+    dir
+}
+
+/// Returns every directory below `dir` that contains no files, including directories that only
+/// contain other empty directories. Parents come before their children.
+pub fn find_empty_dirs(dir: &AnalyzedDir) -> Vec<PathBuf> {
+    // Files left out for being too small aren't in `num_files`, but still aren't nothing.
+    fn has_skipped_files(dir: &AnalyzedDir) -> bool {
+        dir.skipped_files > 0
+            || dir.children.iter().any(|f| match f {
+                AnalyzedItem::Dir(d) => has_skipped_files(d),
+                _ => false,
+            })
+    }
+
+    let mut empty = Vec::new();
+    dir.walk(&mut |f, _| {
+        if let AnalyzedItem::Dir(d) = f {
+            if d.num_files == 0 && !has_skipped_files(d) {
+                empty.push(d.path.to_path_buf());
+            }
+        }
+    });
+    empty
+}
+
+/// Returns every file below `dir` last modified before `cutoff`. Files without a readable
+/// modification time are left out.
+pub fn find_older_than(dir: &AnalyzedDir, cutoff: SystemTime) -> Vec<&AnalyzedFile> {
+    let mut old = Vec::new();
+    dir.walk(&mut |f, _| {
+        if let AnalyzedItem::File(f) = f {
+            if f.modified.is_some_and(|m| m < cutoff) {
+                old.push(f);
+            }
+        }
+    });
+    old
+}
+
+/// Differences between two scans of the same directory.
+#[derive(Debug, Clone, Default)]
+pub struct TreeDiff {
+    /// Size change of every item that grew, shrank, appeared or disappeared, keyed by path.
+    pub deltas: HashMap<PathBuf, i64>,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+impl TreeDiff {
+    pub fn delta(&self, path: &Path) -> Option<i64> {
+        self.deltas.get(path).copied()
+    }
+
+    fn add(&mut self, item: &AnalyzedItem, sign: i64) {
+        let path = item.path();
+        self.deltas.insert(path.clone(), sign * item.size() as i64);
+        if sign > 0 {
+            self.added.push(path);
+        } else {
+            self.removed.push(path);
+        }
+    }
+}
+
+pub fn diff_trees(before: &AnalyzedDir, after: &AnalyzedDir) -> TreeDiff {
+    fn recurse(before: &AnalyzedDir, after: &AnalyzedDir, diff: &mut TreeDiff) {
+        let delta = after.size as i64 - before.size as i64;
+        if delta != 0 {
+            diff.deltas.insert(after.path.to_path_buf(), delta);
+        }
+
+        let mut old = before
+            .children
+            .iter()
+            .filter_map(|f| f.name().map(|n| (n, f)))
+            .collect::<HashMap<_, _>>();
+        for new in &after.children {
+            match (new, new.name().and_then(|n| old.remove(n))) {
+                (AnalyzedItem::Dir(a), Some(AnalyzedItem::Dir(b))) => recurse(b, a, diff),
+                (_, Some(b)) => {
+                    let delta = new.size() as i64 - b.size() as i64;
+                    if delta != 0 {
+                        diff.deltas.insert(new.path(), delta);
+                    }
+                }
+                (_, None) => diff.add(new, 1),
+            }
+        }
+        for gone in old.into_values() {
+            diff.add(gone, -1);
+        }
+    }
+
+    let mut diff = TreeDiff::default();
+    recurse(before, after, &mut diff);
+    diff
+}
+
+/// Capacity of the filesystem a path lives on, in bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct FsStats {
+    pub total: u64,
+    pub used: u64,
+    /// Space available to unprivileged users.
+    pub free: u64,
+}
+
+#[allow(clippy::unnecessary_cast)]
+pub fn filesystem_stats(path: &Path) -> std::io::Result<FsStats> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid nul-terminated string and `stat` is valid for writes.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: `statvfs` succeeded, so it initialized `stat`.
+    let stat = unsafe { stat.assume_init() };
+
+    let block_size = stat.f_frsize as u64;
+    Ok(FsStats {
+        total: stat.f_blocks as u64 * block_size,
+        used: (stat.f_blocks as u64 - stat.f_bfree as u64) * block_size,
+        free: stat.f_bavail as u64 * block_size,
     })
 }
 
+/// Looks up the login name of `uid`.
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; 1024];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for writes, and `buf.len()` is the length of `buf`.
+    let ret = unsafe {
+        libc::getpwuid_r(
+            uid,
+            pwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: `getpwuid_r` found an entry, so it initialized `pwd` with `pw_name` pointing to a
+    // nul-terminated string in `buf`.
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.assume_init().pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
 pub struct PartitionElement<'a> {
     pub placement: treemap::Rect,
+    /// How much of the [`Metric`] the layout was made for the element takes up.
     pub size: u64,
     pub item: Option<&'a AnalyzedItem>,
+    /// How many children the element stands for: 1 for an item, or the number rolled into the
+    /// aggregate.
+    pub count: u64,
 }
 impl treemap::Mappable for PartitionElement<'_> {
     fn size(&self) -> f64 {
@@ -161,39 +1574,707 @@ impl treemap::Mappable for PartitionElement<'_> {
     }
 }
 
-pub fn partition(space: (f64, f64), min: f64, dir: &AnalyzedDir) -> Vec<PartitionElement> {
-    let scale = dir.size as f64 / (space.0 * space.1);
+/// Lays out the children of `dir` in `space`, sized by `metric`.
+///
+/// Children that would get less than `min` area, or whose tile would be narrower than
+/// `min_aspect` (short side over long side), are rolled into a single aggregate element with no
+/// `item`. Nothing is laid out for a directory of size zero or a space smaller than a pixel.
+pub fn partition(
+    space: (f64, f64),
+    min: f64,
+    min_aspect: f64,
+    metric: Metric,
+    dir: &AnalyzedDir,
+) -> Vec<PartitionElement<'_>> {
+    partition_into(
+        treemap::Rect::from_points(0.0, 0.0, space.0, space.1),
+        min,
+        min_aspect,
+        metric,
+        dir,
+    )
+}
+
+/// Like [`partition`], but lays the children out in `rect` rather than in a space starting at the
+/// origin, for drawing the treemap somewhere other than its own widget.
+pub fn partition_into(
+    rect: treemap::Rect,
+    min: f64,
+    min_aspect: f64,
+    metric: Metric,
+    dir: &AnalyzedDir,
+) -> Vec<PartitionElement<'_>> {
+    // Either would make the scale below zero or NaN, which the layout doesn't cope with.
+    let total = metric.of_dir(dir);
+    let area = rect.w * rect.h;
+    if total == 0 || area.is_nan() || area < 1.0 {
+        return Vec::new();
+    }
+
+    // Children are kept sorted by bytes, so other metrics need their own order.
+    let mut children = dir.children.iter().collect::<Vec<_>>();
+    if metric != Metric::Bytes {
+        children.sort_by_key(|f| std::cmp::Reverse(metric.of(f)));
+    }
+
+    let scale = total as f64 / area;
     let min_area = (min * scale) as u64;
-    let end_index = dir
-        .children
+    let mut end_index = children
         .iter()
         .enumerate()
-        .find(|f| f.1.size() < min_area)
+        .find(|f| metric.of(f.1) < min_area)
         .map(|f| f.0);
 
-    let mut items = Vec::with_capacity(end_index.map_or(dir.children.len(), |f| f + 2));
+    loop {
+        let items = layout_children(rect, dir, &children, metric, end_index);
+
+        // Children are laid out largest first, so everything after the first sliver is rolled
+        // up along with it. The end only ever moves back, so this terminates.
+        let end = end_index.unwrap_or(children.len());
+        match items[..end]
+            .iter()
+            .position(|f| aspect(&f.placement) < min_aspect)
+        {
+            Some(i) if i > 0 => end_index = Some(i),
+            _ => return items,
+        }
+    }
+}
+
+fn aspect(rect: &treemap::Rect) -> f64 {
+    let long = rect.w.max(rect.h);
+    if long > 0.0 {
+        rect.w.min(rect.h) / long
+    } else {
+        1.0
+    }
+}
+
+/// Lays out the `children` of `dir` before `end_index` individually, and the rest as one
+/// aggregate.
+fn layout_children<'a>(
+    rect: treemap::Rect,
+    dir: &AnalyzedDir,
+    children: &[&'a AnalyzedItem],
+    metric: Metric,
+    end_index: Option<usize>,
+) -> Vec<PartitionElement<'a>> {
+    let mut items = Vec::with_capacity(end_index.map_or(children.len(), |f| f + 2));
     let mut accum = 0;
-    for ele in &dir.children[0..end_index.unwrap_or(dir.children.len())] {
+    for &ele in &children[0..end_index.unwrap_or(children.len())] {
         items.push(PartitionElement {
             placement: treemap::Rect::default(),
-            size: ele.size(),
+            size: metric.of(ele),
             item: Some(ele),
+            count: 1,
         });
-        accum += ele.size();
+        accum += metric.of(ele);
     }
-    if end_index.is_some() {
+    // Files too small to have been kept go in the aggregate too.
+    let skipped = match metric {
+        Metric::Bytes => dir.skipped_size,
+        Metric::FileCount => dir.skipped_files,
+    };
+    if end_index.is_some() || skipped > 0 {
+        let end_index = end_index.unwrap_or(children.len());
         items.push(PartitionElement {
             placement: treemap::Rect::default(),
-            size: dir.size - accum,
+            size: metric.of_dir(dir).saturating_sub(accum),
             item: None,
+            count: (children.len() - end_index) as u64 + dir.skipped_files,
         });
     }
 
     let layout = treemap::TreemapLayout::new();
     // let aspect = space.0 / space.1;
     // let height = (sz / aspect).sqrt();
-    let bounds = treemap::Rect::from_points(0.0, 0.0, space.0, space.1);
-    layout.layout_items(&mut items, bounds);
+    layout.layout_items(&mut items, rect);
 
     items
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mem_fs::MemFs;
+
+    fn scan(fs: &MemFs, ctx: &Context) -> AnalyzedDir {
+        analyze_dir_with(fs, Path::new("/r"), ctx).unwrap()
+    }
+
+    /// Sizes are in 4 KiB blocks: `big` takes 12 KiB, `sub/a` 8 KiB, `sub/deeper/b` 4 KiB and
+    /// `.hidden/c` 20 KiB. The symlink takes no blocks.
+    fn tree() -> MemFs {
+        MemFs::new()
+            .file("/r/big", 10_000)
+            .file("/r/sub/a", 5_000)
+            .file("/r/sub/deeper/b", 100)
+            .symlink("/r/link", "big")
+            .file("/r/.hidden/c", 20_000)
+    }
+
+    #[test]
+    fn counts_and_sizes() {
+        let dir = scan(&tree(), &Context::default());
+
+        assert_eq!(dir.num_files, 5);
+        assert_eq!(dir.num_dirs, 3);
+        assert_eq!(dir.num_symlinks, 1);
+        assert_eq!(dir.size, 45_056);
+        assert_eq!(dir.apparent_size, 35_103);
+        assert!(dir.children.windows(2).all(|f| f[0].size() >= f[1].size()));
+
+        let Some(ItemRef::Dir(sub)) = dir.find(Path::new("/r/sub")) else {
+            panic!("no /r/sub");
+        };
+        assert_eq!(sub.size, 12_288);
+        assert_eq!(sub.num_files, 2);
+        assert_eq!(sub.num_dirs, 1);
+    }
+
+    #[test]
+    fn walk_visits_every_item_once() {
+        let dir = scan(&tree(), &Context::default());
+        let mut seen = Vec::new();
+        dir.walk(&mut |f, depth| seen.push((f.path(), depth)));
+
+        let position = |path: &str| seen.iter().position(|f| f.0 == Path::new(path)).unwrap();
+        assert!(position("/r/sub") < position("/r/sub/deeper"));
+        assert!(position("/r/sub/deeper") < position("/r/sub/deeper/b"));
+
+        seen.sort();
+        let expected = [
+            ("/r/.hidden", 0),
+            ("/r/.hidden/c", 1),
+            ("/r/big", 0),
+            ("/r/link", 0),
+            ("/r/sub", 0),
+            ("/r/sub/a", 1),
+            ("/r/sub/deeper", 1),
+            ("/r/sub/deeper/b", 2),
+        ]
+        .map(|(path, depth)| (PathBuf::from(path), depth));
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn find_paths() {
+        let dir = scan(&tree(), &Context::default());
+
+        assert!(matches!(dir.find(Path::new("/r")), Some(ItemRef::Dir(d)) if d.size == dir.size));
+        assert!(matches!(
+            dir.find(Path::new("/r/sub/deeper")),
+            Some(ItemRef::Dir(d)) if d.num_files == 1
+        ));
+        assert!(matches!(
+            dir.find(Path::new("/r/sub/deeper/b")),
+            Some(ItemRef::File(f)) if &*f.name == "b"
+        ));
+        assert!(matches!(
+            dir.find(Path::new("/r/link")),
+            Some(ItemRef::Symlink(_))
+        ));
+        assert!(dir.find(Path::new("/r/sub/missing")).is_none());
+        assert!(dir.find(Path::new("/r/missing/b")).is_none());
+        assert!(dir.find(Path::new("/elsewhere/big")).is_none());
+        assert!(dir.find(Path::new("/")).is_none());
+    }
+
+    #[test]
+    fn tail_follows_metric() {
+        let fs = MemFs::new()
+            .file("/r/big", 100_000)
+            .file("/r/mid", 20_000)
+            .file("/r/many/1", 1)
+            .file("/r/many/2", 1)
+            .file("/r/many/3", 1)
+            .file("/r/many/4", 1)
+            .file("/r/small", 1);
+        let dir = scan(&fs, &Context::default());
+        let names = |tail: &AnalyzedDir| {
+            let mut names = tail
+                .children
+                .iter()
+                .filter_map(|f| f.name()?.to_str())
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let by_bytes = dir.tail(2, Metric::Bytes);
+        assert_eq!(names(&by_bytes), ["many", "small"]);
+        assert_eq!(by_bytes.size, 20_480);
+
+        let by_count = dir.tail(2, Metric::FileCount);
+        assert_eq!(names(&by_count), ["mid", "small"]);
+        assert_eq!(by_count.size, 24_576);
+    }
+
+    #[test]
+    fn extension_totals() {
+        let fs = MemFs::new()
+            .file("/r/a.txt", 1)
+            .file("/r/b.TXT", 1)
+            .file("/r/d/c.txt", 5_000)
+            .file("/r/d/e/f.rs", 1)
+            .file("/r/Makefile", 1)
+            .file("/r/.bashrc", 1);
+        let stats = scan(&fs, &Context::default()).extension_stats();
+
+        let stat = |ext: &str| stats.get(OsStr::new(ext)).copied();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            stat("txt"),
+            Some(ExtStat {
+                bytes: 12_288,
+                count: 2
+            })
+        );
+        assert_eq!(stat("TXT").map(|f| f.count), Some(1));
+        assert_eq!(
+            stat("rs"),
+            Some(ExtStat {
+                bytes: 4096,
+                count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn hidden_entries() {
+        let hide = scan(&tree(), &Context::default().with_hidden(HiddenMode::Hide));
+        assert_eq!(hide.num_files, 4);
+        assert_eq!(hide.num_dirs, 2);
+        assert_eq!(hide.size, 24_576);
+        assert_eq!(hide.skipped_files, 0);
+
+        let collapse = scan(
+            &tree(),
+            &Context::default().with_hidden(HiddenMode::Collapse),
+        );
+        assert_eq!(collapse.num_files, 4);
+        assert_eq!(collapse.num_dirs, 2);
+        assert_eq!(collapse.size, 45_056);
+        assert_eq!(collapse.skipped_size, 20_480);
+        assert_eq!(collapse.skipped_files, 1);
+        assert!(collapse.find(Path::new("/r/.hidden")).is_none());
+    }
+
+    #[test]
+    fn hidden_entries_with_detail_threshold() {
+        for threshold in [Some(0), Some(8192), Some(u64::MAX)] {
+            let ctx = Context::default().with_detail_threshold(threshold);
+            let hide = scan(&tree(), &ctx.with_hidden(HiddenMode::Hide));
+            assert_eq!((hide.size, hide.num_files), (24_576, 4), "{threshold:?}");
+
+            let ctx = Context::default().with_detail_threshold(threshold);
+            let collapse = scan(&tree(), &ctx.with_hidden(HiddenMode::Collapse));
+            assert_eq!(collapse.size, 45_056, "{threshold:?}");
+            assert_eq!(collapse.num_files, 4, "{threshold:?}");
+            assert_eq!(collapse.skipped_size, 20_480, "{threshold:?}");
+            assert_eq!(collapse.skipped_files, 1, "{threshold:?}");
+        }
+    }
+
+    #[test]
+    fn summarized_dirs_count_like_detailed_ones() {
+        let fs = tree()
+            .file("/r/sub/tiny", 10)
+            .file("/r/sub/.dot", 100)
+            .symlink("/r/sub/deeper/up", "..");
+        let contexts: [fn() -> Context; 3] = [
+            Context::default,
+            || Context::default().with_min_file_size(8192),
+            || Context::default().with_hidden(HiddenMode::Collapse),
+        ];
+        let counts = |d: &AnalyzedDir| {
+            (
+                d.size,
+                d.apparent_size,
+                d.num_files,
+                d.num_dirs,
+                d.num_symlinks,
+            )
+        };
+        for (i, ctx) in contexts.iter().enumerate() {
+            let detailed = counts(&scan(&fs, &ctx()));
+            for threshold in [8192, u64::MAX] {
+                let summarized = scan(&fs, &ctx().with_detail_threshold(Some(threshold)));
+                assert_eq!(counts(&summarized), detailed, "context {i}, {threshold}");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_files() {
+        let fs = MemFs::new().file("/r/empty", 0).file("/r/full", 1);
+
+        let kept = scan(&fs, &Context::default());
+        assert_eq!(kept.num_files, 2);
+
+        let skipped = scan(&fs, &Context::default().with_include_empty_files(false));
+        assert_eq!(skipped.num_files, 1);
+        assert_eq!(skipped.skipped_files, 0);
+        assert!(skipped.find(Path::new("/r/empty")).is_none());
+        assert!(skipped.find(Path::new("/r/full")).is_some());
+    }
+
+    #[test]
+    fn sparse_file_sizes() {
+        let fs = MemFs::new()
+            .sparse_file("/r/d/sparse", 1 << 30, 8)
+            .file("/r/d/dense", 4096);
+        let dir = scan(&fs, &Context::default());
+
+        let sparse = dir.find(Path::new("/r/d/sparse")).unwrap();
+        assert_eq!(sparse.size(), 4096);
+        assert_eq!(sparse.apparent_size(), 1 << 30);
+        assert_eq!(dir.size, 8192);
+        assert_eq!(dir.apparent_size, (1 << 30) + 4096);
+    }
+
+    #[test]
+    fn unknown_blocks_count_the_length() {
+        let fs = MemFs::new()
+            .file_without_blocks("/r/remote", 1000)
+            .file("/r/local", 1000)
+            .file_without_blocks("/r/d/remote", 3000);
+        let dir = scan(&fs, &Context::default());
+        assert_eq!(dir.find(Path::new("/r/remote")).unwrap().size(), 1000);
+        assert_eq!(dir.find(Path::new("/r/local")).unwrap().size(), 4096);
+        assert_eq!(dir.size, 8096);
+
+        // Directories under the threshold are only added up, which goes by the length too.
+        let dir = scan(
+            &fs,
+            &Context::default().with_detail_threshold(Some(1 << 20)),
+        );
+        assert_eq!(dir.find_dir(Path::new("/r/d")).unwrap().size, 3000);
+        assert_eq!(dir.size, 8096);
+    }
+
+    #[test]
+    fn hard_links_are_grouped() {
+        let fs = MemFs::new()
+            .file("/r/a", 100)
+            .hard_link("/r/d/b", "/r/a")
+            .hard_link("/r/e/c", "/r/a")
+            .file("/r/alone", 100);
+        let ctx = Context::default();
+        scan(&fs, &ctx);
+
+        let links = ctx.take_hardlinks();
+        let paths = ["/r/a", "/r/d/b", "/r/e/c"].map(PathBuf::from);
+        for path in &paths {
+            assert_eq!(links.of(path), Some(&paths[..]));
+        }
+        assert_eq!(links.of(Path::new("/r/alone")), None);
+    }
+
+    #[test]
+    fn fifos_are_skipped() {
+        let fs = MemFs::new().fifo("/r/pipe").file("/r/f", 1);
+        let ctx = Context::default();
+        let dir = scan(&fs, &ctx);
+
+        assert_eq!(dir.num_files, 1);
+        assert_eq!(dir.skipped_files, 0);
+        assert_eq!(dir.size, 4096);
+        assert!(dir.find(Path::new("/r/pipe")).is_none());
+        assert!(ctx.take_errors().is_empty());
+    }
+
+    #[test]
+    fn small_files_are_lumped() {
+        let fs = MemFs::new()
+            .file("/r/big", 10_000)
+            .file("/r/small1", 10)
+            .file("/r/small2", 10);
+        let dir = scan(&fs, &Context::default().with_min_file_size(8192));
+
+        assert_eq!(dir.children.len(), 1);
+        assert_eq!(dir.num_files, 1);
+        assert_eq!(dir.skipped_files, 2);
+        assert_eq!(dir.skipped_size, 8192);
+        assert_eq!(dir.skipped_apparent_size, 20);
+        // The lump keeps the total what it would be without the threshold.
+        assert_eq!(dir.size, scan(&fs, &Context::default()).size);
+    }
+
+    #[test]
+    fn skipped_empty_files_are_not_counted_as_scanned() {
+        let fs = MemFs::new()
+            .file("/r/empty", 0)
+            .file("/r/full", 1)
+            .file("/r/d/empty", 0)
+            .file("/r/d/full", 1);
+
+        for threshold in [None, Some(u64::MAX)] {
+            let ctx = Context::default()
+                .with_include_empty_files(false)
+                .with_detail_threshold(threshold);
+            let dir = scan(&fs, &ctx);
+            assert_eq!(ctx.progress().files, 2);
+            assert_eq!(ctx.progress().bytes, 8192);
+            assert_eq!(dir.num_files, 2);
+        }
+    }
+
+    #[test]
+    fn diff_added_removed_and_resized() {
+        let before = MemFs::new()
+            .file("/r/grown", 1)
+            .file("/r/same", 1)
+            .file("/r/d/gone", 1);
+        let after = MemFs::new()
+            .file("/r/grown", 5_000)
+            .file("/r/same", 1)
+            .file("/r/d/new", 1);
+        let diff = diff_trees(
+            &scan(&before, &Context::default()),
+            &scan(&after, &Context::default()),
+        );
+
+        assert_eq!(diff.added, [Path::new("/r/d/new")]);
+        assert_eq!(diff.removed, [Path::new("/r/d/gone")]);
+        assert_eq!(diff.delta(Path::new("/r/grown")), Some(4096));
+        assert_eq!(diff.delta(Path::new("/r/d/new")), Some(4096));
+        assert_eq!(diff.delta(Path::new("/r/d/gone")), Some(-4096));
+        assert_eq!(diff.delta(Path::new("/r")), Some(4096));
+        assert_eq!(diff.delta(Path::new("/r/d")), None);
+        assert_eq!(diff.delta(Path::new("/r/same")), None);
+    }
+
+    /// A directory of files `f0`, `f1`, ... of each of `lens`.
+    fn files(lens: &[u64]) -> AnalyzedDir {
+        let fs = lens
+            .iter()
+            .enumerate()
+            .fold(MemFs::new().dir("/r"), |fs, (i, len)| {
+                fs.file(&format!("/r/f{i}"), *len)
+            });
+        scan(&fs, &Context::default())
+    }
+
+    fn overlap(a: &treemap::Rect, b: &treemap::Rect) -> f64 {
+        let w = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+        let h = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+        w.max(0.0) * h.max(0.0)
+    }
+
+    /// Checks that `elements` fill `rect` without overlapping each other.
+    fn assert_tiles(elements: &[PartitionElement], rect: treemap::Rect) {
+        let area = elements
+            .iter()
+            .map(|f| f.placement.w * f.placement.h)
+            .sum::<f64>();
+        assert!((area - rect.w * rect.h).abs() < 1e-6 * rect.w * rect.h);
+        for (i, a) in elements.iter().enumerate() {
+            for b in &elements[i + 1..] {
+                assert!(overlap(&a.placement, &b.placement) < 1e-6);
+            }
+        }
+    }
+
+    const LENS: [u64; 8] = [400_000, 200_000, 100_000, 50_000, 20_000, 8_000, 4_000, 100];
+
+    #[test]
+    fn partition_empty_dir() {
+        assert!(partition((100.0, 100.0), 0.0, 0.0, Metric::Bytes, &files(&[])).is_empty());
+    }
+
+    #[test]
+    fn partition_zero_size_and_tiny_space() {
+        let empty = files(&[0, 0]);
+        assert_eq!(empty.size, 0);
+        assert!(partition((100.0, 100.0), 0.0, 0.0, Metric::Bytes, &empty).is_empty());
+        // The files are still there to count.
+        assert_eq!(
+            partition((100.0, 100.0), 0.0, 0.0, Metric::FileCount, &empty).len(),
+            2
+        );
+
+        let dir = files(&LENS);
+        for space in [(0.0, 0.0), (0.5, 1.0), (100.0, 0.0), (f64::NAN, 1.0)] {
+            assert!(partition(space, 0.0, 0.0, Metric::Bytes, &dir).is_empty());
+        }
+        let elements = partition((1.0, 1.0), 0.0, 0.0, Metric::Bytes, &dir);
+        assert!(!elements.is_empty());
+        assert!(elements
+            .iter()
+            .all(|f| f.placement.w.is_finite() && f.placement.h.is_finite()));
+        assert_tiles(&elements, treemap::Rect::from_points(0.0, 0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn partition_fills_space() {
+        let dir = files(&LENS);
+        let elements = partition((300.0, 200.0), 0.0, 0.0, Metric::Bytes, &dir);
+
+        assert_eq!(elements.len(), LENS.len());
+        assert!(elements.iter().all(|f| f.item.is_some() && f.count == 1));
+        assert_tiles(
+            &elements,
+            treemap::Rect::from_points(0.0, 0.0, 300.0, 200.0),
+        );
+    }
+
+    #[test]
+    fn partition_into_stays_in_rect() {
+        let rect = treemap::Rect::from_points(50.0, 20.0, 250.0, 120.0);
+        let dir = files(&LENS);
+        let elements = partition_into(rect, 0.0, 0.0, Metric::Bytes, &dir);
+
+        assert_eq!(elements.len(), LENS.len());
+        let eps = 1e-6;
+        for f in &elements {
+            let r = f.placement;
+            assert!(r.x >= rect.x - eps && r.y >= rect.y - eps);
+            assert!(r.x + r.w <= rect.x + rect.w + eps && r.y + r.h <= rect.y + rect.h + eps);
+        }
+        assert_tiles(&elements, rect);
+    }
+
+    #[test]
+    fn partition_aggregates_small_and_narrow_tiles() {
+        let dir = files(&LENS);
+        let (min, min_aspect) = (1000.0, 0.2);
+        let elements = partition((300.0, 200.0), min, min_aspect, Metric::Bytes, &dir);
+
+        assert_tiles(
+            &elements,
+            treemap::Rect::from_points(0.0, 0.0, 300.0, 200.0),
+        );
+        let (aggregate, kept) = elements.split_last().unwrap();
+        assert!(aggregate.item.is_none());
+        assert_eq!(kept.len() as u64 + aggregate.count, LENS.len() as u64);
+        assert_eq!(
+            elements.iter().map(|f| f.size).sum::<u64>(),
+            Metric::Bytes.of_dir(&dir)
+        );
+        for (i, element) in kept.iter().enumerate() {
+            assert!(element.item.is_some());
+            assert!(element.placement.w * element.placement.h >= min * 0.99);
+            // The largest child is kept however narrow, since there's nothing to roll it into.
+            if i > 0 {
+                assert!(aspect(&element.placement) >= min_aspect);
+            }
+        }
+    }
+
+    #[test]
+    fn partition_all_small_is_one_aggregate() {
+        let dir = files(&LENS[2..]);
+        let elements = partition((100.0, 50.0), 5000.0, 0.0, Metric::Bytes, &dir);
+
+        assert_eq!(elements.len(), 1);
+        assert!(elements[0].item.is_none());
+        assert_eq!(elements[0].count, LENS.len() as u64 - 2);
+        assert_eq!(elements[0].size, dir.size);
+        assert_tiles(&elements, treemap::Rect::from_points(0.0, 0.0, 100.0, 50.0));
+    }
+
+    #[test]
+    fn partition_by_file_count() {
+        let fs = MemFs::new()
+            .file("/r/big", 1_000_000)
+            .file("/r/many/1", 1)
+            .file("/r/many/2", 1)
+            .file("/r/many/3", 1);
+        let dir = scan(&fs, &Context::default());
+        let elements = partition((100.0, 100.0), 0.0, 0.0, Metric::FileCount, &dir);
+
+        let names = elements
+            .iter()
+            .map(|f| f.item.and_then(AnalyzedItem::name).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["many", "big"]);
+        assert_eq!(elements.iter().map(|f| f.size).collect::<Vec<_>>(), [3, 1]);
+        assert_tiles(
+            &elements,
+            treemap::Rect::from_points(0.0, 0.0, 100.0, 100.0),
+        );
+    }
+
+    #[test]
+    fn synthetic_tree() {
+        let dir = scan(&MemFs::synthetic(1000), &Context::default());
+        assert_eq!(dir.num_files, 1000);
+        // 50 directories of 20 files, every one but `/r` itself below it.
+        assert_eq!(dir.num_dirs, 49);
+    }
+
+    #[test]
+    fn symlink_to_ancestor_is_a_cycle() {
+        let fs = MemFs::new().file("/r/a/f", 10).symlink("/r/a/up", "/r");
+        let ctx = Context::default();
+        let dir = scan(&fs, &ctx);
+
+        let errors = ctx.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, Path::new("/r/a/up"));
+        assert!(matches!(errors[0].kind, ScanErrorKind::SymlinkCycle));
+        // It's kept, just not followed.
+        assert_eq!(dir.num_symlinks, 1);
+        assert_eq!(dir.num_files, 2);
+    }
+
+    #[test]
+    fn symlink_loop_is_a_cycle() {
+        let fs = MemFs::new().symlink("/r/a", "b").symlink("/r/b", "a");
+        let ctx = Context::default();
+        let dir = scan(&fs, &ctx);
+
+        let mut cycles = ctx
+            .take_errors()
+            .into_iter()
+            .filter(|f| matches!(f.kind, ScanErrorKind::SymlinkCycle))
+            .map(|f| f.path)
+            .collect::<Vec<_>>();
+        cycles.sort();
+        assert_eq!(cycles, [Path::new("/r/a"), Path::new("/r/b")]);
+        assert_eq!(dir.num_symlinks, 2);
+    }
+
+    #[test]
+    fn unreadable_dir_is_reported_and_skipped() {
+        let fs = MemFs::new()
+            .file("/r/ok/f", 4096)
+            .dir("/r/locked")
+            .deny("/r/locked");
+        let ctx = Context::default();
+        let dir = scan(&fs, &ctx);
+
+        let errors = ctx.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, Path::new("/r/locked"));
+        assert!(matches!(
+            errors[0].kind,
+            ScanErrorKind::Io {
+                kind: std::io::ErrorKind::PermissionDenied,
+                ..
+            }
+        ));
+        assert_eq!(dir.num_dirs, 1);
+        assert_eq!(dir.num_files, 1);
+        assert_eq!(dir.size, 4096);
+        assert!(dir.find(Path::new("/r/locked")).is_none());
+    }
+
+    #[test]
+    fn hard_links_are_counted_once() {
+        let fs = MemFs::new().file("/r/a", 8192).hard_link("/r/b", "/r/a");
+        let ctx = Context::default();
+        let dir = scan(&fs, &ctx);
+
+        assert_eq!(dir.size, 8192);
+        assert_eq!(dir.apparent_size, 8192);
+        for child in &dir.children {
+            assert_eq!(child.size(), 4096);
+        }
+        let links = ctx.take_hardlinks();
+        let paths = [PathBuf::from("/r/a"), PathBuf::from("/r/b")];
+        assert_eq!(links.of(Path::new("/r/b")), Some(&paths[..]));
+    }
+}
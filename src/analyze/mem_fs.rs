@@ -0,0 +1,206 @@
+//! A filesystem held in memory, for testing scans without touching the real one.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{FileKind, FileSystem, Metadata};
+
+#[derive(Debug, Clone)]
+enum Node {
+    Dir,
+    File { len: u64, blocks: Option<u64> },
+    Symlink(PathBuf),
+    Other,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    ino: u64,
+    node: Node,
+}
+
+/// Built up from absolute paths, with missing parent directories made along the way. Everything
+/// is on one device and nothing has a modification time. Symlink targets are absolute or relative
+/// to the symlink's directory, without `..`.
+#[derive(Debug, Default)]
+pub struct MemFs {
+    entries: BTreeMap<PathBuf, Entry>,
+    /// Number of paths of each inode.
+    links: HashMap<u64, u64>,
+    denied: Vec<PathBuf>,
+    next_ino: u64,
+}
+impl MemFs {
+    /// A filesystem with nothing but `/` in it.
+    pub fn new() -> Self {
+        let mut fs = Self::default();
+        fs.insert(Path::new("/"), Node::Dir);
+        fs
+    }
+
+    pub fn dir(mut self, path: &str) -> Self {
+        self.insert(Path::new(path), Node::Dir);
+        self
+    }
+
+    /// A file of `len` bytes, allocated in 4 KiB blocks.
+    pub fn file(self, path: &str, len: u64) -> Self {
+        self.sparse_file(path, len, len.div_ceil(4096) * 8)
+    }
+
+    /// A file of `len` bytes with only `blocks` 512-byte blocks allocated.
+    pub fn sparse_file(mut self, path: &str, len: u64, blocks: u64) -> Self {
+        let blocks = Some(blocks);
+        self.insert(Path::new(path), Node::File { len, blocks });
+        self
+    }
+
+    /// A file of `len` bytes on a filesystem that doesn't say how many blocks it takes, like SFTP.
+    pub fn file_without_blocks(mut self, path: &str, len: u64) -> Self {
+        let blocks = None;
+        self.insert(Path::new(path), Node::File { len, blocks });
+        self
+    }
+
+    /// Another link to the file at `original`.
+    pub fn hard_link(mut self, path: &str, original: &str) -> Self {
+        let entry = self.entries[Path::new(original)].clone();
+        self.insert_entry(Path::new(path), entry);
+        self
+    }
+
+    pub fn symlink(mut self, path: &str, target: &str) -> Self {
+        self.insert(Path::new(path), Node::Symlink(PathBuf::from(target)));
+        self
+    }
+
+    pub fn fifo(mut self, path: &str) -> Self {
+        self.insert(Path::new(path), Node::Other);
+        self
+    }
+
+    /// A tree under `/r` of `files` files of assorted sizes and extensions, 20 to a directory
+    /// and 8 directories to a directory. It's the same every time for the same `files`.
+    pub fn synthetic(files: usize) -> Self {
+        const FILES_PER_DIR: usize = 20;
+        const SUBDIRS: usize = 8;
+        const EXTENSIONS: [&str; 6] = ["txt", "rs", "png", "o", "json", "tar"];
+
+        let mut fs = Self::new().dir("/r");
+        let mut dirs = vec![PathBuf::from("/r")];
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for i in 0..files {
+            let dir = i / FILES_PER_DIR;
+            while dirs.len() <= dir {
+                let parent = &dirs[(dirs.len() - 1) / SUBDIRS];
+                let path = parent.join(format!("d{}", dirs.len()));
+                dirs.push(path);
+            }
+            // Xorshift, for sizes that look random without pulling in a crate for it.
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            // Mostly small files and a few huge ones, like most real trees.
+            let len = (seed % 64).pow(4);
+            let ext = EXTENSIONS[(seed >> 32) as usize % EXTENSIONS.len()];
+            let path = dirs[dir].join(format!("f{i}.{ext}"));
+            fs = fs.file(&path.to_string_lossy(), len);
+        }
+        fs
+    }
+
+    /// Makes reading the directory at `path` fail as if it weren't readable.
+    pub fn deny(mut self, path: &str) -> Self {
+        self.denied.push(PathBuf::from(path));
+        self
+    }
+
+    fn insert(&mut self, path: &Path, node: Node) {
+        self.next_ino += 1;
+        let entry = Entry {
+            ino: self.next_ino,
+            node,
+        };
+        self.insert_entry(path, entry);
+    }
+
+    fn insert_entry(&mut self, path: &Path, entry: Entry) {
+        if let Some(parent) = path.parent() {
+            if !self.entries.contains_key(parent) {
+                self.insert(parent, Node::Dir);
+            }
+        }
+        *self.links.entry(entry.ino).or_default() += 1;
+        self.entries.insert(path.to_path_buf(), entry);
+    }
+
+    fn get(&self, path: &Path) -> io::Result<&Entry> {
+        self.entries
+            .get(path)
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn metadata_of(&self, entry: &Entry) -> Metadata {
+        let (kind, len, blocks) = match &entry.node {
+            Node::Dir => (FileKind::Dir, 4096, Some(8)),
+            Node::File { len, blocks } => (FileKind::File, *len, *blocks),
+            Node::Symlink(target) => (FileKind::Symlink, target.as_os_str().len() as u64, Some(0)),
+            Node::Other => (FileKind::Other, 0, Some(0)),
+        };
+        Metadata {
+            kind,
+            dev: 1,
+            ino: entry.ino,
+            blocks,
+            nlink: self.links[&entry.ino],
+            len,
+            modified: None,
+        }
+    }
+}
+impl FileSystem for MemFs {
+    fn read_dir(&self, dir: &Path) -> io::Result<impl Iterator<Item = io::Result<PathBuf>>> {
+        if self.denied.iter().any(|f| f == dir) {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        if !matches!(self.get(dir)?.node, Node::Dir) {
+            return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+        }
+        let entries = self
+            .entries
+            .range(dir.to_path_buf()..)
+            .map(|f| f.0)
+            .take_while(|f| f.starts_with(dir))
+            .filter(|f| f.parent() == Some(dir))
+            .cloned()
+            .collect::<Vec<_>>();
+        Ok(entries.into_iter().map(Ok))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let mut path = path.to_path_buf();
+        // As many links as Linux follows before giving up.
+        for _ in 0..40 {
+            let entry = self.get(&path)?;
+            match &entry.node {
+                Node::Symlink(target) => path = path.parent().unwrap_or(&path).join(target),
+                _ => return Ok(self.metadata_of(entry)),
+            }
+        }
+        Err(io::Error::from_raw_os_error(libc::ELOOP))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        Ok(self.metadata_of(self.get(path)?))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        match &self.get(path)?.node {
+            Node::Symlink(target) => Ok(target.clone()),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+}
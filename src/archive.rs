@@ -0,0 +1,91 @@
+use std::{
+    fs::File,
+    path::{Component, Path, PathBuf},
+};
+
+/// A file inside an archive.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Relative to the root of the archive, with anything leading out of it dropped.
+    pub path: PathBuf,
+    /// Bytes the entry takes up in the archive.
+    pub size: u64,
+    /// Bytes the entry takes up once extracted.
+    pub apparent_size: u64,
+}
+
+/// Whether `path` is named like an archive that [`read`] can list.
+pub fn is_archive(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+enum Kind {
+    Zip,
+    Tar,
+}
+
+fn kind(path: &Path) -> Option<Kind> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match &*ext {
+        "zip" => Some(Kind::Zip),
+        "tar" => Some(Kind::Tar),
+        _ => None,
+    }
+}
+
+/// Lists the files in the archive at `path` without extracting them, or returns `None` if it
+/// isn't named like a supported archive.
+pub fn read(path: &Path) -> std::io::Result<Option<Vec<Entry>>> {
+    match kind(path) {
+        Some(Kind::Zip) => read_zip(path).map(Some),
+        Some(Kind::Tar) => read_tar(path).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn read_zip(path: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(std::io::Error::other)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        // Only the central directory is read, so nothing is decompressed.
+        let file = archive.by_index_raw(i).map_err(std::io::Error::other)?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(name) = file.enclosed_name() else {
+            continue;
+        };
+        entries.push(Entry {
+            path: name,
+            size: file.compressed_size(),
+            apparent_size: file.size(),
+        });
+    }
+    Ok(entries)
+}
+
+fn read_tar(path: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut archive = tar::Archive::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry
+            .path()?
+            .components()
+            .filter(|f| matches!(f, Component::Normal(_)))
+            .collect::<PathBuf>();
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let size = entry.header().size()?;
+        entries.push(Entry {
+            path,
+            size,
+            apparent_size: size,
+        });
+    }
+    Ok(entries)
+}
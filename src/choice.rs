@@ -0,0 +1,17 @@
+//! Settings picked from a fixed list of values, like the ones in dropdowns.
+
+/// A setting with a fixed set of values, each with a label to pick it by.
+pub trait Choice: Copy + PartialEq + 'static {
+    /// Every value, in the order they're listed.
+    const ALL: &'static [Self];
+    /// What each of [`Self::ALL`] is called, in the same order.
+    const LABELS: &'static [&'static str];
+
+    /// Position of this value in [`Self::ALL`].
+    fn index(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|f| *f == self)
+            .unwrap_or_default()
+    }
+}
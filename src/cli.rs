@@ -0,0 +1,301 @@
+use std::{path::Path, process::ExitCode, time::Duration};
+
+use crate::{
+    analyze::{AnalyzedDir, AnalyzedItem, Context, ScanError},
+    format::{DigitGrouping, SizeUnits},
+};
+
+/// Fraction of the limit a watched directory has to shrink below before going over it again
+/// notifies again, so a size hovering around the limit doesn't notify on every scan.
+const WATCH_REARM_RATIO: f64 = 0.95;
+
+/// How a scan is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// `du` style, one line per item.
+    Tree,
+    /// Indented under each directory with box-drawing lines, like `tree --du`.
+    Outline,
+    Json,
+    /// Prometheus text exposition format, for the node_exporter textfile collector.
+    Metrics,
+}
+
+/// `human` selects the units sizes are printed in, or raw bytes when `None`.
+pub fn run(
+    path: &Path,
+    ctx: &Context,
+    depth: usize,
+    human: Option<SizeUnits>,
+    output: Output,
+) -> ExitCode {
+    let analyzed = match crate::analyze::analyze_dir(path, ctx) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Error: {}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let errors = ctx.take_errors();
+    print_errors(&errors);
+
+    match output {
+        Output::Tree => print_tree(&analyzed, depth, human),
+        Output::Outline => print!("{}", outline(&analyzed, depth, human)),
+        Output::Json => println!("{}", dir_json(&analyzed, depth)),
+        Output::Metrics => print!("{}", metrics(&analyzed, errors.len())),
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Scans `path` every `interval` until killed, showing a desktop notification when its size goes
+/// over `limit`.
+pub fn watch(path: &Path, ctx: &Context, limit: u64, interval: Duration, units: SizeUnits) -> ! {
+    let mut over = false;
+    loop {
+        match crate::analyze::analyze_dir(path, ctx) {
+            Ok(analyzed) => {
+                // Taken so they don't pile up between scans.
+                print_errors(&ctx.take_errors());
+                if !over && analyzed.size > limit {
+                    over = true;
+                    notify_over_limit(path, analyzed.size, limit, units);
+                } else if over && (analyzed.size as f64) < limit as f64 * WATCH_REARM_RATIO {
+                    over = false;
+                }
+            }
+            Err(e) => eprintln!("Error: {}: {e}", path.display()),
+        }
+        std::thread::sleep(interval);
+    }
+}
+
+fn print_errors(errors: &[ScanError]) {
+    for e in errors {
+        eprintln!("Error: {e}");
+    }
+}
+
+fn notify_over_limit(path: &Path, size: u64, limit: u64, units: SizeUnits) {
+    let format = |size| crate::format::format_size(size, units, DigitGrouping::Locale);
+    let shown = notify_rust::Notification::new()
+        .appname("COSMIC DirStat")
+        .summary(&format!("{} is over {}", path.display(), format(limit)))
+        .body(&format!("It now takes up {}.", format(size)))
+        .show();
+    if let Err(e) = shown {
+        eprintln!("Error: {e}");
+    }
+}
+
+fn format_size(size: u64, human: Option<SizeUnits>) -> String {
+    match human {
+        // Grouping would get in the way of piping the output to other tools.
+        Some(units) => crate::format::format_size(size, units, DigitGrouping::Off),
+        None => size.to_string(),
+    }
+}
+
+/// Formats `dir` and its descendants up to `depth` levels deep as an indented outline, largest
+/// first.
+pub fn outline(dir: &AnalyzedDir, depth: usize, human: Option<SizeUnits>) -> String {
+    use std::fmt::Write;
+
+    fn children(
+        dir: &AnalyzedDir,
+        depth: usize,
+        human: Option<SizeUnits>,
+        indent: &str,
+        out: &mut String,
+    ) {
+        for (i, child) in dir.children.iter().enumerate() {
+            let last = i + 1 == dir.children.len();
+            let name = child.name().unwrap_or_default().to_string_lossy();
+            let _ = writeln!(
+                out,
+                "{indent}{}[{}]  {name}",
+                if last { "└── " } else { "├── " },
+                format_size(child.size(), human)
+            );
+            if let (AnalyzedItem::Dir(d), 1..) = (child, depth) {
+                let indent = format!("{indent}{}", if last { "    " } else { "│   " });
+                children(d, depth - 1, human, &indent, out);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "[{}]  {}",
+        format_size(dir.size, human),
+        dir.path.display()
+    );
+    if depth > 0 {
+        children(dir, depth - 1, human, "", &mut out);
+    }
+    out
+}
+
+/// Prints `dir` and its descendants up to `depth` levels deep, `du --all` style. `human` is the
+/// units sizes are printed in, or `None` for raw bytes, rather than a flag, since `--binary`
+/// picks the units.
+pub fn print_tree(dir: &AnalyzedDir, depth: usize, human: Option<SizeUnits>) {
+    println!("{}\t{}", format_size(dir.size, human), dir.path.display());
+
+    if depth == 0 {
+        return;
+    }
+    for child in &dir.children {
+        match child {
+            AnalyzedItem::Dir(d) => print_tree(d, depth - 1, human),
+            _ => println!(
+                "{}\t{}",
+                format_size(child.size(), human),
+                child.path().display()
+            ),
+        }
+    }
+}
+
+fn item_json(item: &AnalyzedItem, depth: usize) -> serde_json::Value {
+    match item {
+        AnalyzedItem::Dir(d) => dir_json(d, depth),
+        AnalyzedItem::File(f) => serde_json::json!({
+            "type": "file",
+            "path": f.path().to_string_lossy(),
+            "size": f.size,
+            "apparent_size": f.apparent_size,
+        }),
+        AnalyzedItem::Symlink(s) => serde_json::json!({
+            "type": "symlink",
+            "path": s.path().to_string_lossy(),
+            "size": s.size,
+            "apparent_size": s.apparent_size,
+            "link": s.link.to_string_lossy(),
+        }),
+    }
+}
+
+fn dir_json(dir: &AnalyzedDir, depth: usize) -> serde_json::Value {
+    let mut value = serde_json::json!({
+        "type": "dir",
+        "path": dir.path.to_string_lossy(),
+        "size": dir.size,
+        "apparent_size": dir.apparent_size,
+        "num_files": dir.num_files,
+        "num_dirs": dir.num_dirs,
+        "num_symlinks": dir.num_symlinks,
+        "truncated": dir.truncated,
+        "summarized": dir.summarized,
+    });
+    if depth > 0 {
+        value["children"] = dir
+            .children
+            .iter()
+            .map(|f| item_json(f, depth - 1))
+            .collect();
+    }
+
+    value
+}
+
+/// Formats the totals of `dir` and the size of each of its direct children as Prometheus metrics.
+/// Metric names and labels are kept stable so existing scrapes and alerts keep working.
+pub fn metrics(dir: &AnalyzedDir, errors: usize) -> String {
+    use std::fmt::Write;
+
+    fn label(path: &Path) -> String {
+        path.to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    }
+
+    let root = label(&dir.path);
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, values: &[(String, u64)]| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        for (path, value) in values {
+            let _ = writeln!(out, "{name}{{path=\"{path}\"}} {value}");
+        }
+    };
+
+    let mut bytes = vec![(root.clone(), dir.size)];
+    bytes.extend(dir.children.iter().map(|f| (label(&f.path()), f.size())));
+    gauge(
+        "dirstat_bytes",
+        "Bytes allocated on disk, for the scanned directory and each item directly in it.",
+        &bytes,
+    );
+    gauge(
+        "dirstat_apparent_bytes",
+        "Sum of file lengths in the scanned directory.",
+        &[(root.clone(), dir.apparent_size)],
+    );
+    gauge(
+        "dirstat_files",
+        "Files in the scanned directory.",
+        &[(root.clone(), dir.num_files)],
+    );
+    gauge(
+        "dirstat_dirs",
+        "Directories in the scanned directory.",
+        &[(root.clone(), dir.num_dirs)],
+    );
+    gauge(
+        "dirstat_scan_errors",
+        "Paths that couldn't be scanned, and so aren't counted.",
+        &[(root, errors as u64)],
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::{analyze_dir_with, mem_fs::MemFs};
+
+    fn fixture() -> AnalyzedDir {
+        let fs = MemFs::new()
+            .file("/r/big", 20_000)
+            .file("/r/d/a", 5_000)
+            .file("/r/d/e/c", 1)
+            .symlink("/r/link", "big");
+        analyze_dir_with(&fs, Path::new("/r"), &Context::default()).unwrap()
+    }
+
+    #[test]
+    fn outline_of_fixture() {
+        assert_eq!(
+            outline(&fixture(), 3, None),
+            "\
+[32768]  /r
+├── [20480]  big
+├── [12288]  d
+│   ├── [8192]  a
+│   └── [4096]  e
+│       └── [4096]  c
+└── [0]  link
+"
+        );
+    }
+
+    #[test]
+    fn outline_stops_at_depth() {
+        assert_eq!(
+            outline(&fixture(), 2, Some(SizeUnits::Binary)),
+            "\
+[32 KiB]  /r
+├── [20 KiB]  big
+├── [12 KiB]  d
+│   ├── [8 KiB]  a
+│   └── [4 KiB]  e
+└── [0 B]  link
+"
+        );
+        assert_eq!(outline(&fixture(), 0, None), "[32768]  /r\n");
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fmt::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::analyze::{AnalyzedDir, AnalyzedItem};
+
+/// Formats a scanned tree can be written out in for other tools to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeFormat {
+    /// The JSON that `ncdu -o` writes, which `ncdu -f` opens.
+    Ncdu,
+    /// What `du --all` prints: every file and directory in 1024-byte blocks, children first.
+    DuAll,
+}
+impl TreeFormat {
+    /// Name and extension for the save dialog's filter, and the file name it suggests.
+    pub const fn file_filter(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Self::Ncdu => ("ncdu JSON", "json", "dirstat.ncdu.json"),
+            Self::DuAll => ("du output", "txt", "dirstat.du.txt"),
+        }
+    }
+
+    pub fn write(self, dir: &AnalyzedDir) -> String {
+        match self {
+            Self::Ncdu => ncdu_json(dir),
+            Self::DuAll => du_all(dir),
+        }
+    }
+}
+
+/// Formats `dir` as an `ncdu` export: `[1, 2, {metadata}, [{root}, children...]]`, where each
+/// directory is an array starting with its own info.
+///
+/// Entries the scan didn't keep are written as a single file per directory, so the sizes `ncdu`
+/// adds up match. Sizes of hard-linked files are already split between their links.
+pub fn ncdu_json(dir: &AnalyzedDir) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |f| f.as_secs());
+    ncdu_json_at(dir, timestamp)
+}
+
+/// Like [`ncdu_json`], but saying the export was made at `timestamp` seconds since the epoch.
+fn ncdu_json_at(dir: &AnalyzedDir, timestamp: u64) -> String {
+    let value = serde_json::json!([
+        1,
+        2,
+        {
+            "progname": env!("CARGO_PKG_NAME"),
+            "progver": env!("CARGO_PKG_VERSION"),
+            "timestamp": timestamp,
+        },
+        ncdu_dir(dir, dir.path.to_string_lossy().into_owned()),
+    ]);
+    value.to_string()
+}
+
+fn ncdu_dir(dir: &AnalyzedDir, name: String) -> serde_json::Value {
+    // Only what the directory takes up itself goes in its info; ncdu adds up the rest.
+    let children_size = dir.children.iter().map(AnalyzedItem::size).sum::<u64>() + dir.skipped_size;
+    let children_apparent = dir
+        .children
+        .iter()
+        .map(AnalyzedItem::apparent_size)
+        .sum::<u64>()
+        + dir.skipped_apparent_size;
+    let mut info = serde_json::json!({
+        "name": name,
+        "asize": dir.apparent_size.saturating_sub(children_apparent),
+        "dsize": dir.size.saturating_sub(children_size),
+    });
+    if let Some(mtime) = dir.modified.and_then(unix_secs) {
+        info["mtime"] = mtime.into();
+    }
+
+    let mut entries = vec![info];
+    for child in &dir.children {
+        let name = child
+            .name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        entries.push(match child {
+            AnalyzedItem::Dir(d) => ncdu_dir(d, name),
+            AnalyzedItem::File(_) | AnalyzedItem::Symlink(_) => {
+                let mut file = serde_json::json!({
+                    "name": name,
+                    "asize": child.apparent_size(),
+                    "dsize": child.size(),
+                });
+                if matches!(child, AnalyzedItem::Symlink(_)) {
+                    file["notreg"] = true.into();
+                }
+                if let Some(mtime) = child.modified().and_then(unix_secs) {
+                    file["mtime"] = mtime.into();
+                }
+                file
+            }
+        });
+    }
+    if dir.skipped_files > 0 {
+        entries.push(serde_json::json!({
+            "name": format!("({} skipped entries)", dir.skipped_files),
+            "asize": dir.skipped_apparent_size,
+            "dsize": dir.skipped_size,
+        }));
+    }
+    entries.into()
+}
+
+fn unix_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|f| f.as_secs())
+}
+
+/// Formats `dir` like `du --all` does, so it can be compared with its output line by line.
+/// Entries the scan didn't keep are only counted in their directory.
+pub fn du_all(dir: &AnalyzedDir) -> String {
+    fn walk(dir: &AnalyzedDir, out: &mut String) {
+        for child in &dir.children {
+            match child {
+                AnalyzedItem::Dir(d) => walk(d, out),
+                AnalyzedItem::File(_) | AnalyzedItem::Symlink(_) => {
+                    line(child.size(), &child.path(), out);
+                }
+            }
+        }
+        line(dir.size, &dir.path, out);
+    }
+    fn line(size: u64, path: &Path, out: &mut String) {
+        let _ = writeln!(out, "{}\t{}", size.div_ceil(1024), path.display());
+    }
+
+    let mut out = String::new();
+    walk(dir, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::{analyze_dir_with, mem_fs::MemFs, Context};
+
+    /// `tiny` is below the minimum size, so it's one of the skipped entries of `d`.
+    fn fixture() -> AnalyzedDir {
+        let fs = MemFs::new()
+            .file("/r/big", 20_000)
+            .file("/r/d/a", 5_000)
+            .file("/r/d/tiny", 10)
+            .symlink("/r/link", "big");
+        let ctx = Context::default().with_min_file_size(4097);
+        analyze_dir_with(&fs, Path::new("/r"), &ctx).unwrap()
+    }
+
+    #[test]
+    fn ncdu_matches_golden_file() {
+        // Compared as JSON, since the order of keys in an object doesn't matter to ncdu.
+        let golden = include_str!("../tests/data/export.ncdu.json")
+            .replace("{version}", env!("CARGO_PKG_VERSION"));
+        let golden = serde_json::from_str::<serde_json::Value>(&golden).unwrap();
+        let json = ncdu_json_at(&fixture(), 1_700_000_000);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            golden
+        );
+    }
+
+    #[test]
+    fn du_matches_golden_file() {
+        assert_eq!(
+            du_all(&fixture()),
+            include_str!("../tests/data/export.du.txt")
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::{collections::HashMap, ffi::OsString, fmt::Write, sync::OnceLock, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analyze::{AnalyzedDir, ExtStat, Metric},
+    choice::Choice,
+};
+
+/// Number of extensions listed by [`format_report`].
+const REPORT_EXTENSIONS: usize = 10;
+
+/// Which unit prefixes sizes are displayed with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SizeUnits {
+    /// Powers of 1000: kB, MB, GB.
+    #[default]
+    Decimal,
+    /// Powers of 1024: KiB, MiB, GiB. These line up with block-based sizes.
+    Binary,
+}
+impl Choice for SizeUnits {
+    const ALL: &'static [Self] = &[Self::Decimal, Self::Binary];
+    const LABELS: &'static [&'static str] = &["Decimal (kB, MB)", "Binary (KiB, MiB)"];
+}
+
+/// How the digits of large numbers are separated for display.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DigitGrouping {
+    /// Thousands separators and decimal mark of the locale in `LC_ALL`, `LC_NUMERIC` or `LANG`.
+    #[default]
+    Locale,
+    /// No thousands separators, and a `.` decimal mark.
+    Off,
+}
+impl Choice for DigitGrouping {
+    const ALL: &'static [Self] = &[Self::Locale, Self::Off];
+    const LABELS: &'static [&'static str] = &["Locale", "Off"];
+}
+impl DigitGrouping {
+    /// The thousands separator and decimal mark numbers are formatted with.
+    fn separators(self) -> (Option<char>, char) {
+        static LOCALE: OnceLock<(Option<char>, char)> = OnceLock::new();
+        match self {
+            Self::Locale => *LOCALE.get_or_init(locale_separators),
+            Self::Off => (None, '.'),
+        }
+    }
+}
+
+/// Picks separators for the language of the user's numeric locale. Only the common conventions
+/// are covered; anything else gets English ones.
+fn locale_separators() -> (Option<char>, char) {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .into_iter()
+        .filter_map(|f| std::env::var(f).ok())
+        .find(|f| !f.is_empty())
+        .unwrap_or_default();
+    let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+    match language {
+        "C" | "POSIX" => (None, '.'),
+        "de" | "es" | "it" | "nl" | "pt" | "da" | "id" | "tr" | "el" | "ro" | "hr" | "sl"
+        | "sr" | "vi" => (Some('.'), ','),
+        "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "nb" | "nn" | "fi" | "uk" | "hu" | "bg"
+        | "et" | "lv" | "lt" => (Some('\u{202f}'), ','),
+        _ => (Some(','), '.'),
+    }
+}
+
+/// Inserts thousands separators into the integer part of a number written with a `.` decimal
+/// mark, and swaps the mark for the one of `grouping`.
+fn group_digits(number: &str, grouping: DigitGrouping) -> String {
+    let (thousands, decimal) = grouping.separators();
+    let (integer, fraction) = match number.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (number, None),
+    };
+    let mut grouped = String::with_capacity(number.len() + integer.len() / 3);
+    for (i, c) in integer.chars().enumerate() {
+        if let Some(sep) = thousands.filter(|_| i > 0 && (integer.len() - i) % 3 == 0) {
+            grouped.push(sep);
+        }
+        grouped.push(c);
+    }
+    if let Some(fraction) = fraction {
+        grouped.push(decimal);
+        grouped.push_str(fraction);
+    }
+    grouped
+}
+
+/// Formats a count of files, directories or anything else for display.
+pub fn format_count(count: u64, grouping: DigitGrouping) -> String {
+    group_digits(&count.to_string(), grouping)
+}
+
+/// Reads a size typed by the user, like `500M`, `1.5 GB` or `10GiB`. Prefixes followed by `iB` or
+/// written alone are powers of 1024 as with `du`, and followed by `B` powers of 1000.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number.parse::<f64>().ok()?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let (prefix, suffix) = match unit.char_indices().nth(1) {
+        Some((i, _)) => unit.split_at(i),
+        None => (&*unit, ""),
+    };
+    let exponent = match prefix {
+        "" | "b" if suffix.is_empty() => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        _ => return None,
+    };
+    let base: f64 = match suffix {
+        "" | "ib" => 1024.0,
+        "b" => 1000.0,
+        _ => return None,
+    };
+    Some((number * base.powi(exponent)) as u64)
+}
+
+/// Fills in the label of an aggregate tile from `template`, replacing `{count}` with the number
+/// of items rolled into it and `{size}` with how much of `metric` they take up.
+pub fn format_aggregate(
+    template: &str,
+    count: u64,
+    amount: u64,
+    metric: Metric,
+    units: SizeUnits,
+    grouping: DigitGrouping,
+) -> String {
+    template
+        .replace("{count}", &format_count(count, grouping))
+        .replace("{size}", &format_metric(amount, metric, units, grouping))
+}
+
+/// Fills in each `{field}` of `template` with what `value` gives for it, with `\n` starting a new
+/// line. This goes through the template once, so braces in the values are left alone, as are
+/// fields `value` doesn't know.
+pub fn fill_template(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let template = template.replace("\\n", "\n");
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        rest = &rest[open + 1..];
+        let field = rest.find('}').and_then(|f| Some((f, value(&rest[..f])?)));
+        match field {
+            Some((close, value)) => {
+                filled.push_str(&value);
+                rest = &rest[close + 1..];
+            }
+            None => filled.push('{'),
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Shortens `name` to at most `max` characters by putting an ellipsis in its middle, like
+/// `verylong…name.ext`. The extension is kept whole when there's room for it, since it says the
+/// most about what a file is. With room for one character, only the ellipsis is left, and with no
+/// room at all, nothing is.
+pub fn ellipsize_middle(name: &str, max: usize) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    if chars.len() <= max {
+        return name.to_owned();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    // One character goes to the ellipsis, and the start gets the odd one of what's left.
+    let room = max.saturating_sub(1);
+    let ext = name
+        .rfind('.')
+        .filter(|f| *f > 0)
+        .map_or(0, |f| name[f..].chars().count());
+    let ext = if ext < room { ext } else { 0 };
+    let start = (room - ext).div_ceil(2);
+    let end = room - start;
+    let mut short = chars[..start].iter().collect::<String>();
+    short.push('…');
+    short.extend(&chars[chars.len() - end..]);
+    short
+}
+
+/// Formats a byte count for display. Every size shown to the user goes through this.
+pub fn format_size(size: u64, units: SizeUnits, grouping: DigitGrouping) -> String {
+    let formatted = match units {
+        SizeUnits::Decimal => humansize::format_size(size, humansize::DECIMAL),
+        SizeUnits::Binary => humansize::format_size(size, humansize::BINARY),
+    };
+    match formatted.split_once(' ') {
+        Some((number, unit)) => format!("{} {unit}", group_digits(number, grouping)),
+        None => formatted,
+    }
+}
+
+/// Formats an amount of `metric`, as a size or as a number of files.
+pub fn format_metric(
+    amount: u64,
+    metric: Metric,
+    units: SizeUnits,
+    grouping: DigitGrouping,
+) -> String {
+    match metric {
+        Metric::Bytes => format_size(amount, units, grouping),
+        Metric::FileCount => match amount {
+            1 => "1 file".into(),
+            n => format!("{} files", format_count(n, grouping)),
+        },
+    }
+}
+
+/// Formats how long ago `time` was, to the largest whole unit.
+pub fn format_age(time: SystemTime) -> String {
+    let Ok(age) = SystemTime::now().duration_since(time) else {
+        return "in the future".into();
+    };
+    let secs = age.as_secs();
+    let (n, unit) = match secs {
+        0..60 => return "just now".into(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86400 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{n} {unit}{} ago", if n == 1 { "" } else { "s" })
+}
+
+/// Summarizes `dir` as plain text for pasting elsewhere: its path, size and counts, then the
+/// extensions in `stats` taking up the most space.
+pub fn format_report(
+    dir: &AnalyzedDir,
+    stats: &HashMap<OsString, ExtStat>,
+    units: SizeUnits,
+    grouping: DigitGrouping,
+) -> String {
+    let mut report = format!(
+        "{}\n{} in {} files, {} directories, {} symlinks\n",
+        dir.path.display(),
+        format_size(dir.size, units, grouping),
+        format_count(dir.num_files, grouping),
+        format_count(dir.num_dirs, grouping),
+        format_count(dir.num_symlinks, grouping),
+    );
+
+    let mut ext = stats.iter().collect::<Vec<_>>();
+    ext.sort_by_key(|f| std::cmp::Reverse(f.1.bytes));
+    if !ext.is_empty() {
+        report.push_str("\nTop extensions:\n");
+    }
+    for (name, stat) in ext.into_iter().take(REPORT_EXTENSIONS) {
+        let percent = if dir.size == 0 {
+            0.0
+        } else {
+            stat.bytes as f64 / dir.size as f64 * 100.0
+        };
+        let _ = writeln!(
+            report,
+            ".{}\t{} ({percent:.1}%)\t{} files",
+            name.to_string_lossy(),
+            format_size(stat.bytes, units, grouping),
+            format_count(stat.count, grouping),
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes() {
+        let cases = [
+            ("500", Some(500)),
+            ("7b", Some(7)),
+            ("1k", Some(1 << 10)),
+            ("1KiB", Some(1 << 10)),
+            ("1KB", Some(1000)),
+            ("500M", Some(500 << 20)),
+            ("1.5 GB", Some(1_500_000_000)),
+            ("10GiB", Some(10 << 30)),
+            ("2t", Some(2 << 40)),
+            ("1pb", Some(1_000_000_000_000_000)),
+            ("  3 mib ", Some(3 << 20)),
+            ("", None),
+            ("GB", None),
+            ("1x", None),
+            ("1kx", None),
+            ("1kibb", None),
+        ];
+        for (s, size) in cases {
+            assert_eq!(parse_size(s), size, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn template_fields() {
+        let value = |f: &str| match f {
+            "name" => Some("{path}".to_owned()),
+            "path" => Some("/a/{name}".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            fill_template("{name} in {path}\\n{unknown} {{name}}", value),
+            "{path} in /a/{name}\n{unknown} {{path}}"
+        );
+        assert_eq!(fill_template("no fields {", value), "no fields {");
+    }
+
+    #[test]
+    fn ellipsize() {
+        assert_eq!(ellipsize_middle("short.txt", 9), "short.txt");
+        assert_eq!(ellipsize_middle("verylongname.ext", 10), "ver…me.ext");
+        assert_eq!(ellipsize_middle("verylongname", 5), "ve…me");
+        // An extension that doesn't leave room for the start is cut like the rest.
+        assert_eq!(ellipsize_middle("a.verylongext", 5), "a.…xt");
+        assert_eq!(ellipsize_middle("äöüäöü", 3), "ä…ü");
+        assert_eq!(ellipsize_middle("name", 1), "…");
+        assert_eq!(ellipsize_middle("name", 0), "");
+        assert_eq!(ellipsize_middle("", 0), "");
+    }
+
+    #[test]
+    fn sizes_in_both_units() {
+        let size = |size, units| format_size(size, units, DigitGrouping::Off);
+        assert_eq!(size(0, SizeUnits::Decimal), "0 B");
+        assert_eq!(size(999, SizeUnits::Decimal), "999 B");
+        assert_eq!(size(1000, SizeUnits::Decimal), "1 kB");
+        assert_eq!(size(1024, SizeUnits::Binary), "1 KiB");
+        assert_eq!(size(1_500_000, SizeUnits::Decimal), "1.50 MB");
+        assert_eq!(size(1_572_864, SizeUnits::Binary), "1.50 MiB");
+        assert_eq!(size(5 << 40, SizeUnits::Binary), "5 TiB");
+    }
+}
@@ -1,58 +1,1385 @@
-use std::{ffi::OsString, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{analyze::ScanProgress, choice::Choice};
 
+mod config;
+mod export_image;
+mod focus_list;
 mod partition_view;
+mod watch;
 
 use cosmic::{
-    iced::{alignment::Horizontal, Color, Length, Point},
+    cosmic_config::CosmicConfigEntry,
+    iced::{alignment::Horizontal, Color, Length, Size},
     iced_widget::scrollable,
     widget::{self, container, grid},
 };
 
-pub fn run() {
-    cosmic::app::run::<App>(cosmic::app::Settings::default().transparent(true), ()).unwrap();
+const TEXT_SIZE: f32 = 8.0;
+const MINIMUM_AREA: f32 = TEXT_SIZE * 8.0;
+const MINIMUM_ASPECT: f32 = 0.05;
+/// Minimum tile area when zoomed into the small items of a directory.
+const TAIL_MINIMUM_AREA: f32 = MINIMUM_AREA / 2.0;
+/// Size of the overview drawn in a corner of the treemap while inside a subdirectory.
+const OVERVIEW_SIZE: Size = Size::new(192.0, 120.0);
+/// The overview is left out of treemaps less than this many times its size across, so it doesn't
+/// cover much of them.
+const OVERVIEW_MIN_SCALE: f32 = 3.0;
+/// Rows shown for a single expanded directory in the outline, largest first.
+const OUTLINE_MAX_CHILDREN: usize = 200;
+const OUTLINE_INDENT: f32 = 16.0;
+/// How many of the largest files jumping to the next largest cycles through.
+const LARGEST_FILES: usize = 10;
+/// Levels below each finished folder shown while the first scan is still going.
+const PARTIAL_DEPTH: usize = 2;
+/// Span of recent progress samples the scan rate is averaged over.
+const RATE_WINDOW: Duration = Duration::from_secs(2);
+/// How often the idle timeout is checked, which is about how late it can be.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Rows shown in the cleanup lists and in each group of scan errors, to keep huge scans
+/// responsive.
+const CLEANUP_MAX_ROWS: usize = 200;
+/// Directories taking up this many times their apparent size get a note in the inspector about
+/// small-file slack.
+const SLACK_RATIO: f64 = 2.0;
+/// Directories allocating less than this aren't worth a note, however much of it is slack.
+const SLACK_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Ages offered by the stale files finder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum StaleAge {
+    Month,
+    ThreeMonths,
+    SixMonths,
+    #[default]
+    Year,
+    TwoYears,
+}
+impl Choice for StaleAge {
+    const ALL: &'static [Self] = &[
+        Self::Month,
+        Self::ThreeMonths,
+        Self::SixMonths,
+        Self::Year,
+        Self::TwoYears,
+    ];
+    const LABELS: &'static [&'static str] =
+        &["1 month", "3 months", "6 months", "1 year", "2 years"];
+}
+impl StaleAge {
+    const fn days(self) -> u64 {
+        match self {
+            Self::Month => 30,
+            Self::ThreeMonths => 91,
+            Self::SixMonths => 182,
+            Self::Year => 365,
+            Self::TwoYears => 730,
+        }
+    }
+}
+
+/// Sizes below which files can be left out of scans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MinFileSize {
+    KeepAll,
+    Kib4,
+    Kib64,
+    Mib1,
+}
+impl Choice for MinFileSize {
+    const ALL: &'static [Self] = &[Self::KeepAll, Self::Kib4, Self::Kib64, Self::Mib1];
+    const LABELS: &'static [&'static str] = &["Keep all", "4 KiB", "64 KiB", "1 MiB"];
+}
+impl MinFileSize {
+    const fn bytes(self) -> u64 {
+        match self {
+            Self::KeepAll => 0,
+            Self::Kib4 => 4 * 1024,
+            Self::Kib64 => 64 * 1024,
+            Self::Mib1 => 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MaxEntries {
+    KeepAll,
+    Thousand,
+    TenThousand,
+    HundredThousand,
+}
+impl Choice for MaxEntries {
+    const ALL: &'static [Self] = &[
+        Self::KeepAll,
+        Self::Thousand,
+        Self::TenThousand,
+        Self::HundredThousand,
+    ];
+    const LABELS: &'static [&'static str] = &["Keep all", "1,000", "10,000", "100,000"];
+}
+impl MaxEntries {
+    const fn count(self) -> Option<usize> {
+        match self {
+            Self::KeepAll => None,
+            Self::Thousand => Some(1_000),
+            Self::TenThousand => Some(10_000),
+            Self::HundredThousand => Some(100_000),
+        }
+    }
+}
+
+/// Sizes below which folders are only added up, without keeping what's in them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DetailThreshold {
+    All,
+    Mib16,
+    Mib256,
+    Gib1,
+}
+impl Choice for DetailThreshold {
+    const ALL: &'static [Self] = &[Self::All, Self::Mib16, Self::Mib256, Self::Gib1];
+    const LABELS: &'static [&'static str] = &["Look inside all", "16 MiB", "256 MiB", "1 GiB"];
+}
+impl DetailThreshold {
+    const fn bytes(self) -> Option<u64> {
+        match self {
+            Self::All => None,
+            Self::Mib16 => Some(16 * 1024 * 1024),
+            Self::Mib256 => Some(256 * 1024 * 1024),
+            Self::Gib1 => Some(1024 * 1024 * 1024),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum ExportResolution {
+    Hd,
+    #[default]
+    FullHd,
+    Uhd,
+}
+impl Choice for ExportResolution {
+    const ALL: &'static [Self] = &[Self::Hd, Self::FullHd, Self::Uhd];
+    const LABELS: &'static [&'static str] = &["1280 × 720", "1920 × 1080", "3840 × 2160"];
+}
+impl ExportResolution {
+    const fn size(self) -> (u32, u32) {
+        match self {
+            Self::Hd => (1280, 720),
+            Self::FullHd => (1920, 1080),
+            Self::Uhd => (3840, 2160),
+        }
+    }
+}
+
+pub fn run(path: Option<PathBuf>) {
+    cosmic::app::run::<App>(cosmic::app::Settings::default().transparent(true), path).unwrap();
 }
 
 #[derive(Debug, Clone)]
 enum Msg {
     CrawlPathChanged(PathBuf),
-    CrawlPath { cancel: bool },
+    CrawlPath {
+        cancel: bool,
+    },
     CrawlPathDialogue,
+    /// Reads a directory from disk, for loading a new root or refreshing part of the tree.
     Crawl(PathBuf),
+    /// Shows a directory of the scanned tree, scanning it first if it isn't in the tree.
+    ShowDir(PathBuf),
     ExtensionLegendChanged(Vec<(OsString, Color)>),
     PaneResize(cosmic::widget::pane_grid::ResizeEvent),
-    Analyzed(Arc<crate::analyze::AnalyzedDir>),
-    AnalyzedError(String),
+    MaximizeToggled,
+    /// A scan finished, with the context it was started with to tell whether it's still the
+    /// latest one.
+    Analyzed(
+        Arc<crate::analyze::Context>,
+        Arc<crate::analyze::AnalyzedDir>,
+    ),
+    AnalyzedError(Arc<crate::analyze::Context>, String),
     ClearError,
-    NewItemHighlighted(Option<(Point, String, u64, PathBuf)>),
+    /// Shows every setting in place of the panels, or goes back to the panels.
+    SettingsToggled(bool),
+    ShowScanErrors(bool),
+    CopyScanErrors,
+    DismissScanErrors,
+    NewItemHighlighted(Option<partition_view::HoverInfo>),
+    OverviewUpdated,
+    OverviewToggled(bool),
+    WatchToggled(bool),
+    PathChanged(PathBuf),
+    /// A directory scanned again after it changed, with the hard links and errors found in it.
+    PathRescanned(
+        Arc<crate::analyze::AnalyzedDir>,
+        crate::analyze::HardLinks,
+        Vec<crate::analyze::ScanError>,
+    ),
+    WatchFailed(String),
+    Rescanned(Arc<crate::analyze::AnalyzedDir>),
+    /// Scans a directory of the tree again with the current scan settings, putting the result in
+    /// its place without leaving the directory being looked at.
+    RescanSubtree(PathBuf),
+    SubtreeRescanned(
+        Arc<crate::analyze::Context>,
+        Arc<crate::analyze::AnalyzedDir>,
+    ),
+    RefreshIntervalChanged(usize),
+    RefreshTick,
+    FsStats(Option<crate::analyze::FsStats>),
+    SetBaseline(bool),
+    ExportResolutionChanged(usize),
+    ExportLegendToggled(bool),
+    ExportImageDialogue,
+    ExportImage(PathBuf),
+    ExportTreeDialogue(crate::export::TreeFormat),
+    ExportTree(crate::export::TreeFormat, PathBuf),
+    ExportFinished(Result<(), String>),
+    OutlineToggled(PathBuf),
+    OutlineSelected(PathBuf),
+    /// Lists the other paths of the selected file in the inspector.
+    ShowHardLinks(bool),
+    ClearSelection,
+    /// Selects the largest file in the treemap, showing the folder it's in.
+    JumpToLargest,
+    /// Selects the next of the largest files, starting over after the last.
+    JumpToNextLargest,
+    MarkToggled(PathBuf),
+    ConfirmTrashMarked(bool),
+    TrashMarked,
+    SortKeyChanged(usize),
+    SortOrderToggled,
+    SizeUnitsChanged(usize),
+    DigitGroupingChanged(usize),
+    ConfirmTrashEmptyDirs(bool),
+    TrashEmptyDirs,
+    StaleAgeChanged(usize),
+    ScanLastOnStartupToggled(bool),
+    HighContrastToggled(bool),
+    DistinctDirsToggled(bool),
+    TabbedPanelsToggled(bool),
+    PanelTabActivated(widget::segmented_button::Entity),
+    TileBordersChanged(usize),
+    TileDetailChanged(usize),
+    LabelLengthChanged(usize),
+    HeaderHeightChanged(usize),
+    QuotaInputChanged(String),
+    /// Sets the quota of the directory being looked at to what's typed in.
+    SetQuota,
+    ClearQuota,
+    QuotaWarningChanged(usize),
+    AggregateLabelChanged(String),
+    MetricChanged(usize),
+    PaletteChanged(usize),
+    ColorModeChanged(usize),
+    RootLabelChanged(usize),
+    CustomRootLabelChanged(String),
+    SizeBandsChanged(String),
+    CopyReport,
+    RoundedTilesToggled(bool),
+    AnimateNavigationToggled(bool),
+    CompactHoverToggled(bool),
+    HoverDelayChanged(usize),
+    HoverTemplateChanged(String),
+    MinFileSizeChanged(usize),
+    MaxEntriesChanged(usize),
+    DetailThresholdChanged(usize),
+    IncludeEmptyFilesToggled(bool),
+    #[cfg(feature = "archives")]
+    ScanArchivesToggled(bool),
+    DedupDirsToggled(bool),
+    HiddenModeChanged(usize),
+    ProfileSelected(usize),
+    ProfileNameChanged(String),
+    SaveProfile,
+    DeleteProfile,
+    ConfirmTrashStaleFiles(bool),
+    TrashStaleFiles,
+    Trashed(Result<Vec<PathBuf>, String>),
+    ProgressTick,
+    PauseToggled,
+    ExtensionFilter(Option<OsString>),
+    /// Leaves files with the extension out of the treemap, or puts them back.
+    ExtensionHidden(OsString, bool),
+    /// Filters to the extension at this index in the legend, or stops filtering if it's the one
+    /// already filtered to.
+    ExtensionFilterToggled(usize),
+    AggregateClicked(PathBuf, u64),
+    GoUp,
+    NavBack,
+    NavForward,
+    AddRoot,
+    RootAdded(
+        Arc<crate::analyze::Context>,
+        Arc<crate::analyze::AnalyzedDir>,
+    ),
+    RemoveRoot(usize),
+    CloseTail,
+    IdleTimeoutChanged(usize),
+    IdleTick,
+}
+impl Msg {
+    /// Whether the message comes from the user, rather than from a scan or timer.
+    const fn is_interaction(&self) -> bool {
+        !matches!(
+            self,
+            Self::ExtensionLegendChanged(_)
+                | Self::Analyzed(..)
+                | Self::AnalyzedError(..)
+                | Self::OverviewUpdated
+                | Self::PathChanged(_)
+                | Self::PathRescanned(..)
+                | Self::WatchFailed(_)
+                | Self::Rescanned(_)
+                | Self::SubtreeRescanned(..)
+                | Self::RefreshTick
+                | Self::FsStats(_)
+                | Self::ExportFinished(_)
+                | Self::Trashed(_)
+                | Self::ProgressTick
+                | Self::RootAdded(..)
+                | Self::IdleTick
+        )
+    }
+}
+
+/// Explains why the root of a scan couldn't be read, or `None` if the scan was cancelled.
+fn scan_error_message(path: &std::path::Path, e: &std::io::Error) -> Option<String> {
+    let path = path.display();
+    Some(match e.kind() {
+        std::io::ErrorKind::Interrupted => return None,
+        std::io::ErrorKind::PermissionDenied => {
+            format!("Permission denied: you aren't allowed to read {path}")
+        }
+        std::io::ErrorKind::NotFound => format!("{path} doesn't exist"),
+        std::io::ErrorKind::NotADirectory => format!("{path} isn't a directory"),
+        _ => format!("Couldn't scan {path}: {e}"),
+    })
+}
+
+/// Whether `path` is a file or directory on this machine, rather than on an SFTP server or inside
+/// an archive, so it can be trashed, watched and read from disk.
+fn is_local(path: &std::path::Path) -> bool {
+    if path.to_str().is_some_and(|f| f.starts_with("sftp://")) {
+        return false;
+    }
+    // The archive itself is a real file, but what's listed inside it isn't.
+    #[cfg(feature = "archives")]
+    if path.ancestors().skip(1).any(crate::archive::is_archive) {
+        return false;
+    }
+    true
+}
+
+/// Groups the paths of `errors` by what went wrong, in order of the label.
+fn group_scan_errors(
+    errors: &[crate::analyze::ScanError],
+) -> std::collections::BTreeMap<String, Vec<&std::path::Path>> {
+    let mut groups = std::collections::BTreeMap::<_, Vec<_>>::new();
+    for e in errors {
+        groups.entry(e.kind.label()).or_default().push(&*e.path);
+    }
+    groups
+}
+
+/// Maps global shortcuts to messages. Keys already handled by a widget, such as Backspace in the
+/// path input, are left alone.
+fn key_pressed(
+    event: cosmic::iced::Event,
+    status: cosmic::iced::event::Status,
+    _window: cosmic::iced::window::Id,
+) -> Option<Msg> {
+    use cosmic::iced::keyboard::{key::Named, Event, Key};
+
+    if status == cosmic::iced::event::Status::Captured {
+        return None;
+    }
+    let cosmic::iced::Event::Keyboard(Event::KeyPressed { key, modifiers, .. }) = event else {
+        return None;
+    };
+    match key {
+        Key::Named(Named::Backspace) if modifiers.is_empty() => Some(Msg::GoUp),
+        Key::Named(Named::Escape) if modifiers.is_empty() => Some(Msg::ClearSelection),
+        Key::Named(Named::F11) if modifiers.is_empty() => Some(Msg::MaximizeToggled),
+        Key::Named(Named::ArrowUp) if modifiers.alt() => Some(Msg::GoUp),
+        Key::Named(Named::ArrowLeft) if modifiers.alt() => Some(Msg::NavBack),
+        Key::Named(Named::ArrowRight) if modifiers.alt() => Some(Msg::NavForward),
+        // Letters need Alt, so typing into whatever has focus doesn't jump around the tree.
+        Key::Character(c) if modifiers.alt() && c.eq_ignore_ascii_case("l") => {
+            Some(Msg::JumpToLargest)
+        }
+        Key::Character(c) if modifiers.alt() && c.eq_ignore_ascii_case("n") => {
+            Some(Msg::JumpToNextLargest)
+        }
+        Key::Character(c)
+            if c.eq_ignore_ascii_case("c") && modifiers.control() && modifiers.shift() =>
+        {
+            Some(Msg::CopyReport)
+        }
+        _ => None,
+    }
+}
+
+/// Paths of the `count` largest files below `dir`, largest first.
+fn largest_files(dir: &crate::analyze::AnalyzedDir, count: usize) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    dir.walk(&mut |item, _| {
+        if let crate::analyze::AnalyzedItem::File(f) = item {
+            files.push(f);
+        }
+    });
+    if files.len() > count {
+        files.select_nth_unstable_by_key(count, |f| std::cmp::Reverse(f.size));
+        files.truncate(count);
+    }
+    files.sort_unstable_by_key(|f| std::cmp::Reverse(f.size));
+    files
+        .into_iter()
+        .map(crate::analyze::AnalyzedFile::path)
+        .collect()
 }
 
 enum Panels {
     NamePath,
     Tree,
+    Cleanup,
     Partioned,
+    Inspector,
+    Summary,
 }
 
 struct App {
     core: cosmic::app::Core,
+    config_handler: Option<cosmic::cosmic_config::Config>,
+    config: config::Config,
+    /// Names of [`App::profiles`], for their dropdown.
+    profile_names: Vec<String>,
+    /// What a new profile will be saved as.
+    profile_name: String,
+    /// Quota being typed in for the directory being looked at.
+    quota_input: String,
     crawl_path: PathBuf,
     crawling_path: bool,
     state: cosmic::widget::pane_grid::State<Panels>,
+    /// The treemap's pane, which can be maximized to fill the window.
+    partitioned_pane: cosmic::widget::pane_grid::Pane,
+    /// The inspector's pane, while it isn't a tab of the tree's.
+    inspector_pane: Option<cosmic::widget::pane_grid::Pane>,
+    /// Tabs of the tree's pane, holding [`config::PanelTab`]s.
+    panel_tabs: widget::segmented_button::SingleSelectModel,
+    /// The whole scanned tree, of which [`App::current`] is shown.
     analyzed: Option<Arc<crate::analyze::AnalyzedDir>>,
+    /// Directory of `analyzed` being looked at, when it isn't its root.
+    view: Option<PathBuf>,
     error: Option<String>,
     extensions_ordered: Vec<(OsString, Color)>,
-    highlighted: Option<(Point, String, u64, PathBuf)>,
+    highlighted: Option<partition_view::HoverInfo>,
+    watching: bool,
+    fs_stats: Option<crate::analyze::FsStats>,
+    baseline: Option<Arc<crate::analyze::AnalyzedDir>>,
+    diff: Option<crate::analyze::TreeDiff>,
+    export_resolution: ExportResolution,
+    export_legend: bool,
+    expanded: HashSet<PathBuf>,
+    selected: Option<PathBuf>,
+    /// The largest files of the treemap when [`Msg::JumpToLargest`] was last pressed, largest
+    /// first, and which of them is selected.
+    largest_files: Vec<PathBuf>,
+    largest_rank: usize,
+    /// Items marked in the treemap for trashing together.
+    marked: std::collections::BTreeSet<PathBuf>,
+    confirm_trash_marked: bool,
+    empty_dirs: Vec<PathBuf>,
+    confirm_trash_empty_dirs: bool,
+    stale_age: StaleAge,
+    /// Files older than the selected age, largest first.
+    stale_files: Vec<(PathBuf, u64)>,
+    confirm_trash_stale_files: bool,
+    /// Directory and number of small items the treemap is zoomed into.
+    tail: Option<(PathBuf, u64)>,
+    tail_dir: Option<crate::analyze::AnalyzedDir>,
+    /// Previously scanned directories, most recent last.
+    back: Vec<PathBuf>,
+    forward: Vec<PathBuf>,
+    /// Scanned roots. With more than one, `analyzed` is a synthetic directory holding them all.
+    roots: Vec<Arc<crate::analyze::AnalyzedDir>>,
+    scan_started: Option<Instant>,
+    /// How long the last finished scan took.
+    scan_duration: Option<Duration>,
+    /// Context of the scan in progress, polled for progress.
+    scan_context: Option<Arc<crate::analyze::Context>>,
+    paused: bool,
+    /// When the user last did anything, for [`config::IdleTimeout`].
+    last_interaction: Instant,
+    /// Root of the scan that was let go of after the idle timeout, to offer scanning it again.
+    expired: Option<PathBuf>,
+    /// Progress samples within the last [`RATE_WINDOW`], oldest first.
+    progress: std::collections::VecDeque<(Instant, ScanProgress)>,
+    /// Paths the last scan skipped.
+    scan_errors: Vec<crate::analyze::ScanError>,
+    show_scan_errors: bool,
+    show_settings: bool,
+    /// Links between the files of `roots`, for listing every path of the selected one.
+    hardlinks: crate::analyze::HardLinks,
+    show_hardlinks: bool,
+    /// Only files with this extension are shown in the treemap.
+    extension_filter: Option<OsString>,
+    /// Files with these extensions are left out of the treemap for the rest of the session.
+    hidden_extensions: HashSet<OsString>,
+    /// Files of the treemap's directory in each size band.
+    band_counts: Vec<u64>,
+    /// Size bands being typed in, until they can all be read.
+    size_bands_input: String,
+    filtered: Option<crate::analyze::AnalyzedDir>,
+    /// Extension totals of the tree shown in the treemap.
+    extension_stats: HashMap<OsString, crate::analyze::ExtStat>,
+    /// The item shown in the inspector when it was last read from disk, and what was read, or
+    /// `None` if it couldn't be.
+    inspected: Option<(PathBuf, Option<DiskDetails>)>,
+}
+
+/// What the inspector shows of an item that the scan doesn't keep.
+struct DiskDetails {
+    owner: String,
+    /// How many paths the file has, or `None` for a directory.
+    hard_links: Option<u64>,
 }
 impl App {
+    fn format_size(&self, size: u64) -> String {
+        crate::format::format_size(size, self.config.size_units, self.config.digit_grouping)
+    }
+
+    fn format_count(&self, count: u64) -> String {
+        crate::format::format_count(count, self.config.digit_grouping)
+    }
+
+    fn format_metric(&self, amount: u64, metric: crate::analyze::Metric) -> String {
+        crate::format::format_metric(
+            amount,
+            metric,
+            self.config.size_units,
+            self.config.digit_grouping,
+        )
+    }
+
+    /// Applies `f` to the config, persisting the result when the config backend is available.
+    fn update_config(&mut self, f: impl FnOnce(&mut config::Config)) {
+        f(&mut self.config);
+        self.profile_names = self.profiles().into_iter().map(|f| f.name).collect();
+        if let Some(handler) = &self.config_handler {
+            if let Err(e) = self.config.write_entry(handler) {
+                eprintln!("Error: {e}");
+            }
+        }
+    }
+
+    /// The built-in scan profiles followed by the user's.
+    fn profiles(&self) -> Vec<config::ScanProfile> {
+        config::ScanProfile::builtin()
+            .into_iter()
+            .chain(self.config.profiles.iter().cloned())
+            .collect()
+    }
+
+    /// Index in [`App::profiles`] of the profile the current scan settings came from, if any.
+    fn current_profile(&self) -> Option<usize> {
+        self.profiles().iter().position(|f| f.matches(&self.config))
+    }
+
+    /// The directory being looked at, which everything but the overview and the summary shows.
+    fn current(&self) -> Option<&crate::analyze::AnalyzedDir> {
+        let analyzed = self.analyzed.as_deref()?;
+        match &self.view {
+            Some(path) => analyzed.find_dir(path).or(Some(analyzed)),
+            None => Some(analyzed),
+        }
+    }
+
+    /// The directory holding the nearest quota around [`App::current`], and that quota.
+    fn quota(&self) -> Option<(&crate::analyze::AnalyzedDir, u64)> {
+        let analyzed = self.analyzed.as_deref()?;
+        self.current()?.path.ancestors().find_map(|f| {
+            let quota = *self.config.quotas.get(f)?;
+            Some((analyzed.find_dir(f)?, quota))
+        })
+    }
+
+    /// [`App::current`] to hand to a task, only copied when it's inside the tree.
+    fn current_arc(&self) -> Option<Arc<crate::analyze::AnalyzedDir>> {
+        match (&self.view, self.current()) {
+            (Some(_), Some(current)) => Some(Arc::new(current.clone())),
+            _ => self.analyzed.clone(),
+        }
+    }
+
+    /// Shows `path` from the scanned tree straight away, or scans it if it's not in the tree.
+    fn show_dir(&mut self, path: PathBuf) -> cosmic::app::Task<Msg> {
+        // Only the totals of summarized folders were kept, so they're scanned to see inside,
+        // unless they're only summarized until the first scan is done.
+        let Some(analyzed) = self.analyzed.as_ref().filter(|f| {
+            f.find_dir(&path)
+                .is_some_and(|d| !d.summarized || self.roots.is_empty())
+        }) else {
+            return self.scan(path);
+        };
+        self.view = (*analyzed.path != *path).then(|| path.clone());
+        self.tail = None;
+        self.crawl_path = path;
+        self.update_title();
+        self.tree_changed();
+        self.reveal_selected();
+        cosmic::Task::none()
+    }
+
+    /// Recomputes everything derived from the analyzed tree after it changes.
+    fn tree_changed(&mut self) {
+        self.diff = match (&self.baseline, self.current()) {
+            (Some(before), Some(after)) if before.path == after.path => {
+                Some(crate::analyze::diff_trees(before, after))
+            }
+            _ => None,
+        };
+        // The cleanup lists are for trashing, so they only hold what can be.
+        self.empty_dirs = self
+            .current()
+            .map(crate::analyze::find_empty_dirs)
+            .unwrap_or_default();
+        self.empty_dirs.retain(|f| is_local(f));
+        self.update_stale_files();
+        self.update_filter();
+        // What's on disk may have changed with the tree.
+        self.inspected = None;
+        self.update_inspected();
+    }
+
+    fn update_filter(&mut self) {
+        let only = self.extension_filter.as_deref();
+        let hidden = &self.hidden_extensions;
+        self.filtered = match self.current() {
+            Some(analyzed) if only.is_some() || !hidden.is_empty() => {
+                Some(analyzed.filter_files(&|f| {
+                    let ext = f.extension();
+                    only.is_none_or(|only| ext == Some(only))
+                        && !ext.is_some_and(|ext| hidden.contains(ext))
+                }))
+            }
+            _ => None,
+        };
+        self.update_tail();
+        self.update_band_counts();
+    }
+
+    /// Where size bands start, if files are colored by them. They're in bytes, so other metrics
+    /// don't get them.
+    fn size_bands(&self) -> Option<&[u64]> {
+        (self.config.color_mode == config::ColorMode::SizeBand
+            && self.config.metric == crate::analyze::Metric::Bytes)
+            .then(|| {
+                self.config
+                    .size_bands
+                    .as_deref()
+                    .unwrap_or(&config::DEFAULT_SIZE_BANDS)
+            })
+    }
+
+    fn update_band_counts(&mut self) {
+        let Some((dir, bands)) = self.treemap_dir().zip(self.size_bands()) else {
+            self.band_counts.clear();
+            return;
+        };
+        let mut counts = vec![0; partition_view::SIZE_BAND_COLORS.len().min(bands.len() + 1)];
+        dir.walk(&mut |item, _| {
+            if let crate::analyze::AnalyzedItem::File(f) = item {
+                counts[partition_view::size_band(f.size, bands)] += 1;
+            }
+        });
+        self.band_counts = counts;
+    }
+
+    /// The tree the treemap is built from, before zooming into small items.
+    fn treemap_dir(&self) -> Option<&crate::analyze::AnalyzedDir> {
+        self.filtered.as_ref().or(self.current())
+    }
+
+    /// Resolves `path` to an absolute path without `..` or symlinks, so going up and the history
+    /// follow the real directories.
+    fn canonical_path(&self, path: PathBuf) -> Result<PathBuf, String> {
+        match path.canonicalize() {
+            Ok(path) => Ok(path),
+            // Directories inside an archive only exist in the scan.
+            #[cfg(feature = "archives")]
+            Err(_)
+                if self.config.scan_archives
+                    && path.ancestors().any(crate::archive::is_archive) =>
+            {
+                Ok(path)
+            }
+            // Nor can remote ones be resolved before connecting.
+            #[cfg(feature = "sftp")]
+            Err(_) if crate::sftp::Remote::parse(&path).is_some() => Ok(path),
+            Err(e) => Err(scan_error_message(&path, &e).unwrap_or_default()),
+        }
+    }
+
+    /// Marked items that aren't inside another marked directory, since trashing that takes them
+    /// with it.
+    fn outermost_marked(&self) -> Vec<PathBuf> {
+        let mut outermost: Vec<PathBuf> = Vec::new();
+        for path in &self.marked {
+            if !outermost.last().is_some_and(|f| path.starts_with(f)) {
+                outermost.push(path.clone());
+            }
+        }
+        outermost
+    }
+
+    /// Bytes trashing the marked items would free, counting whole directories.
+    fn marked_size(&self) -> u64 {
+        let Some(analyzed) = &self.analyzed else {
+            return 0;
+        };
+        self.outermost_marked()
+            .iter()
+            .filter_map(|f| analyzed.find(f))
+            .map(crate::analyze::ItemRef::size)
+            .sum()
+    }
+
+    fn new_context(&self) -> crate::analyze::Context {
+        let ctx = crate::analyze::Context::default()
+            .with_min_file_size(self.config.min_file_size)
+            .with_include_empty_files(!self.config.skip_empty_files)
+            .with_max_entries_per_dir(self.config.max_entries_per_dir)
+            .with_detail_threshold(self.config.detail_threshold)
+            // With nothing to show yet, the folders are shown as they're finished.
+            .with_partial_results(self.roots.is_empty().then_some(PARTIAL_DEPTH))
+            .with_dedup_dirs(self.config.dedup_dirs)
+            .with_hidden(self.config.hidden);
+        #[cfg(feature = "archives")]
+        let ctx = ctx.with_scan_archives(self.config.scan_archives);
+        ctx
+    }
+
+    /// Marks a scan as started, returning the context it should report progress to.
+    fn start_scan(&mut self) -> Arc<crate::analyze::Context> {
+        let ctx = Arc::new(self.new_context());
+        // Only the latest scan is kept.
+        if let Some(previous) = &self.scan_context {
+            previous.cancel();
+        }
+        self.crawling_path = true;
+        self.paused = false;
+        self.scan_started = Some(Instant::now());
+        self.scan_context = Some(ctx.clone());
+        self.progress.clear();
+        ctx
+    }
+
+    /// Whether `ctx` is the context of the scan in progress, rather than of one cancelled since.
+    fn is_current_scan(&self, ctx: &Arc<crate::analyze::Context>) -> bool {
+        self.scan_context
+            .as_ref()
+            .is_some_and(|f| Arc::ptr_eq(f, ctx))
+    }
+
+    fn take_hardlinks(&self) -> crate::analyze::HardLinks {
+        self.scan_context
+            .as_ref()
+            .map(|f| f.take_hardlinks())
+            .unwrap_or_default()
+    }
+
+    fn take_scan_errors(&self) -> Vec<crate::analyze::ScanError> {
+        self.scan_context
+            .as_ref()
+            .map(|f| f.take_errors())
+            .unwrap_or_default()
+    }
+
+    /// Remembers the directory being looked at before going to `path`, for going back to it.
+    fn push_history(&mut self, path: &std::path::Path) {
+        if self.roots.len() != 1 {
+            return;
+        }
+        let current = self.current().map(|f| f.path.to_path_buf());
+        if let Some(current) = current.filter(|f| f != path) {
+            self.back.push(current);
+            self.forward.clear();
+        }
+    }
+
+    /// Drops every scanned tree, keeping only the path to offer scanning it again.
+    fn expire_scan(&mut self) {
+        self.expired = self.roots.first().map(|f| f.path.to_path_buf());
+        self.roots.clear();
+        self.baseline = None;
+        self.view = None;
+        self.tail = None;
+        self.scan_errors.clear();
+        self.hardlinks = crate::analyze::HardLinks::default();
+        self.roots_changed();
+    }
+
+    fn finish_scan(&mut self) {
+        self.crawling_path = false;
+        self.paused = false;
+        self.scan_context = None;
+        self.progress.clear();
+        // What was shown of the scan so far gives way to its result, or to nothing if it failed.
+        if self.roots.is_empty() && self.analyzed.is_some() {
+            self.analyzed = None;
+            self.view = None;
+            self.tree_changed();
+        }
+    }
+
+    /// Files and bytes per second over the last [`RATE_WINDOW`].
+    fn scan_rate(&self) -> Option<(f64, f64)> {
+        let ((start, first), (end, last)) = (self.progress.front()?, self.progress.back()?);
+        let secs = end.duration_since(*start).as_secs_f64();
+        (secs > 0.0).then(|| {
+            (
+                (last.files - first.files) as f64 / secs,
+                (last.bytes - first.bytes) as f64 / secs,
+            )
+        })
+    }
+
+    fn progress_text(&self) -> Option<String> {
+        let (_, progress) = self.progress.back()?;
+        let mut text = format!(
+            "{} files, {} scanned",
+            self.format_count(progress.files),
+            self.format_size(progress.bytes)
+        );
+        if self.paused {
+            text.push_str("\nPaused");
+        } else if let Some((files, bytes)) = self.scan_rate() {
+            text.push_str(&format!(
+                "\n{} files/s, {}/s",
+                self.format_count(files.round() as u64),
+                self.format_size(bytes as u64)
+            ));
+            // The used space of the filesystem bounds what's left, so this is a worst case.
+            if let Some(stats) = self.fs_stats.filter(|_| bytes > 0.0) {
+                let left = stats.used.saturating_sub(progress.bytes) as f64 / bytes;
+                text.push_str(&format!(", at most {left:.0}s left"));
+            }
+        }
+        Some(text)
+    }
+
+    fn update_title(&mut self) {
+        let title = format!("COSMIC DirStat - {}", self.display_path(&self.crawl_path));
+        self.core.set_header_title(title);
+    }
+
+    /// How `path` is shown in the heading and title, with the scanned folder it's in labelled as
+    /// configured. Only what's shown changes; everything else goes by the real path.
+    fn display_path(&self, path: &std::path::Path) -> String {
+        let root = self
+            .analyzed
+            .as_ref()
+            .map(|f| &*f.path)
+            .filter(|f| path.starts_with(f))
+            .unwrap_or(path);
+        let name = || {
+            root.file_name()
+                .unwrap_or(root.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        };
+        let (root, label) = match self.config.root_label {
+            config::RootLabel::FullPath => return path.to_string_lossy().into_owned(),
+            config::RootLabel::Home => match std::env::var_os("HOME") {
+                Some(home) if path.starts_with(&home) => (PathBuf::from(home), "~".to_owned()),
+                _ => return path.to_string_lossy().into_owned(),
+            },
+            config::RootLabel::Name => (root.to_path_buf(), name()),
+            config::RootLabel::Custom if self.config.custom_root_label.is_empty() => {
+                (root.to_path_buf(), name())
+            }
+            config::RootLabel::Custom => {
+                (root.to_path_buf(), self.config.custom_root_label.clone())
+            }
+        };
+        match path.strip_prefix(&root) {
+            Ok(rest) if !rest.as_os_str().is_empty() => format!("{label}/{}", rest.display()),
+            _ => label,
+        }
+    }
+
+    /// Scans `s` without touching the navigation history.
+    fn scan(&mut self, s: PathBuf) -> cosmic::app::Task<Msg> {
+        let ctx = self.start_scan();
+        self.crawl_path = s.clone();
+        self.update_title();
+        let stats_path = s.clone();
+        cosmic::Task::batch([
+            cosmic::Task::perform(
+                async move {
+                    let result = crate::analyze::analyze_dir(&s, &ctx);
+                    (ctx, result.map_err(|e| scan_error_message(&s, &e)))
+                },
+                |(ctx, a)| match a {
+                    Ok(a) => Msg::Analyzed(ctx, Arc::new(a)).into(),
+                    Err(Some(e)) => Msg::AnalyzedError(ctx, e).into(),
+                    Err(None) => cosmic::app::Message::None,
+                },
+            ),
+            cosmic::Task::perform(
+                async move { crate::analyze::filesystem_stats(&stats_path).ok() },
+                |f| Msg::FsStats(f).into(),
+            ),
+        ])
+    }
+
+    /// Rebuilds `analyzed` from `roots`, going back to its root if the viewed directory is gone.
+    fn roots_changed(&mut self) {
+        self.analyzed = match self.roots.as_slice() {
+            [] => None,
+            [root] => Some(root.clone()),
+            roots => Some(Arc::new(crate::analyze::AnalyzedDir::combined(roots))),
+        };
+        if let Some(view) = &self.view {
+            if !self
+                .analyzed
+                .as_ref()
+                .is_some_and(|f| f.find_dir(view).is_some())
+            {
+                self.view = None;
+            }
+        }
+        self.tree_changed();
+    }
+
+    /// Expands the outline down to the selected item, or drops the selection if it's not in the
+    /// tree.
+    fn reveal_selected(&mut self) {
+        let Some((selected, analyzed)) = self.selected.clone().zip(self.current()) else {
+            return;
+        };
+        let Some(root) = analyzed
+            .find(&selected)
+            .map(|_| analyzed.path.to_path_buf())
+        else {
+            self.selected = None;
+            self.update_inspected();
+            return;
+        };
+        for ancestor in selected.ancestors().skip(1) {
+            if !ancestor.starts_with(&root) {
+                break;
+            }
+            self.expanded.insert(ancestor.to_path_buf());
+        }
+    }
+
+    /// Selects the `rank`th of [`App::largest_files`], first showing the folder it's in so it's
+    /// drawn large enough to see.
+    fn show_largest(&mut self, rank: usize) -> cosmic::app::Task<Msg> {
+        let Some(path) = self.largest_files.get(rank).cloned() else {
+            return cosmic::Task::none();
+        };
+        self.largest_rank = rank;
+        let parent = path.parent().map(std::path::Path::to_path_buf);
+        let task = match parent {
+            Some(parent) if self.current().is_some_and(|f| *f.path != *parent) => {
+                self.push_history(&parent);
+                self.show_dir(parent)
+            }
+            _ => cosmic::Task::none(),
+        };
+        self.selected = Some(path);
+        self.reveal_selected();
+        self.update_inspected();
+        task
+    }
+
+    /// What going up a level does, or `None` at the filesystem root.
+    fn go_up(&self) -> Option<Msg> {
+        // Going up from the small items returns to their directory.
+        if self.tail_dir.is_some() {
+            return Some(Msg::CloseTail);
+        }
+        let parent = self
+            .current()
+            .and_then(|f| f.path.parent().map(std::borrow::ToOwned::to_owned));
+        // Inside the tree, the parent is already scanned.
+        if self.view.is_some() {
+            return parent.map(Msg::ShowDir);
+        }
+        // The combined view of several roots has no meaningful parent.
+        if self.roots.len() > 1 {
+            return None;
+        }
+        parent.map(Msg::Crawl)
+    }
+
+    fn update_tail(&mut self) {
+        self.tail_dir = match (&self.tail, self.treemap_dir()) {
+            (Some((path, count)), Some(dir)) => dir
+                .find_dir(path)
+                .map(|d| d.tail(*count as usize, self.config.metric)),
+            _ => None,
+        };
+        self.extension_stats = self
+            .tail_dir
+            .as_ref()
+            .or(self.treemap_dir())
+            .map(crate::analyze::AnalyzedDir::extension_stats)
+            .unwrap_or_default();
+    }
+
+    fn update_stale_files(&mut self) {
+        let cutoff = std::time::SystemTime::now()
+            - std::time::Duration::from_secs(self.stale_age.days() * 24 * 60 * 60);
+        self.stale_files = self
+            .current()
+            .map(|a| {
+                crate::analyze::find_older_than(a, cutoff)
+                    .into_iter()
+                    .map(|f| (f.path(), f.size))
+                    .filter(|f| is_local(&f.0))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        self.stale_files
+            .sort_unstable_by_key(|f| std::cmp::Reverse(f.1));
+    }
+
+    /// Bytes trashing the stale files would free.
+    fn stale_size(&self) -> u64 {
+        self.stale_files.iter().map(|f| f.1).sum()
+    }
+
+    pub fn cleanup_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{button, column, row, text};
+
+        let heading = row::with_children(vec![
+            text::heading(format!(
+                "Empty Directories ({})",
+                self.format_count(self.empty_dirs.len() as u64)
+            ))
+            .width(Length::Fill)
+            .into(),
+            button::destructive("Move All to Trash")
+                .on_press_maybe(
+                    (!self.empty_dirs.is_empty()).then_some(Msg::ConfirmTrashEmptyDirs(true)),
+                )
+                .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let list = column::with_children(
+            self.empty_dirs
+                .iter()
+                .take(CLEANUP_MAX_ROWS)
+                .map(|f| text(f.to_string_lossy()).into())
+                .collect(),
+        );
+
+        let reclaimable = self.stale_size();
+        let stale_heading = row::with_children(vec![
+            text::heading(format!(
+                "Untouched Files ({}, {})",
+                self.format_count(self.stale_files.len() as u64),
+                self.format_size(reclaimable)
+            ))
+            .width(Length::Fill)
+            .into(),
+            widget::dropdown(
+                StaleAge::LABELS,
+                Some(self.stale_age.index()),
+                Msg::StaleAgeChanged,
+            )
+            .into(),
+            button::destructive("Move All to Trash")
+                .on_press_maybe(
+                    (!self.stale_files.is_empty()).then_some(Msg::ConfirmTrashStaleFiles(true)),
+                )
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let stale_list = column::with_children(
+            self.stale_files
+                .iter()
+                .take(CLEANUP_MAX_ROWS)
+                .map(|(path, size)| {
+                    row::with_children(vec![
+                        text(path.to_string_lossy()).width(Length::Fill).into(),
+                        text(self.format_size(*size)).into(),
+                    ])
+                    .into()
+                })
+                .collect(),
+        );
+
+        column::with_children(vec![
+            heading.into(),
+            container(scrollable(list))
+                .height(Length::FillPortion(1))
+                .into(),
+            stale_heading.into(),
+            container(scrollable(stale_list))
+                .height(Length::FillPortion(1))
+                .into(),
+        ])
+        .spacing(5.0)
+        .padding(10.0)
+        .into()
+    }
+
     pub fn tree_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::column;
+
+        if self.config.tabbed_panels {
+            return self.tabbed_view();
+        }
+        column::with_children(vec![
+            container(self.outline_view())
+                .height(Length::FillPortion(2))
+                .into(),
+            container(self.legend_view())
+                .height(Length::FillPortion(1))
+                .into(),
+        ])
+        .spacing(5.0)
+        .padding(10.0)
+        .into()
+    }
+
+    /// The tree, legend and inspector, one at a time under tabs picking between them.
+    fn tabbed_view(&self) -> cosmic::Element<Msg> {
+        let tabs = widget::segmented_control::horizontal(&self.panel_tabs)
+            .on_activate(Msg::PanelTabActivated);
+        let content = match self.panel_tabs.active_data::<config::PanelTab>() {
+            Some(config::PanelTab::Legend) => container(self.legend_view()).padding(10.0).into(),
+            Some(config::PanelTab::Inspector) => self.inspector_view(),
+            Some(config::PanelTab::Tree) | None => {
+                container(self.outline_view()).padding(10.0).into()
+            }
+        };
+        widget::column::with_children(vec![
+            container(tabs).padding([10, 10, 0, 10]).into(),
+            content,
+        ])
+        .into()
+    }
+
+    /// Closes the inspector's pane while it's a tab of the tree's, and puts it back next to the
+    /// treemap when it isn't.
+    fn layout_panes(&mut self) {
+        match (self.config.tabbed_panels, self.inspector_pane) {
+            (true, Some(pane)) => {
+                self.state.close(pane);
+                self.inspector_pane = None;
+            }
+            (false, None) => {
+                let split = self.state.split(
+                    widget::pane_grid::Axis::Vertical,
+                    self.partitioned_pane,
+                    Panels::Inspector,
+                );
+                if let Some((pane, split)) = split {
+                    self.state.resize(split, 0.75);
+                    self.inspector_pane = Some(pane);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn outline_view(&self) -> cosmic::Element<Msg> {
         use cosmic::widget::{column, text};
 
+        let sort_icon = if self.config.sort_ascending {
+            "view-sort-ascending-symbolic"
+        } else {
+            "view-sort-descending-symbolic"
+        };
+        let heading = widget::row::with_children(vec![
+            text::heading("Tree").width(Length::Fill).into(),
+            widget::dropdown(
+                config::SortKey::LABELS,
+                Some(self.config.sort_key.index()),
+                Msg::SortKeyChanged,
+            )
+            .into(),
+            widget::button::icon(widget::icon::from_name(sort_icon).handle())
+                .on_press(Msg::SortOrderToggled)
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+        let outline: cosmic::Element<Msg> = match self.current() {
+            Some(a) => {
+                let mut rows = Vec::new();
+                self.outline_rows(a, 0, &mut rows);
+                scrollable(column::with_children(rows)).into()
+            }
+            None => text("No Directory Analyzed").into(),
+        };
+
+        column::with_children(vec![heading.into(), outline])
+            .spacing(5.0)
+            .into()
+    }
+
+    /// Pushes a row for every child of `dir`, descending into expanded directories.
+    fn outline_rows<'a>(
+        &'a self,
+        dir: &'a crate::analyze::AnalyzedDir,
+        depth: usize,
+        rows: &mut Vec<cosmic::Element<'a, Msg>>,
+    ) {
+        let mut children = dir.children.iter().collect::<Vec<_>>();
+        let (key, ascending) = (self.config.sort_key, self.config.sort_ascending);
+        // The analyzed tree is already sorted largest first.
+        if key != config::SortKey::Size || ascending {
+            children.sort_by(|a, b| {
+                let ord = key.cmp(a, b);
+                if ascending {
+                    ord
+                } else {
+                    ord.reverse()
+                }
+            });
+        }
+
+        for child in children.into_iter().take(OUTLINE_MAX_CHILDREN) {
+            rows.push(self.outline_row(child, depth));
+            if let crate::analyze::AnalyzedItem::Dir(d) = child {
+                if self.expanded.contains(&*d.path) {
+                    self.outline_rows(d, depth + 1, rows);
+                }
+            }
+        }
+
+        if let Some(rest) = dir.children.len().checked_sub(OUTLINE_MAX_CHILDREN) {
+            if rest > 0 {
+                rows.push(
+                    widget::row::with_children(vec![
+                        widget::Space::with_width(Length::Fixed(
+                            (depth + 1) as f32 * OUTLINE_INDENT,
+                        ))
+                        .into(),
+                        widget::text::caption(format!("{rest} more items")).into(),
+                    ])
+                    .into(),
+                );
+            }
+        }
+    }
+
+    fn outline_row<'a>(
+        &'a self,
+        item: &'a crate::analyze::AnalyzedItem,
+        depth: usize,
+    ) -> cosmic::Element<'a, Msg> {
+        use cosmic::widget::{button, icon, row, text};
+
+        let path = item.path();
+        let disclosure: cosmic::Element<Msg> = match item {
+            crate::analyze::AnalyzedItem::Dir(d) => {
+                let icon_name = if self.expanded.contains(&*d.path) {
+                    "pan-down-symbolic"
+                } else {
+                    "pan-end-symbolic"
+                };
+                button::icon(icon::from_name(icon_name).handle())
+                    .padding(4)
+                    .on_press(Msg::OutlineToggled(path.clone()))
+                    .into()
+            }
+            _ => widget::Space::with_width(Length::Fixed(24.0)).into(),
+        };
+
+        let name = text(
+            item.name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        )
+        .width(Length::Fill);
+        let size = text(self.format_size(item.size()));
+        let class = if self.selected.as_ref() == Some(&path) {
+            cosmic::theme::Button::Standard
+        } else {
+            cosmic::theme::Button::Text
+        };
+        let label = button::custom(row::with_children(vec![name.into(), size.into()]))
+            .class(class)
+            .width(Length::Fill)
+            .on_press(Msg::OutlineSelected(path));
+
+        row::with_children(vec![
+            widget::Space::with_width(Length::Fixed(depth as f32 * OUTLINE_INDENT)).into(),
+            disclosure,
+            label.into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center)
+        .into()
+    }
+
+    pub fn legend_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{column, text};
+
+        fn legend_row(
+            key: String,
+            parts: [cosmic::Element<'static, Msg>; 3],
+        ) -> cosmic::Element<'static, Msg> {
+            let [col, name, button] = parts;
+            cosmic::widget::row::with_children(vec![
+                text::caption(key).width(Length::Fixed(12.0)).into(),
+                col,
+                name,
+                button,
+            ])
+            .spacing(5.0)
+            .align_y(cosmic::iced::Alignment::Center)
+            .into()
+        }
+
         let heading = text::heading("Legend");
+        if let Some(bands) = self.size_bands() {
+            return column::with_children(vec![heading.into(), self.size_band_legend(bands)])
+                .into();
+        }
+        let heading = match &self.extension_filter {
+            Some(ext) => cosmic::widget::row::with_children(vec![
+                heading.width(Length::Fill).into(),
+                text(format!("Only .{}", ext.to_string_lossy())).into(),
+                widget::button::standard("Show All")
+                    .on_press(Msg::ExtensionFilter(None))
+                    .into(),
+            ])
+            .spacing(5.0)
+            .align_y(cosmic::iced::Alignment::Center)
+            .into(),
+            None => cosmic::Element::from(heading),
+        };
 
-        let mut grid = grid();
-        for (name, col) in &self.extensions_ordered {
-            let name = name.to_string_lossy().into_owned();
+        let mut rows = Vec::new();
+        for (i, (ext, col)) in self.extensions_ordered.iter().enumerate() {
             let col = *col;
-            let name = text(name);
+            // Clicking an extension shows only its files in the treemap.
+            let name = widget::button::text(ext.to_string_lossy().into_owned())
+                .on_press(Msg::ExtensionFilter(Some(ext.clone())))
+                .width(Length::Fill);
+            // With the legend focused, the first rows can be toggled with the number keys, which
+            // are shown next to them.
+            let key = if i < 9 {
+                (i + 1).to_string()
+            } else {
+                String::new()
+            };
             let col = container(widget::Space::new(10.0, 10.0)).class(
                 cosmic::theme::Container::custom(move |theme| {
                     container::Style {
@@ -65,63 +1392,239 @@ impl App {
                     .border(cosmic::iced::border::rounded(2.))
                 }),
             );
-            // .class(cosmic::widget::container::Style::default().background(col));
-            grid = grid.push(col).push(name).insert_row();
+            let hide =
+                widget::button::icon(widget::icon::from_name("view-conceal-symbolic").handle())
+                    .on_press(Msg::ExtensionHidden(ext.clone(), true));
+            rows.push(legend_row(key, [col.into(), name.into(), hide.into()]));
         }
-        let legend = scrollable(grid.row_alignment(cosmic::iced::Alignment::Center));
-        column::Column::with_children(vec![heading.into(), legend.into()])
-            .padding(10.0)
-            .into()
+        let shown = focus_list::FocusList::new(
+            column::with_children(rows),
+            self.extensions_ordered.len(),
+            Msg::ExtensionFilterToggled,
+        )
+        .on_clear(Msg::ExtensionFilter(None));
+
+        // Hidden extensions aren't drawn, so they aren't in the list above.
+        let mut rows = vec![shown.into()];
+        let mut hidden = self.hidden_extensions.iter().collect::<Vec<_>>();
+        hidden.sort_unstable();
+        for ext in hidden {
+            // Struck through with a combining stroke after every character.
+            let name = ext
+                .to_string_lossy()
+                .chars()
+                .flat_map(|f| [f, '\u{336}'])
+                .collect::<String>();
+            let restore =
+                widget::button::text("Restore").on_press(Msg::ExtensionHidden(ext.clone(), false));
+            rows.push(legend_row(
+                String::new(),
+                [
+                    widget::Space::new(10.0, 10.0).into(),
+                    text(name).width(Length::Fill).into(),
+                    restore.into(),
+                ],
+            ));
+        }
+        let legend = scrollable(column::with_children(rows));
+        column::Column::with_children(vec![heading, legend.into()]).into()
+    }
+
+    /// Each size band's color and range, with how many files are in it.
+    fn size_band_legend(&self, bands: &[u64]) -> cosmic::Element<Msg> {
+        use cosmic::widget::text;
+
+        let mut grid = grid().column_spacing(5.0);
+        for (i, count) in self.band_counts.iter().enumerate() {
+            let col = partition_view::SIZE_BAND_COLORS[i];
+            let range = match (i.checked_sub(1).map(|f| bands[f]), bands.get(i)) {
+                (None, Some(end)) => format!("Under {}", self.format_size(*end)),
+                (Some(start), Some(end)) if i + 1 < self.band_counts.len() => {
+                    format!("{} to {}", self.format_size(start), self.format_size(*end))
+                }
+                (Some(start), _) => format!("{} and up", self.format_size(start)),
+                (None, None) => "Any size".into(),
+            };
+            let swatch = container(widget::Space::new(10.0, 10.0)).class(
+                cosmic::theme::Container::custom(move |theme| {
+                    container::Style {
+                        background: Some(col.into()),
+                        ..cosmic::widget::container::Catalog::style(
+                            theme,
+                            &cosmic::theme::Container::Card,
+                        )
+                    }
+                    .border(cosmic::iced::border::rounded(2.))
+                }),
+            );
+            grid = grid
+                .push(swatch)
+                .push(text(range))
+                .push(text::caption(format!(
+                    "{} files",
+                    self.format_count(*count)
+                )))
+                .insert_row();
+        }
+        scrollable(grid.row_alignment(cosmic::iced::Alignment::Center)).into()
     }
 
     pub fn partition_view(&self) -> cosmic::Element<Msg> {
         use cosmic::widget::{button, column, container, icon, row, text};
 
+        let shown = self.tail_dir.as_ref().or(self.treemap_dir());
         let heading_text = text::heading(format!(
-            "Directory{}{}",
-            if self.analyzed.is_some() { " - " } else { "" },
-            self.analyzed
-                .as_ref()
-                .map(|f| f.path.to_string_lossy())
-                .unwrap_or_default()
+            "Directory{}{}{}",
+            if shown.is_some() { " - " } else { "" },
+            shown
+                .map(|f| self.display_path(&f.path))
+                .unwrap_or_default(),
+            match &self.tail_dir {
+                Some(t) => format!(
+                    " ({} small items)",
+                    self.format_count(t.children.len() as u64)
+                ),
+                None if self.roots.len() > 1 => format!(" ({} roots)", self.roots.len()),
+                None => String::new(),
+            }
         ))
         .width(Length::FillPortion(2));
-        let go_up_button = button::icon(icon::from_name("go-up-symbolic").handle()).on_press_maybe(
-            self.analyzed
-                .as_ref()
-                .and_then(|f| f.path.parent().map(std::borrow::ToOwned::to_owned))
-                .map(Msg::Crawl),
-        );
-        let go_up_button = container(go_up_button).align_x(Horizontal::Right);
+        let back_button = button::icon(icon::from_name("go-previous-symbolic").handle())
+            .on_press_maybe((!self.back.is_empty()).then_some(Msg::NavBack));
+        let forward_button = button::icon(icon::from_name("go-next-symbolic").handle())
+            .on_press_maybe((!self.forward.is_empty()).then_some(Msg::NavForward));
+        let go_up_button =
+            button::icon(icon::from_name("go-up-symbolic").handle()).on_press_maybe(self.go_up());
+        let baseline_button = if self.baseline.is_some() {
+            button::standard("Clear Baseline").on_press(Msg::SetBaseline(false))
+        } else {
+            button::standard("Set Baseline")
+                .on_press_maybe(self.analyzed.as_ref().map(|_| Msg::SetBaseline(true)))
+        };
+        let rescan_button = button::icon(icon::from_name("view-refresh-symbolic").handle())
+            .on_press_maybe(
+                self.current()
+                    .filter(|_| !self.crawling_path)
+                    .map(|f| Msg::RescanSubtree(f.path.to_path_buf())),
+            );
+        let maximize_button = button::icon(
+            icon::from_name(if self.state.maximized().is_some() {
+                "view-restore-symbolic"
+            } else {
+                "view-fullscreen-symbolic"
+            })
+            .handle(),
+        )
+        .on_press(Msg::MaximizeToggled);
+        let go_up_button = container(
+            row::with_children(vec![
+                baseline_button.into(),
+                back_button.into(),
+                forward_button.into(),
+                go_up_button.into(),
+                rescan_button.into(),
+                maximize_button.into(),
+            ])
+            .spacing(5.0),
+        )
+        .align_x(Horizontal::Right);
         let heading = row::with_children(vec![heading_text.into(), go_up_button.into()]);
-        let d = match &self.analyzed {
-            Some(d) => cosmic::widget::tooltip(
-                partition_view::PartitionView::new(
+        let d = match shown {
+            Some(d) => {
+                let view = partition_view::PartitionView::new(
                     d,
-                    8.0,
-                    8.0 * 8.0,
-                    Msg::Crawl,
+                    TEXT_SIZE,
+                    if self.tail_dir.is_some() {
+                        TAIL_MINIMUM_AREA
+                    } else {
+                        MINIMUM_AREA
+                    },
+                    Msg::ShowDir,
                     Msg::ExtensionLegendChanged,
                     Msg::NewItemHighlighted,
-                ),
-                match self.highlighted.as_ref() {
-                    Some(s) => cosmic::widget::column()
-                        .push(cosmic::widget::text(s.1.as_str()))
-                        .push(cosmic::widget::text(humansize::format_size(
-                            s.2,
-                            humansize::DECIMAL,
-                        )))
-                        .push(cosmic::widget::text(s.3.to_string_lossy()))
+                )
+                .on_aggregate_click(Msg::AggregateClicked)
+                .on_select(Msg::OutlineSelected)
+                .on_mark(Msg::MarkToggled)
+                .marked(&self.marked)
+                .extension_stats(&self.extension_stats)
+                .minimum_aspect(MINIMUM_ASPECT)
+                .diff(self.diff.as_ref())
+                .selected(self.selected.as_deref())
+                .units(self.config.size_units, self.config.digit_grouping)
+                .high_contrast(self.config.high_contrast)
+                .distinct_dirs(self.config.distinct_dirs)
+                .palette(self.config.palette)
+                .size_bands(self.size_bands())
+                .metric(self.config.metric)
+                .header_height(self.config.header_height.height(TEXT_SIZE))
+                // Quotas are in bytes, so other metrics can't be compared with them.
+                .warn_above(
+                    self.quota()
+                        .filter(|_| self.config.metric == crate::analyze::Metric::Bytes)
+                        .map(|(_, f)| (f as f64 * self.config.quota_warning.fraction()) as u64),
+                )
+                .max_label_chars(self.config.label_length.max_chars())
+                .aggregate_label(
+                    self.config
+                        .aggregate_label
+                        .as_deref()
+                        .unwrap_or(config::DEFAULT_AGGREGATE_LABEL),
+                )
+                .animate(self.config.animate_navigation)
+                .tile_border(partition_view::TileBorder {
+                    width: self.config.tile_borders.width(),
+                    radius: if self.config.rounded_tiles { 3.0 } else { 0.0 },
+                })
+                // The small items are zoomed into to see more of them.
+                .target_tiles(self.config.tile_detail.target_tiles().map(|f| {
+                    if self.tail_dir.is_some() {
+                        f * 2
+                    } else {
+                        f
+                    }
+                }));
+                let treemap = if self.config.compact_hover {
+                    // What's hovered goes in the status bar, so it's shown straight away.
+                    view.hover_delay(std::time::Duration::ZERO).into()
+                } else {
+                    let view = view.hover_delay(self.config.hover_delay.duration());
+                    let popup = match self.highlighted.as_ref() {
+                        Some(s) => cosmic::widget::column::with_children(
+                            self.hover_text(s)
+                                .lines()
+                                .map(|f| cosmic::widget::text(f.to_owned()).into())
+                                .collect(),
+                        )
                         .into(),
-                    None => cosmic::iced::Element::new(cosmic::widget::Space::with_width(
-                        cosmic::iced::Length::Shrink,
-                    )),
-                },
-                widget::tooltip::Position::FollowCursor,
-            )
-            .class(cosmic::theme::Container::Card)
-            .into(),
-            None => text("No Directory Analyzed").into(),
+                        None => cosmic::iced::Element::new(cosmic::widget::Space::with_width(
+                            cosmic::iced::Length::Shrink,
+                        )),
+                    };
+                    cosmic::widget::tooltip(view, popup, widget::tooltip::Position::FollowCursor)
+                        // Clamps the popup so it never spills past the window edges.
+                        .snap_within_viewport(true)
+                        .gap(4.0)
+                        .class(cosmic::theme::Container::Card)
+                        .into()
+                };
+                self.with_overview(treemap)
+            }
+            None => match &self.expired {
+                Some(path) => column::with_children(vec![
+                    text(format!(
+                        "The scan of {} was let go of after a while unused, to free memory.",
+                        path.display()
+                    ))
+                    .into(),
+                    button::standard("Rescan")
+                        .on_press(Msg::Crawl(path.clone()))
+                        .into(),
+                ])
+                .spacing(5.0)
+                .into(),
+                None => text("No Directory Analyzed").into(),
+            },
         };
 
         column::with_children(vec![heading.into(), d])
@@ -129,51 +1632,951 @@ impl App {
             .into()
     }
 
+    /// Puts a small treemap of the whole scan in a corner of `treemap` while inside one of its
+    /// subdirectories, outlining where that is. Clicking it goes to what was clicked.
+    fn with_overview<'a>(&'a self, treemap: cosmic::Element<'a, Msg>) -> cosmic::Element<'a, Msg> {
+        let Some((overview, current)) = self.analyzed.as_deref().zip(self.current()) else {
+            return treemap;
+        };
+        if self.config.hide_overview || self.roots.len() > 1 || self.view.is_none() {
+            return treemap;
+        }
+
+        let overview = cosmic::iced::widget::responsive(move |size| {
+            if size.width < OVERVIEW_SIZE.width * OVERVIEW_MIN_SCALE
+                || size.height < OVERVIEW_SIZE.height * OVERVIEW_MIN_SCALE
+            {
+                return widget::Space::new(0.0, 0.0).into();
+            }
+            let map = partition_view::PartitionView::new(
+                overview,
+                TEXT_SIZE,
+                MINIMUM_AREA,
+                Msg::ShowDir,
+                |_| Msg::OverviewUpdated,
+                |_| Msg::OverviewUpdated,
+            )
+            .labels(false)
+            .selected(Some(&*current.path))
+            .palette(self.config.palette)
+            .metric(self.config.metric);
+            container(
+                container(map)
+                    .width(OVERVIEW_SIZE.width)
+                    .height(OVERVIEW_SIZE.height)
+                    .padding(2.0)
+                    .class(cosmic::theme::Container::Card),
+            )
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(Horizontal::Right)
+            .align_y(cosmic::iced::alignment::Vertical::Bottom)
+            .padding(10.0)
+            .into()
+        });
+        cosmic::iced::widget::Stack::with_children(vec![treemap, overview.into()]).into()
+    }
+
+    /// The item the inspector shows. What's selected stays shown while hovering over other tiles.
+    fn inspected_path(&self) -> Option<&std::path::Path> {
+        self.selected.as_deref().or_else(|| {
+            self.highlighted
+                .as_ref()
+                .filter(|f| f.aggregated.is_none())
+                .map(|f| f.path.as_path())
+        })
+    }
+
+    /// Reads what the inspector shows from disk when it moved to another item. The scan only
+    /// keeps what the treemap needs, and this is done here rather than while drawing so hovering
+    /// doesn't hit the disk on every frame.
+    fn update_inspected(&mut self) {
+        use std::os::unix::fs::MetadataExt;
+
+        let Some(path) = self.inspected_path() else {
+            self.inspected = None;
+            return;
+        };
+        if self.inspected.as_ref().is_some_and(|f| f.0 == path) {
+            return;
+        }
+        let metadata = if is_local(path) {
+            std::fs::symlink_metadata(path).ok()
+        } else {
+            None
+        };
+        let details = metadata.map(|m| DiskDetails {
+            owner: crate::analyze::user_name(m.uid()).unwrap_or_else(|| m.uid().to_string()),
+            hard_links: (!m.is_dir()).then(|| m.nlink()),
+        });
+        self.inspected = Some((path.to_path_buf(), details));
+    }
+
+    /// Details of the selected item, or of the hovered one while nothing is selected.
+    pub fn inspector_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{column, text};
+
+        let heading = text::heading("Inspector");
+        let Some((path, analyzed)) = self.inspected_path().zip(self.analyzed.as_deref()) else {
+            return column::with_children(vec![heading.into(), text("Nothing selected").into()])
+                .spacing(5.0)
+                .padding(10.0)
+                .into();
+        };
+
+        let mut rows = vec![
+            (
+                "Name",
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            ("Path", path.to_string_lossy().into_owned()),
+        ];
+        let item = analyzed.find(path);
+        let dir = item.and_then(crate::analyze::ItemRef::dir);
+        if let Some(item) = item {
+            rows.push(("Allocated", self.format_size(item.size())));
+            rows.push(("Apparent", self.format_size(item.apparent_size())));
+        }
+        if let Some(modified) = item.and_then(crate::analyze::ItemRef::modified) {
+            rows.push(("Modified", crate::format::format_age(modified)));
+        }
+        // Remote items and those inside archives can't be read from disk.
+        match self.inspected.as_ref().filter(|f| f.0 == path) {
+            Some((_, Some(details))) => {
+                rows.push(("Owner", details.owner.clone()));
+                if let Some(links) = details.hard_links {
+                    rows.push(("Hard links", self.format_count(links)));
+                }
+            }
+            Some((_, None)) => rows.push(("Owner", "Unavailable".into())),
+            None => {}
+        }
+        if let Some(d) = dir {
+            rows.push(("Files", self.format_count(d.num_files)));
+            rows.push(("Directories", self.format_count(d.num_dirs)));
+            rows.push(("Symlinks", self.format_count(d.num_symlinks)));
+            if d.summarized {
+                rows.push(("Summarized", "Open to scan what's inside".into()));
+            }
+            if d.truncated {
+                rows.push((
+                    "Lumped",
+                    format!("{} smallest entries", self.format_count(d.skipped_files)),
+                ));
+            }
+        }
+
+        let mut details = grid().column_spacing(10.0).row_spacing(2.0);
+        for (label, value) in rows {
+            details = details
+                .push(text::body(label))
+                .push(text(value))
+                .insert_row();
+        }
+        let mut details = column().spacing(5.0).push(details);
+        if let Some(note) = dir.and_then(|d| self.slack_note(d)) {
+            details = details.push(text::caption(note));
+        }
+        if let Some(links) = self.hardlinks.of(path) {
+            details = details.push(
+                widget::button::text(if self.show_hardlinks {
+                    "Hide Hard Links"
+                } else {
+                    "Show All Hard Links"
+                })
+                .on_press(Msg::ShowHardLinks(!self.show_hardlinks)),
+            );
+            if self.show_hardlinks {
+                for link in links.iter().filter(|f| **f != *path) {
+                    details = details.push(
+                        widget::button::link(link.to_string_lossy().into_owned())
+                            .on_press(Msg::OutlineSelected(link.clone())),
+                    );
+                }
+            }
+        }
+        column::with_children(vec![heading.into(), scrollable(details).into()])
+            .spacing(5.0)
+            .padding(10.0)
+            .into()
+    }
+
+    /// What the hover popup says about `h`, filled in from the configured template.
+    fn hover_text(&self, h: &partition_view::HoverInfo) -> String {
+        let name = match h.aggregated {
+            Some(n) => format!("{} small items", self.format_count(n)),
+            None => match self.config.label_length.max_chars() {
+                Some(max) => crate::format::ellipsize_middle(&h.name, max),
+                None => h.name.clone(),
+            },
+        };
+        let template = self
+            .config
+            .hover_template
+            .as_deref()
+            .unwrap_or(config::DEFAULT_HOVER_TEMPLATE);
+        crate::format::fill_template(template, |field| {
+            Some(match field {
+                "name" => name.clone(),
+                "size" => self.format_metric(h.size, h.metric),
+                "percent" => format!("{:.1}%", h.percent_of_parent()),
+                "total_percent" => format!("{:.1}%", h.percent_of_total()),
+                "count" => self.format_count(h.files),
+                "modified" => h
+                    .modified
+                    .map(crate::format::format_age)
+                    .unwrap_or_default(),
+                "path" => h.path.to_string_lossy().into_owned(),
+                _ => return None,
+            })
+        })
+    }
+
+    /// Explains why `dir` takes up much more space than its contents add up to, if it does.
+    fn slack_note(&self, dir: &crate::analyze::AnalyzedDir) -> Option<String> {
+        let ratio = dir.size as f64 / dir.apparent_size.max(1) as f64;
+        if dir.size < SLACK_MIN_SIZE || ratio < SLACK_RATIO || dir.num_files == 0 {
+            return None;
+        }
+        Some(format!(
+            "Takes up {ratio:.1}× its contents on disk. Every file fills whole blocks, so with \
+             {} files averaging {} each, much of this is the unused space left in them.",
+            self.format_count(dir.num_files),
+            self.format_size(dir.apparent_size / dir.num_files),
+        ))
+    }
+
+    /// The direct children of the scanned directory as bars, largest first, for a quick look at
+    /// where the space went before going into the treemap.
+    pub fn summary_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{column, row, text};
+
+        let heading = text::heading("Top Level");
+        let Some(analyzed) = &self.analyzed else {
+            return column::with_children(vec![heading.into(), text("Nothing scanned").into()])
+                .spacing(5.0)
+                .padding(10.0)
+                .into();
+        };
+
+        let mut children = analyzed.children.iter().collect::<Vec<_>>();
+        children.sort_unstable_by_key(|f| std::cmp::Reverse(f.size()));
+        let total = analyzed.size.max(1) as f32;
+        let mut bars = column().spacing(5.0);
+        for child in children.into_iter().take(OUTLINE_MAX_CHILDREN) {
+            let name = child
+                .name()
+                .map(|f| f.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let size = child.size();
+            let label = row::with_children(vec![
+                text(name).width(Length::Fill).into(),
+                text(format!(
+                    "{} ({:.1}%)",
+                    self.format_size(size),
+                    size as f32 / total * 100.0
+                ))
+                .into(),
+            ]);
+            let bar = column::with_children(vec![
+                label.into(),
+                widget::progress_bar(0.0..=total, size as f32).into(),
+            ])
+            .spacing(2.0);
+            // Clicking a bar goes into it like clicking its tile does.
+            bars = bars.push(widget::mouse_area(bar).on_press(Msg::ShowDir(child.path())));
+        }
+        column::with_children(vec![heading.into(), scrollable(bars).into()])
+            .spacing(5.0)
+            .padding(10.0)
+            .into()
+    }
+
+    fn scan_errors_dialog(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{button, column, container, dialog, text};
+
+        let mut list = column().spacing(2.0);
+        for (label, paths) in group_scan_errors(&self.scan_errors) {
+            list = list.push(text::heading(format!(
+                "{label} ({})",
+                self.format_count(paths.len() as u64)
+            )));
+            for path in paths.iter().take(CLEANUP_MAX_ROWS) {
+                list = list.push(text(path.to_string_lossy()));
+            }
+            if paths.len() > CLEANUP_MAX_ROWS {
+                list = list.push(text::caption(format!(
+                    "and {} more",
+                    self.format_count((paths.len() - CLEANUP_MAX_ROWS) as u64)
+                )));
+            }
+        }
+
+        dialog()
+            .title("Skipped paths")
+            .body("These couldn't be scanned, so they aren't counted in any size.")
+            .control(container(scrollable(list)).max_height(400.0))
+            .primary_action(button::standard("Close").on_press(Msg::ShowScanErrors(false)))
+            .secondary_action(button::standard("Copy").on_press(Msg::CopyScanErrors))
+            .tertiary_action(button::text("Dismiss").on_press(Msg::DismissScanErrors))
+            .into()
+    }
+
+    pub fn status_bar(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{row, text};
+
+        let Some(a) = self.current() else {
+            return text::caption("Nothing scanned").into();
+        };
+        let mut summary = format!(
+            "{} ({} apparent) in {} files, {} directories, {} symlinks",
+            self.format_size(a.size),
+            self.format_size(a.apparent_size),
+            self.format_count(a.num_files),
+            self.format_count(a.num_dirs),
+            self.format_count(a.num_symlinks),
+        );
+        if let Some(d) = self.scan_duration {
+            summary.push_str(&format!(", scanned in {:.2}s", d.as_secs_f64()));
+        }
+        // In compact mode the hovered item takes the place of the summary.
+        if let Some(h) = self
+            .highlighted
+            .as_ref()
+            .filter(|_| self.config.compact_hover)
+        {
+            let name = match h.aggregated {
+                Some(n) => format!("{} small items", self.format_count(n)),
+                None => h.name.clone(),
+            };
+            summary = format!(
+                "{name} - {} ({:.1}% of folder)",
+                self.format_metric(h.size, h.metric),
+                h.percent_of_parent()
+            );
+        }
+        let mut row = row::with_children(vec![text::caption(summary).into()]);
+        // What couldn't be read is left out, so the totals are a lower bound.
+        let skipped = match self.scan_errors.len() {
+            0 => None,
+            1 => Some("1 path skipped".to_string()),
+            n => Some(format!("{} paths skipped", self.format_count(n as u64))),
+        };
+        if let Some(skipped) = skipped {
+            row =
+                row.push(cosmic::widget::button::link(skipped).on_press(Msg::ShowScanErrors(true)));
+        }
+        if !self.marked.is_empty() {
+            row = row.push(
+                cosmic::widget::button::destructive(format!(
+                    "Trash {} marked (frees {})",
+                    self.format_count(self.marked.len() as u64),
+                    self.format_size(self.marked_size())
+                ))
+                .on_press(Msg::ConfirmTrashMarked(true)),
+            );
+        }
+        row.spacing(5.0)
+            .align_y(cosmic::iced::Alignment::Center)
+            .padding([0, 10])
+            .into()
+    }
+
     pub fn path_and_title(&self) -> cosmic::Element<Msg> {
         use cosmic::widget::{button, column, container, icon, row, text, text_input};
 
-        let title = text::title1("COSMIC DirStat");
-        let sub = text::caption(concat!("v", env!("CARGO_PKG_VERSION")));
+        let title = text::title1("COSMIC DirStat");
+        let sub = text::caption(concat!("v", env!("CARGO_PKG_VERSION")));
+
+        let title_box = container(
+            column::with_children(vec![title.into(), sub.into()])
+                .align_x(cosmic::iced::Alignment::End)
+                .height(Length::Fill)
+                .width(Length::Fill),
+        )
+        .align_x(Horizontal::Right);
+
+        let path_input = text_input("path/to/analyzed/dir", self.crawl_path.to_string_lossy())
+            .on_input(|f| Msg::CrawlPathChanged(PathBuf::from(f)));
+        let submit_button = button::standard(if !self.crawling_path {
+            "Scan"
+        } else {
+            "Cancel"
+        })
+        .on_press(Msg::CrawlPath {
+            cancel: self.crawling_path,
+        });
+
+        let open_folder = button::icon(icon::from_name("folder-open-symbolic").handle())
+            .on_press(Msg::CrawlPathDialogue);
+
+        let settings = button::icon(icon::from_name("preferences-system-symbolic").handle())
+            .on_press(Msg::SettingsToggled(true));
+
+        let path_input =
+            row::with_children(vec![path_input.into(), open_folder.into(), settings.into()])
+                .spacing(5.0)
+                .align_y(cosmic::iced::Alignment::Center);
+
+        let watch_toggle =
+            widget::checkbox("Watch for changes", self.watching).on_toggle(Msg::WatchToggled);
+        let has_quota = self
+            .current()
+            .is_some_and(|f| self.config.quotas.contains_key(&*f.path));
+        let quota = row::with_children(vec![
+            text_input("Quota, like 10 GiB", self.quota_input.as_str())
+                .on_input(Msg::QuotaInputChanged)
+                .into(),
+            button::standard("Set Quota")
+                .on_press_maybe(
+                    crate::format::parse_size(&self.quota_input)
+                        .filter(|_| self.analyzed.is_some())
+                        .map(|_| Msg::SetQuota),
+                )
+                .into(),
+            button::destructive("Clear")
+                .on_press_maybe(has_quota.then_some(Msg::ClearQuota))
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+        let export = row::with_children(vec![
+            widget::dropdown(
+                ExportResolution::LABELS,
+                Some(self.export_resolution.index()),
+                Msg::ExportResolutionChanged,
+            )
+            .into(),
+            widget::checkbox("Legend", self.export_legend)
+                .on_toggle(Msg::ExportLegendToggled)
+                .into(),
+            button::standard("Export PNG")
+                .on_press_maybe(self.analyzed.as_ref().map(|_| Msg::ExportImageDialogue))
+                .into(),
+            button::standard("Export ncdu")
+                .on_press_maybe(
+                    self.analyzed
+                        .as_ref()
+                        .map(|_| Msg::ExportTreeDialogue(crate::export::TreeFormat::Ncdu)),
+                )
+                .into(),
+            button::standard("Export du")
+                .on_press_maybe(
+                    self.analyzed
+                        .as_ref()
+                        .map(|_| Msg::ExportTreeDialogue(crate::export::TreeFormat::DuAll)),
+                )
+                .into(),
+            button::standard("Copy Report")
+                .on_press_maybe(self.analyzed.as_ref().map(|_| Msg::CopyReport))
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let add_root_button = button::standard("Add Root").on_press_maybe(
+            (!self.crawling_path && !self.roots.is_empty()).then_some(Msg::AddRoot),
+        );
+        let pause_button = button::standard(if self.paused { "Resume" } else { "Pause" })
+            .on_press_maybe(self.crawling_path.then_some(Msg::PauseToggled));
+        let submit_button = row::with_children(vec![
+            submit_button.into(),
+            pause_button.into(),
+            add_root_button.into(),
+        ])
+        .spacing(5.0);
+
+        let mut roots = column().spacing(2.0);
+        if self.roots.len() > 1 {
+            for (i, root) in self.roots.iter().enumerate() {
+                roots = roots.push(
+                    row::with_children(vec![
+                        text(root.path.to_string_lossy()).width(Length::Fill).into(),
+                        button::icon(icon::from_name("list-remove-symbolic").handle())
+                            .on_press(Msg::RemoveRoot(i))
+                            .into(),
+                    ])
+                    .align_y(cosmic::iced::Alignment::Center),
+                );
+            }
+        }
+
+        let mut input_box =
+            column::with_children(vec![path_input.into(), submit_button.into()]).spacing(5.0);
+        if let Some(progress) = self.progress_text() {
+            input_box = input_box.push(text::caption(progress));
+        }
+        input_box = input_box
+            .push(roots)
+            .push(watch_toggle)
+            .push(quota)
+            .push(export);
+
+        if let Some(stats) = self.fs_stats {
+            let scanned = self.analyzed.as_ref().map_or(0, |f| f.size);
+            let gauge = widget::progress_bar(0.0..=stats.total as f32, stats.used as f32);
+            let caption = text::caption(format!(
+                "Scanned {} of {} used, {} free, {} total",
+                self.format_size(scanned),
+                self.format_size(stats.used),
+                self.format_size(stats.free),
+                self.format_size(stats.total),
+            ));
+            input_box = input_box.push(gauge).push(caption);
+        }
+        if let Some((dir, quota)) = self.quota() {
+            let gauge = widget::progress_bar(0.0..=quota as f32, dir.size.min(quota) as f32);
+            let mut caption = format!(
+                "{} of {} quota used by {}",
+                self.format_size(dir.size),
+                self.format_size(quota),
+                dir.path.display()
+            );
+            if dir.size > quota {
+                caption.push_str(&format!(", {} over", self.format_size(dir.size - quota)));
+            }
+            input_box = input_box.push(gauge).push(text::caption(caption));
+        }
+
+        column::with_children(vec![title_box.into(), input_box.into()])
+            .padding(10.0)
+            .into()
+    }
+
+    /// Every setting, in sections, shown in place of the panels while they're being changed.
+    pub fn settings_view(&self) -> cosmic::Element<Msg> {
+        use cosmic::widget::{button, column, row, text, text_input};
+
+        fn section<'a>(
+            title: &'a str,
+            items: Vec<cosmic::Element<'a, Msg>>,
+        ) -> cosmic::Element<'a, Msg> {
+            let mut section = column::with_children(vec![text::heading(title).into()]);
+            for item in items {
+                section = section.push(item);
+            }
+            section.spacing(5.0).into()
+        }
+
+        let scan_last_toggle = widget::checkbox(
+            "Scan last directory on startup",
+            self.config.scan_last_on_startup,
+        )
+        .on_toggle(Msg::ScanLastOnStartupToggled);
+        let animate_toggle = widget::checkbox("Animate navigation", self.config.animate_navigation)
+            .on_toggle(Msg::AnimateNavigationToggled);
+        let high_contrast_toggle =
+            widget::checkbox("High contrast treemap", self.config.high_contrast)
+                .on_toggle(Msg::HighContrastToggled);
+
+        let refresh = row::with_children(vec![
+            text("Auto-refresh").width(Length::Fill).into(),
+            widget::dropdown(
+                config::RefreshInterval::LABELS,
+                Some(self.config.refresh_interval.index()),
+                Msg::RefreshIntervalChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let idle_timeout = row::with_children(vec![
+            text("Free memory when unused for")
+                .width(Length::Fill)
+                .into(),
+            widget::dropdown(
+                config::IdleTimeout::LABELS,
+                Some(self.config.idle_timeout.index()),
+                Msg::IdleTimeoutChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let quota_warning = row::with_children(vec![
+            text("Tint folders using quota past")
+                .width(Length::Fill)
+                .into(),
+            widget::dropdown(
+                config::QuotaWarning::LABELS,
+                Some(self.config.quota_warning.index()),
+                Msg::QuotaWarningChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let hover_delay = row::with_children(vec![
+            text("Hover popup delay").width(Length::Fill).into(),
+            widget::dropdown(
+                config::HoverDelay::LABELS,
+                Some(self.config.hover_delay.index()),
+                Msg::HoverDelayChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+        let hover_template = row::with_children(vec![
+            text("Hover popup").width(Length::Fill).into(),
+            text_input(
+                config::DEFAULT_HOVER_TEMPLATE,
+                self.config.hover_template.as_deref().unwrap_or_default(),
+            )
+            .on_input(Msg::HoverTemplateChanged)
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let detail = row::with_children(vec![
+            text("Smallest tiles").width(Length::Fill).into(),
+            widget::dropdown(
+                config::TileDetail::LABELS,
+                Some(self.config.tile_detail.index()),
+                Msg::TileDetailChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let label_length = row::with_children(vec![
+            text("Longest names").width(Length::Fill).into(),
+            widget::dropdown(
+                config::LabelLength::LABELS,
+                Some(self.config.label_length.index()),
+                Msg::LabelLengthChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let borders = row::with_children(vec![
+            text("Tile borders").width(Length::Fill).into(),
+            widget::checkbox("Rounded", self.config.rounded_tiles)
+                .on_toggle(Msg::RoundedTilesToggled)
+                .into(),
+            widget::dropdown(
+                config::TileBorders::LABELS,
+                Some(self.config.tile_borders.index()),
+                Msg::TileBordersChanged,
+            )
+            .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let metric = row::with_children(vec![
+            text("Size tiles by").width(Length::Fill).into(),
+            widget::dropdown(
+                crate::analyze::Metric::LABELS,
+                Some(self.config.metric.index()),
+                Msg::MetricChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let headers = row::with_children(vec![
+            text("Folder headers").width(Length::Fill).into(),
+            widget::dropdown(
+                config::HeaderHeight::LABELS,
+                Some(self.config.header_height.index()),
+                Msg::HeaderHeightChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        // Left empty, the placeholder is what's used.
+        let aggregate_label = row::with_children(vec![
+            text("Small items label").width(Length::Fill).into(),
+            text_input(
+                config::DEFAULT_AGGREGATE_LABEL,
+                self.config.aggregate_label.as_deref().unwrap_or_default(),
+            )
+            .on_input(Msg::AggregateLabelChanged)
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let palette = row::with_children(vec![
+            text("Colors").width(Length::Fill).into(),
+            widget::dropdown(
+                config::Palette::LABELS,
+                Some(self.config.palette.index()),
+                Msg::PaletteChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let color_mode = row::with_children(vec![
+            text("Color files by").width(Length::Fill).into(),
+            widget::dropdown(
+                config::ColorMode::LABELS,
+                Some(self.config.color_mode.index()),
+                Msg::ColorModeChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+        // Left empty, the placeholder shows the bands in use.
+        let bands = self
+            .config
+            .size_bands
+            .as_deref()
+            .unwrap_or(&config::DEFAULT_SIZE_BANDS)
+            .iter()
+            .map(|f| self.format_size(*f))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let size_bands = row::with_children(vec![
+            text("Size bands start at").width(Length::Fill).into(),
+            text_input(bands, self.size_bands_input.as_str())
+                .on_input(Msg::SizeBandsChanged)
+                .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let root_label = row::with_children(vec![
+            text("Show the scanned folder as")
+                .width(Length::Fill)
+                .into(),
+            widget::dropdown(
+                config::RootLabel::LABELS,
+                Some(self.config.root_label.index()),
+                Msg::RootLabelChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+        let custom_root_label = row::with_children(vec![
+            text("Custom label for the scanned folder")
+                .width(Length::Fill)
+                .into(),
+            text_input("Folder name", self.config.custom_root_label.as_str())
+                .on_input(Msg::CustomRootLabelChanged)
+                .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let min_file_size = row::with_children(vec![
+            text("Skip files smaller than").width(Length::Fill).into(),
+            widget::dropdown(
+                MinFileSize::LABELS,
+                MinFileSize::ALL
+                    .iter()
+                    .position(|f| f.bytes() == self.config.min_file_size),
+                Msg::MinFileSizeChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let max_entries = row::with_children(vec![
+            text("Entries kept per folder").width(Length::Fill).into(),
+            widget::dropdown(
+                MaxEntries::LABELS,
+                MaxEntries::ALL
+                    .iter()
+                    .position(|f| f.count() == self.config.max_entries_per_dir),
+                Msg::MaxEntriesChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        let detail_threshold = row::with_children(vec![
+            text("Only look inside folders over")
+                .width(Length::Fill)
+                .into(),
+            widget::dropdown(
+                DetailThreshold::LABELS,
+                DetailThreshold::ALL
+                    .iter()
+                    .position(|f| f.bytes() == self.config.detail_threshold),
+                Msg::DetailThresholdChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+
+        // Changing any of the settings below leaves the profile, unless they match another one.
+        let current_profile = self.current_profile();
+        let is_user_profile =
+            current_profile.is_some_and(|f| f >= config::ScanProfile::builtin().len());
+        let profile = row::with_children(vec![
+            text("Profile").width(Length::Fill).into(),
+            widget::dropdown(
+                self.profile_names.as_slice(),
+                current_profile,
+                Msg::ProfileSelected,
+            )
+            .into(),
+            button::standard("Delete")
+                .on_press_maybe(is_user_profile.then_some(Msg::DeleteProfile))
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
+        let save_profile = row::with_children(vec![
+            text_input("Profile name", self.profile_name.as_str())
+                .on_input(Msg::ProfileNameChanged)
+                .into(),
+            button::standard("Save Profile")
+                .on_press_maybe((!self.profile_name.trim().is_empty()).then_some(Msg::SaveProfile))
+                .into(),
+        ])
+        .spacing(5.0)
+        .align_y(cosmic::iced::Alignment::Center);
 
-        let title_box = container(
-            column::with_children(vec![title.into(), sub.into()])
-                .align_x(cosmic::iced::Alignment::End)
-                .height(Length::Fill)
-                .width(Length::Fill),
-        )
-        .align_x(Horizontal::Right);
+        #[cfg(feature = "archives")]
+        let archives = Some(
+            widget::checkbox("Look inside archives", self.config.scan_archives)
+                .on_toggle(Msg::ScanArchivesToggled)
+                .into(),
+        );
+        #[cfg(not(feature = "archives"))]
+        let archives = None;
 
-        let path_input = text_input("path/to/analyzed/dir", self.crawl_path.to_string_lossy())
-            .on_input(|f| Msg::CrawlPathChanged(PathBuf::from(f)));
-        let submit_button = button::standard(if !self.crawling_path {
-            "Scan"
-        } else {
-            "Cancel"
-        })
-        .on_press(Msg::CrawlPath {
-            cancel: self.crawling_path,
-        });
+        let hidden = row::with_children(vec![
+            text("Hidden entries").width(Length::Fill).into(),
+            widget::dropdown(
+                crate::analyze::HiddenMode::LABELS,
+                Some(self.config.hidden.index()),
+                Msg::HiddenModeChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
 
-        let open_folder = button::icon(icon::from_name("folder-open-symbolic").handle())
-            .on_press(Msg::CrawlPathDialogue);
+        let units = row::with_children(vec![
+            text("Units").width(Length::Fill).into(),
+            widget::dropdown(
+                crate::format::SizeUnits::LABELS,
+                Some(self.config.size_units.index()),
+                Msg::SizeUnitsChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
 
-        let path_input = row::with_children(vec![path_input.into(), open_folder.into()])
-            .spacing(5.0)
-            .align_y(cosmic::iced::Alignment::Center);
+        let grouping = row::with_children(vec![
+            text("Digit grouping").width(Length::Fill).into(),
+            widget::dropdown(
+                crate::format::DigitGrouping::LABELS,
+                Some(self.config.digit_grouping.index()),
+                Msg::DigitGroupingChanged,
+            )
+            .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
 
-        let input_box =
-            column::with_children(vec![path_input.into(), submit_button.into()]).spacing(5.0);
+        let mut scanning: Vec<cosmic::Element<Msg>> = vec![
+            profile.into(),
+            save_profile.into(),
+            min_file_size.into(),
+            max_entries.into(),
+            detail_threshold.into(),
+            hidden.into(),
+            widget::checkbox("Include empty files", !self.config.skip_empty_files)
+                .on_toggle(Msg::IncludeEmptyFilesToggled)
+                .into(),
+        ];
+        scanning.extend(archives);
+        scanning.extend([
+            widget::checkbox("Count bind-mounted folders once", self.config.dedup_dirs)
+                .on_toggle(Msg::DedupDirsToggled)
+                .into(),
+            scan_last_toggle.into(),
+            refresh.into(),
+            idle_timeout.into(),
+        ]);
+        let scanning = section("Scanning", scanning);
+        let treemap = section(
+            "Treemap",
+            vec![
+                metric.into(),
+                detail.into(),
+                label_length.into(),
+                borders.into(),
+                headers.into(),
+                aggregate_label.into(),
+                palette.into(),
+                color_mode.into(),
+                size_bands.into(),
+                quota_warning.into(),
+                high_contrast_toggle.into(),
+                widget::checkbox("Set folders apart from files", self.config.distinct_dirs)
+                    .on_toggle(Msg::DistinctDirsToggled)
+                    .into(),
+                animate_toggle.into(),
+                widget::checkbox(
+                    "Show overview inside subdirectories",
+                    !self.config.hide_overview,
+                )
+                .on_toggle(Msg::OverviewToggled)
+                .into(),
+            ],
+        );
+        let hover = section(
+            "Hovering",
+            vec![
+                widget::checkbox("Show hovered item in status bar", self.config.compact_hover)
+                    .on_toggle(Msg::CompactHoverToggled)
+                    .into(),
+                hover_delay.into(),
+                hover_template.into(),
+            ],
+        );
+        let display = section(
+            "Display",
+            vec![
+                widget::checkbox(
+                    "Show tree, legend and inspector as tabs",
+                    self.config.tabbed_panels,
+                )
+                .on_toggle(Msg::TabbedPanelsToggled)
+                .into(),
+                root_label.into(),
+                custom_root_label.into(),
+                units.into(),
+                grouping.into(),
+            ],
+        );
 
-        column::with_children(vec![title_box.into(), input_box.into()])
-            .padding(10.0)
-            .into()
+        let header = row::with_children(vec![
+            text::title3("Settings").width(Length::Fill).into(),
+            button::suggested("Done")
+                .on_press(Msg::SettingsToggled(false))
+                .into(),
+        ])
+        .align_y(cosmic::iced::Alignment::Center);
+        let sections = column::with_children(vec![scanning, treemap, hover, display])
+            .spacing(20.0)
+            .max_width(600.0);
+        column::with_children(vec![
+            header.into(),
+            scrollable(
+                container(sections)
+                    .width(Length::Fill)
+                    .align_x(Horizontal::Center),
+            )
+            .into(),
+        ])
+        .spacing(10.0)
+        .padding(10.0)
+        .into()
     }
 }
 
 impl cosmic::Application for App {
     type Executor = cosmic::executor::Default;
 
-    type Flags = ();
+    /// Directory to scan on startup.
+    type Flags = Option<PathBuf>;
 
     type Message = Msg;
 
@@ -189,10 +2592,10 @@ impl cosmic::Application for App {
 
     fn init(
         mut core: cosmic::app::Core,
-        _flags: Self::Flags,
+        flags: Self::Flags,
     ) -> (Self, cosmic::app::Task<Self::Message>) {
         let (mut state, tree_panel) = cosmic::widget::pane_grid::State::new(Panels::Tree);
-        let (_partitioned_panel, header_partitioned_split) = state
+        let (partitioned_panel, header_partitioned_split) = state
             .split(
                 widget::pane_grid::Axis::Horizontal,
                 tree_panel,
@@ -200,6 +2603,22 @@ impl cosmic::Application for App {
             )
             .unwrap();
         state.resize(header_partitioned_split, 0.33);
+        let (inspector_panel, partitioned_inspector_split) = state
+            .split(
+                widget::pane_grid::Axis::Vertical,
+                partitioned_panel,
+                Panels::Inspector,
+            )
+            .unwrap();
+        state.resize(partitioned_inspector_split, 0.75);
+        let (_summary_panel, inspector_summary_split) = state
+            .split(
+                widget::pane_grid::Axis::Horizontal,
+                inspector_panel,
+                Panels::Summary,
+            )
+            .unwrap();
+        state.resize(inspector_summary_split, 0.5);
         let (_name_path_panel, name_path_tree_split) = state
             .split(
                 widget::pane_grid::Axis::Vertical,
@@ -208,48 +2627,154 @@ impl cosmic::Application for App {
             )
             .unwrap();
         state.resize(name_path_tree_split, 0.4);
+        let (_cleanup_panel, tree_cleanup_split) = state
+            .split(
+                widget::pane_grid::Axis::Vertical,
+                tree_panel,
+                Panels::Cleanup,
+            )
+            .unwrap();
+        state.resize(tree_cleanup_split, 0.5);
 
         core.set_header_title("COSMIC DirStat".into());
 
-        let app = Self {
+        let (config_handler, config) = config::Config::load(Self::APP_ID);
+        let mut panel_tabs = widget::segmented_button::SingleSelectModel::default();
+        for (&tab, &label) in config::PanelTab::ALL.iter().zip(config::PanelTab::LABELS) {
+            let id = panel_tabs.insert().text(label).data(tab).id();
+            if tab == config.panel_tab {
+                panel_tabs.activate(id);
+            }
+        }
+
+        let mut app = Self {
             core,
+            config_handler,
+            config,
+            profile_names: Vec::new(),
+            profile_name: String::new(),
+            quota_input: String::new(),
             crawl_path: PathBuf::new(),
             crawling_path: false,
             state,
+            partitioned_pane: partitioned_panel,
+            inspector_pane: Some(inspector_panel),
+            panel_tabs,
             analyzed: None,
+            view: None,
             error: None,
             extensions_ordered: Vec::new(),
             highlighted: None,
+            watching: false,
+            fs_stats: None,
+            baseline: None,
+            diff: None,
+            export_resolution: ExportResolution::default(),
+            export_legend: true,
+            expanded: HashSet::new(),
+            selected: None,
+            largest_files: Vec::new(),
+            largest_rank: 0,
+            marked: std::collections::BTreeSet::new(),
+            confirm_trash_marked: false,
+            confirm_trash_empty_dirs: false,
+            confirm_trash_stale_files: false,
+            empty_dirs: Vec::new(),
+            stale_age: StaleAge::default(),
+            stale_files: Vec::new(),
+            tail: None,
+            tail_dir: None,
+            back: Vec::new(),
+            forward: Vec::new(),
+            roots: Vec::new(),
+            scan_started: None,
+            scan_duration: None,
+            scan_context: None,
+            paused: false,
+            last_interaction: Instant::now(),
+            expired: None,
+            progress: std::collections::VecDeque::new(),
+            scan_errors: Vec::new(),
+            show_scan_errors: false,
+            show_settings: false,
+            hardlinks: crate::analyze::HardLinks::default(),
+            show_hardlinks: false,
+            extension_filter: None,
+            hidden_extensions: HashSet::new(),
+            band_counts: Vec::new(),
+            size_bands_input: String::new(),
+            filtered: None,
+            extension_stats: HashMap::new(),
+            inspected: None,
+        };
+        app.profile_names = app.profiles().into_iter().map(|f| f.name).collect();
+        app.layout_panes();
+
+        let last_path = app.config.last_path.clone().filter(|f| f.is_dir());
+        let task = match flags.filter(|f| f.exists()) {
+            Some(path) => app.update(Msg::Crawl(path)),
+            None => match last_path {
+                Some(path) if app.config.scan_last_on_startup => app.update(Msg::Crawl(path)),
+                Some(path) => app.update(Msg::CrawlPathChanged(path)),
+                None => cosmic::Task::none(),
+            },
         };
 
-        (app, cosmic::Task::none())
+        (app, task)
     }
 
     fn update(&mut self, message: Self::Message) -> cosmic::app::Task<Self::Message> {
+        if message.is_interaction() {
+            self.last_interaction = Instant::now();
+        }
         match message {
             Msg::CrawlPathChanged(s) => {
                 self.crawl_path = s;
-                self.core.set_header_title(format!(
-                    "COSMIC DirStat - {}",
-                    self.crawl_path.to_string_lossy().into_owned()
-                ));
+                self.update_title();
             }
             Msg::Crawl(s) => {
-                self.crawl_path = s.clone();
-                self.core.set_header_title(format!(
-                    "COSMIC DirStat - {}",
-                    self.crawl_path.to_string_lossy().into_owned()
-                ));
-                return cosmic::Task::perform(
-                    async move { crate::analyze::analyze_dir(&s, &crate::analyze::Context {}) },
-                    |a| {
-                        match a {
-                            Ok(a) => Msg::Analyzed(Arc::new(a)),
-                            Err(e) => Msg::AnalyzedError(e.to_string()),
-                        }
-                        .into()
-                    },
-                );
+                let s = match self.canonical_path(s) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return cosmic::Task::none();
+                    }
+                };
+                // A file is shown selected in its directory, unless it's an archive to look inside.
+                #[cfg(feature = "archives")]
+                let is_archive = self.config.scan_archives && crate::archive::is_archive(&s);
+                #[cfg(not(feature = "archives"))]
+                let is_archive = false;
+                let s = match s.parent() {
+                    Some(parent) if s.exists() && !s.is_dir() && !is_archive => {
+                        let parent = parent.to_path_buf();
+                        self.selected = Some(s);
+                        parent
+                    }
+                    _ => s,
+                };
+                self.push_history(&s);
+                return self.scan(s);
+            }
+            Msg::ShowDir(s) => {
+                self.push_history(&s);
+                return self.show_dir(s);
+            }
+            Msg::NavBack => {
+                if let Some(path) = self.back.pop() {
+                    if let Some(current) = self.current().map(|f| f.path.to_path_buf()) {
+                        self.forward.push(current);
+                    }
+                    return self.show_dir(path);
+                }
+            }
+            Msg::NavForward => {
+                if let Some(path) = self.forward.pop() {
+                    if let Some(current) = self.current().map(|f| f.path.to_path_buf()) {
+                        self.back.push(current);
+                    }
+                    return self.show_dir(path);
+                }
             }
             Msg::CrawlPath { cancel } => {
                 if !cancel {
@@ -258,7 +2783,10 @@ impl cosmic::Application for App {
 
                     return self.update(Msg::Crawl(crawl_path));
                 }
-                self.crawling_path = false;
+                if let Some(ctx) = &self.scan_context {
+                    ctx.cancel();
+                }
+                self.finish_scan();
             }
             Msg::CrawlPathDialogue => {
                 return cosmic::Task::perform(
@@ -270,37 +2798,750 @@ impl cosmic::Application for App {
                 );
             }
             Msg::PaneResize(f) => self.state.resize(f.split, f.ratio),
-            Msg::Analyzed(a) => {
-                self.crawling_path = false;
-                self.analyzed = Some(a);
+            Msg::MaximizeToggled => {
+                if self.state.maximized().is_some() {
+                    self.state.restore();
+                } else {
+                    self.state.maximize(self.partitioned_pane);
+                }
+            }
+            // A scan replaced by a newer one may still have finished before it was cancelled.
+            Msg::Analyzed(ctx, _)
+            | Msg::AnalyzedError(ctx, _)
+            | Msg::SubtreeRescanned(ctx, _)
+            | Msg::RootAdded(ctx, _)
+                if !self.is_current_scan(&ctx) => {}
+            Msg::Analyzed(_, a) => {
+                self.expired = None;
+                self.scan_errors = self.take_scan_errors();
+                let hardlinks = self.take_hardlinks();
+                self.finish_scan();
+                self.scan_duration = self.scan_started.take().map(|f| f.elapsed());
+                self.tail = None;
+                let path = a.path.to_path_buf();
+                self.update_config(|f| f.last_path = Some(path.clone()));
+                // A directory inside the scanned tree is put in its place, so the rest stays
+                // around for going back up.
+                let root = match self.roots.as_mut_slice() {
+                    [root] if root.find_dir(&a.path).is_some() => Some(root),
+                    _ => None,
+                };
+                if let Some(root) = root {
+                    self.hardlinks.splice(&a.path, hardlinks);
+                    self.view = (*root.path != *a.path).then_some(path);
+                    // As with a rescan, nothing else may hold the root while it's spliced.
+                    self.analyzed = None;
+                    Arc::make_mut(root).splice(Arc::unwrap_or_clone(a));
+                } else {
+                    self.hardlinks = hardlinks;
+                    self.view = None;
+                    self.roots = vec![a];
+                }
+                self.roots_changed();
+                self.reveal_selected();
             }
-            Msg::AnalyzedError(e) => {
-                self.crawling_path = false;
+            Msg::AnalyzedError(_, e) => {
+                self.finish_scan();
                 self.error = Some(e);
             }
             Msg::ClearError => self.error = None,
+            Msg::SettingsToggled(s) => self.show_settings = s,
+            Msg::ShowScanErrors(s) => self.show_scan_errors = s,
+            Msg::CopyScanErrors => {
+                let text = group_scan_errors(&self.scan_errors)
+                    .into_iter()
+                    .map(|(label, paths)| {
+                        let list = paths
+                            .iter()
+                            .map(|f| format!("  {}\n", f.display()))
+                            .collect::<String>();
+                        format!("{label} ({}):\n{list}", paths.len())
+                    })
+                    .collect::<String>();
+                return cosmic::iced::clipboard::write(text);
+            }
+            Msg::DismissScanErrors => {
+                self.scan_errors.clear();
+                self.show_scan_errors = false;
+            }
             Msg::ExtensionLegendChanged(l) => self.extensions_ordered = l,
-            Msg::NewItemHighlighted(h) => match h {
-                Some(s) => self.highlighted = Some(s),
-                None => self.highlighted = None,
+            // The colors and hovered tile of the overview are its own business.
+            Msg::OverviewUpdated => {}
+            Msg::OverviewToggled(o) => self.update_config(|f| f.hide_overview = !o),
+            Msg::NewItemHighlighted(h) => {
+                self.highlighted = h;
+                self.update_inspected();
+            }
+            Msg::WatchToggled(w) => self.watching = w,
+            Msg::PathChanged(p) => {
+                let ctx = self.new_context();
+                return cosmic::Task::perform(
+                    async move {
+                        crate::analyze::analyze_dir(&p, &ctx)
+                            .map(|a| (a, ctx.take_hardlinks(), ctx.take_errors()))
+                            .map_err(|e| scan_error_message(&p, &e))
+                    },
+                    |a| match a {
+                        Ok((a, hardlinks, errors)) => {
+                            Msg::PathRescanned(Arc::new(a), hardlinks, errors).into()
+                        }
+                        Err(Some(e)) => Msg::WatchFailed(e).into(),
+                        Err(None) => cosmic::app::Message::None,
+                    },
+                );
+            }
+            Msg::PathRescanned(a, hardlinks, errors) => {
+                self.hardlinks.splice(&a.path, hardlinks);
+                self.scan_errors.retain(|f| !f.path.starts_with(&a.path));
+                self.scan_errors.extend(errors);
+                return self.update(Msg::Rescanned(a));
+            }
+            Msg::WatchFailed(e) => self.error = Some(e),
+            Msg::RescanSubtree(path) => {
+                let ctx = self.start_scan();
+                return cosmic::Task::perform(
+                    async move {
+                        let result = crate::analyze::analyze_dir(&path, &ctx);
+                        (ctx, result.map_err(|e| scan_error_message(&path, &e)))
+                    },
+                    |(ctx, a)| match a {
+                        Ok(a) => Msg::SubtreeRescanned(ctx, Arc::new(a)).into(),
+                        Err(Some(e)) => Msg::AnalyzedError(ctx, e).into(),
+                        Err(None) => cosmic::app::Message::None,
+                    },
+                );
+            }
+            Msg::SubtreeRescanned(_, a) => {
+                self.scan_errors = self.take_scan_errors();
+                let hardlinks = self.take_hardlinks();
+                self.hardlinks.splice(&a.path, hardlinks);
+                self.finish_scan();
+                self.scan_duration = self.scan_started.take().map(|f| f.elapsed());
+                return self.update(Msg::Rescanned(a));
+            }
+            Msg::Rescanned(a) => {
+                // Let go of the view first so the root isn't needlessly copied.
+                self.analyzed = None;
+                if let Some(root) = self.roots.iter_mut().find(|f| a.path.starts_with(&f.path)) {
+                    Arc::make_mut(root).splice(Arc::unwrap_or_clone(a));
+                }
+                self.roots_changed();
+            }
+            Msg::AddRoot => {
+                let path = match self.canonical_path(self.crawl_path.clone()) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        self.error = Some(e);
+                        return cosmic::Task::none();
+                    }
+                };
+                if self
+                    .roots
+                    .iter()
+                    .any(|f| path.starts_with(&f.path) || f.path.starts_with(&path))
+                {
+                    self.error = Some(format!("{} is already scanned", path.display()));
+                    return cosmic::Task::none();
+                }
+                let ctx = self.start_scan();
+                return cosmic::Task::perform(
+                    async move {
+                        let result = crate::analyze::analyze_dir(&path, &ctx);
+                        (ctx, result.map_err(|e| scan_error_message(&path, &e)))
+                    },
+                    |(ctx, a)| match a {
+                        Ok(a) => Msg::RootAdded(ctx, Arc::new(a)).into(),
+                        Err(Some(e)) => Msg::AnalyzedError(ctx, e).into(),
+                        Err(None) => cosmic::app::Message::None,
+                    },
+                );
+            }
+            Msg::RootAdded(_, a) => {
+                let errors = self.take_scan_errors();
+                self.scan_errors.extend(errors);
+                let hardlinks = self.take_hardlinks();
+                self.hardlinks.splice(&a.path, hardlinks);
+                self.finish_scan();
+                self.scan_duration = self.scan_started.take().map(|f| f.elapsed());
+                self.tail = None;
+                self.view = None;
+                self.roots.push(a);
+                self.roots_changed();
+            }
+            Msg::RemoveRoot(i) => {
+                if i < self.roots.len() && self.roots.len() > 1 {
+                    self.tail = None;
+                    self.roots.remove(i);
+                    self.roots_changed();
+                }
+            }
+            Msg::ExportResolutionChanged(i) => self.export_resolution = ExportResolution::ALL[i],
+            Msg::ExportLegendToggled(l) => self.export_legend = l,
+            Msg::CopyReport => {
+                if let Some(a) = self.current() {
+                    let report = crate::format::format_report(
+                        a,
+                        &a.extension_stats(),
+                        self.config.size_units,
+                        self.config.digit_grouping,
+                    );
+                    return cosmic::iced::clipboard::write(report);
+                }
+            }
+            Msg::ExportImageDialogue => {
+                return cosmic::Task::perform(
+                    rfd::AsyncFileDialog::new()
+                        .add_filter("PNG", &["png"])
+                        .set_file_name("dirstat.png")
+                        .save_file(),
+                    |f| match f {
+                        Some(f) => Msg::ExportImage(f.path().to_path_buf()).into(),
+                        None => cosmic::app::Message::None,
+                    },
+                );
+            }
+            Msg::ExportImage(path) => {
+                if let Some(dir) = self.current_arc() {
+                    let resolution = self.export_resolution.size();
+                    let legend = self.export_legend;
+                    let palette = self.config.palette;
+                    let header_height = self.config.header_height.height(TEXT_SIZE);
+                    let metric = self.config.metric;
+                    return cosmic::Task::perform(
+                        async move {
+                            export_image::export_png(
+                                &dir,
+                                &path,
+                                resolution,
+                                partition_view::LayoutParams {
+                                    minimum_area: f64::from(MINIMUM_AREA),
+                                    minimum_aspect: f64::from(MINIMUM_ASPECT),
+                                    header_height: f64::from(header_height),
+                                    metric,
+                                },
+                                palette,
+                                legend,
+                            )
+                        },
+                        |r| Msg::ExportFinished(r).into(),
+                    );
+                }
+            }
+            Msg::ExportTreeDialogue(format) => {
+                let (name, extension, file_name) = format.file_filter();
+                return cosmic::Task::perform(
+                    rfd::AsyncFileDialog::new()
+                        .add_filter(name, &[extension])
+                        .set_file_name(file_name)
+                        .save_file(),
+                    move |f| match f {
+                        Some(f) => Msg::ExportTree(format, f.path().to_path_buf()).into(),
+                        None => cosmic::app::Message::None,
+                    },
+                );
+            }
+            Msg::ExportTree(format, path) => {
+                if let Some(dir) = self.current_arc() {
+                    return cosmic::Task::perform(
+                        async move {
+                            std::fs::write(&path, format.write(&dir)).map_err(|e| e.to_string())
+                        },
+                        |r| Msg::ExportFinished(r).into(),
+                    );
+                }
+            }
+            Msg::ExportFinished(r) => {
+                if let Err(e) = r {
+                    self.error = Some(e);
+                }
+            }
+            Msg::OutlineToggled(path) => {
+                if !self.expanded.remove(&path) {
+                    self.expanded.insert(path);
+                }
+            }
+            Msg::OutlineSelected(path) => {
+                self.selected = Some(path);
+                self.update_inspected();
+            }
+            Msg::ShowHardLinks(s) => self.show_hardlinks = s,
+            Msg::ClearSelection => {
+                self.selected = None;
+                self.marked.clear();
+                self.update_inspected();
+            }
+            Msg::JumpToLargest => {
+                self.largest_files = self
+                    .treemap_dir()
+                    .map(|f| largest_files(f, LARGEST_FILES))
+                    .unwrap_or_default();
+                return self.show_largest(0);
+            }
+            Msg::JumpToNextLargest => {
+                if self.largest_files.is_empty() {
+                    return self.update(Msg::JumpToLargest);
+                }
+                let next = (self.largest_rank + 1) % self.largest_files.len();
+                return self.show_largest(next);
+            }
+            // Marking is for trashing, which only works on this machine's files.
+            Msg::MarkToggled(path) if !is_local(&path) => {}
+            Msg::MarkToggled(path) => {
+                if !self.marked.remove(&path) {
+                    self.marked.insert(path);
+                }
+            }
+            Msg::ConfirmTrashMarked(c) => self.confirm_trash_marked = c,
+            Msg::TrashMarked => {
+                self.confirm_trash_marked = false;
+                let paths = self.outermost_marked();
+                return cosmic::Task::perform(
+                    async move {
+                        trash::delete_all(&paths)
+                            .map(|()| paths)
+                            .map_err(|e| e.to_string())
+                    },
+                    |r| Msg::Trashed(r).into(),
+                );
+            }
+            Msg::SortKeyChanged(i) => self.update_config(|f| f.sort_key = config::SortKey::ALL[i]),
+            Msg::SizeUnitsChanged(i) => {
+                self.update_config(|f| f.size_units = crate::format::SizeUnits::ALL[i]);
+            }
+            Msg::DigitGroupingChanged(i) => {
+                self.update_config(|f| f.digit_grouping = crate::format::DigitGrouping::ALL[i]);
+            }
+            Msg::ConfirmTrashEmptyDirs(c) => self.confirm_trash_empty_dirs = c,
+            Msg::TrashEmptyDirs => {
+                self.confirm_trash_empty_dirs = false;
+                // Trashing a directory takes its empty children with it.
+                let mut outermost: Vec<PathBuf> = Vec::new();
+                for dir in &self.empty_dirs {
+                    if !outermost.last().is_some_and(|f| dir.starts_with(f)) {
+                        outermost.push(dir.clone());
+                    }
+                }
+                return cosmic::Task::perform(
+                    async move {
+                        trash::delete_all(&outermost)
+                            .map(|()| outermost)
+                            .map_err(|e| e.to_string())
+                    },
+                    |r| Msg::Trashed(r).into(),
+                );
+            }
+            Msg::ScanLastOnStartupToggled(s) => self.update_config(|f| f.scan_last_on_startup = s),
+            Msg::HighContrastToggled(h) => self.update_config(|f| f.high_contrast = h),
+            Msg::DistinctDirsToggled(d) => self.update_config(|f| f.distinct_dirs = d),
+            Msg::TabbedPanelsToggled(t) => {
+                self.update_config(|f| f.tabbed_panels = t);
+                self.layout_panes();
+            }
+            Msg::PanelTabActivated(id) => {
+                self.panel_tabs.activate(id);
+                if let Some(tab) = self.panel_tabs.data::<config::PanelTab>(id).copied() {
+                    self.update_config(|f| f.panel_tab = tab);
+                }
+            }
+            Msg::TileDetailChanged(i) => {
+                self.update_config(|f| f.tile_detail = config::TileDetail::ALL[i]);
+            }
+            Msg::LabelLengthChanged(i) => {
+                self.update_config(|f| f.label_length = config::LabelLength::ALL[i]);
+            }
+            Msg::TileBordersChanged(i) => {
+                self.update_config(|f| f.tile_borders = config::TileBorders::ALL[i]);
+            }
+            Msg::AggregateLabelChanged(l) => {
+                self.update_config(|f| f.aggregate_label = (!l.is_empty()).then_some(l));
+            }
+            Msg::QuotaInputChanged(q) => self.quota_input = q,
+            Msg::SetQuota => {
+                let quota = crate::format::parse_size(&self.quota_input);
+                let path = self.current().map(|f| f.path.to_path_buf());
+                if let Some((quota, path)) = quota.zip(path) {
+                    self.update_config(|f| {
+                        f.quotas.insert(path, quota);
+                    });
+                    self.quota_input.clear();
+                }
+            }
+            Msg::ClearQuota => {
+                if let Some(path) = self.current().map(|f| f.path.to_path_buf()) {
+                    self.update_config(|f| {
+                        f.quotas.remove(&path);
+                    });
+                }
+            }
+            Msg::QuotaWarningChanged(i) => {
+                self.update_config(|f| f.quota_warning = config::QuotaWarning::ALL[i]);
+            }
+            Msg::HeaderHeightChanged(i) => {
+                self.update_config(|f| f.header_height = config::HeaderHeight::ALL[i]);
+            }
+            Msg::MetricChanged(i) => {
+                self.update_config(|f| f.metric = crate::analyze::Metric::ALL[i]);
+                // The aggregate being looked at holds other items by another metric.
+                self.tail = None;
+                self.update_tail();
+                self.update_band_counts();
+            }
+            Msg::PaletteChanged(i) => self.update_config(|f| f.palette = config::Palette::ALL[i]),
+            Msg::RootLabelChanged(i) => {
+                self.update_config(|f| f.root_label = config::RootLabel::ALL[i]);
+                self.update_title();
+            }
+            Msg::CustomRootLabelChanged(l) => {
+                self.update_config(|f| f.custom_root_label = l);
+                self.update_title();
+            }
+            Msg::ColorModeChanged(i) => {
+                self.update_config(|f| f.color_mode = config::ColorMode::ALL[i]);
+                self.update_band_counts();
+            }
+            Msg::SizeBandsChanged(s) => {
+                let bands = s
+                    .split(',')
+                    .map(crate::format::parse_size)
+                    .collect::<Option<Vec<_>>>();
+                if s.trim().is_empty() {
+                    self.update_config(|f| f.size_bands = None);
+                } else if let Some(mut bands) = bands {
+                    bands.sort_unstable();
+                    bands.dedup();
+                    self.update_config(|f| f.size_bands = Some(bands));
+                }
+                self.size_bands_input = s;
+                self.update_band_counts();
+            }
+            Msg::RoundedTilesToggled(r) => self.update_config(|f| f.rounded_tiles = r),
+            Msg::AnimateNavigationToggled(a) => self.update_config(|f| f.animate_navigation = a),
+            Msg::CompactHoverToggled(c) => self.update_config(|f| f.compact_hover = c),
+            Msg::HoverDelayChanged(i) => {
+                self.update_config(|f| f.hover_delay = config::HoverDelay::ALL[i]);
+            }
+            Msg::HoverTemplateChanged(t) => {
+                self.update_config(|f| f.hover_template = (!t.is_empty()).then_some(t));
+            }
+            Msg::MinFileSizeChanged(i) => {
+                self.update_config(|f| f.min_file_size = MinFileSize::ALL[i].bytes());
+            }
+            Msg::MaxEntriesChanged(i) => {
+                self.update_config(|f| f.max_entries_per_dir = MaxEntries::ALL[i].count());
+            }
+            Msg::DetailThresholdChanged(i) => {
+                self.update_config(|f| f.detail_threshold = DetailThreshold::ALL[i].bytes());
+            }
+            Msg::IncludeEmptyFilesToggled(i) => self.update_config(|f| f.skip_empty_files = !i),
+            #[cfg(feature = "archives")]
+            Msg::ScanArchivesToggled(s) => self.update_config(|f| f.scan_archives = s),
+            Msg::DedupDirsToggled(d) => self.update_config(|f| f.dedup_dirs = d),
+            Msg::HiddenModeChanged(i) => {
+                self.update_config(|f| f.hidden = crate::analyze::HiddenMode::ALL[i]);
+            }
+            Msg::ProfileSelected(i) => {
+                if let Some(profile) = self.profiles().get(i) {
+                    self.update_config(|f| profile.apply(f));
+                }
+            }
+            Msg::ProfileNameChanged(name) => self.profile_name = name,
+            Msg::SaveProfile => {
+                let name = self.profile_name.trim().to_string();
+                let builtin = config::ScanProfile::builtin();
+                if name.is_empty() || builtin.iter().any(|f| f.name == name) {
+                    return cosmic::Task::none();
+                }
+                // Saving under an existing name replaces that profile.
+                let profile = config::ScanProfile::from_config(name, &self.config);
+                self.update_config(|f| {
+                    match f.profiles.iter_mut().find(|f| f.name == profile.name) {
+                        Some(existing) => *existing = profile,
+                        None => f.profiles.push(profile),
+                    }
+                });
+                self.profile_name.clear();
+            }
+            Msg::DeleteProfile => {
+                let builtin = config::ScanProfile::builtin().len();
+                if let Some(i) = self.current_profile().filter(|f| *f >= builtin) {
+                    self.update_config(|f| {
+                        f.profiles.remove(i - builtin);
+                    });
+                }
+            }
+            Msg::StaleAgeChanged(i) => {
+                self.stale_age = StaleAge::ALL[i];
+                self.update_stale_files();
+            }
+            Msg::ConfirmTrashStaleFiles(c) => self.confirm_trash_stale_files = c,
+            Msg::TrashStaleFiles => {
+                self.confirm_trash_stale_files = false;
+                let files = self
+                    .stale_files
+                    .iter()
+                    .map(|f| f.0.clone())
+                    .collect::<Vec<_>>();
+                return cosmic::Task::perform(
+                    async move {
+                        trash::delete_all(&files)
+                            .map(|()| files)
+                            .map_err(|e| e.to_string())
+                    },
+                    |r| Msg::Trashed(r).into(),
+                );
+            }
+            Msg::Trashed(r) => match r {
+                Ok(paths) => {
+                    // Let go of the view first so the roots aren't needlessly copied.
+                    self.analyzed = None;
+                    for path in &paths {
+                        if let Some(root) =
+                            self.roots.iter_mut().find(|f| path.starts_with(&f.path))
+                        {
+                            Arc::make_mut(root).remove_path(path);
+                        }
+                        self.marked.retain(|f| !f.starts_with(path));
+                        self.hardlinks
+                            .splice(path, crate::analyze::HardLinks::default());
+                    }
+                    self.roots_changed();
+                }
+                Err(e) => self.error = Some(e),
             },
+            Msg::SortOrderToggled => self.update_config(|f| f.sort_ascending = !f.sort_ascending),
+            Msg::SetBaseline(set) => {
+                self.baseline = if set { self.current_arc() } else { None };
+                self.tree_changed();
+            }
+            Msg::RefreshIntervalChanged(i) => {
+                self.update_config(|f| f.refresh_interval = config::RefreshInterval::ALL[i]);
+            }
+            Msg::IdleTimeoutChanged(i) => {
+                self.update_config(|f| f.idle_timeout = config::IdleTimeout::ALL[i]);
+            }
+            Msg::IdleTick => {
+                let idle = self
+                    .config
+                    .idle_timeout
+                    .duration()
+                    .is_some_and(|f| self.last_interaction.elapsed() >= f);
+                if idle && !self.crawling_path {
+                    self.expire_scan();
+                }
+            }
+            Msg::FsStats(f) => self.fs_stats = f,
+            Msg::AggregateClicked(path, count) => {
+                self.tail = Some((path, count));
+                self.update_tail();
+            }
+            Msg::GoUp => {
+                if let Some(msg) = self.go_up() {
+                    return self.update(msg);
+                }
+            }
+            Msg::CloseTail => {
+                self.tail = None;
+                self.update_tail();
+            }
+            Msg::PauseToggled => {
+                if let Some(ctx) = &self.scan_context {
+                    self.paused = !self.paused;
+                    if self.paused {
+                        ctx.pause();
+                    } else {
+                        ctx.resume();
+                    }
+                }
+            }
+            Msg::ProgressTick => {
+                if let Some(ctx) = &self.scan_context {
+                    let now = Instant::now();
+                    self.progress.push_back((now, ctx.progress()));
+                    while self
+                        .progress
+                        .front()
+                        .is_some_and(|(at, _)| now.duration_since(*at) > RATE_WINDOW)
+                    {
+                        self.progress.pop_front();
+                    }
+                }
+                let partial = self.scan_context.as_ref().and_then(|f| f.take_partial());
+                if let Some(partial) = partial.filter(|_| self.roots.is_empty()) {
+                    self.analyzed = Some(Arc::new(partial));
+                    self.tree_changed();
+                }
+            }
+            Msg::ExtensionFilter(ext) => {
+                self.extension_filter = ext;
+                self.tail = None;
+                self.update_filter();
+            }
+            Msg::ExtensionHidden(ext, hide) => {
+                if hide {
+                    if self.extension_filter.as_ref() == Some(&ext) {
+                        self.extension_filter = None;
+                    }
+                    self.hidden_extensions.insert(ext);
+                } else {
+                    self.hidden_extensions.remove(&ext);
+                }
+                self.tail = None;
+                self.update_filter();
+            }
+            Msg::ExtensionFilterToggled(i) => {
+                if let Some((ext, _)) = self.extensions_ordered.get(i) {
+                    let ext = (self.extension_filter.as_ref() != Some(ext)).then(|| ext.clone());
+                    return self.update(Msg::ExtensionFilter(ext));
+                }
+            }
+            Msg::RefreshTick => {
+                if !self.crawling_path {
+                    match self.roots.as_slice() {
+                        [] => {}
+                        // Only what's on display is scanned again, and put back in the tree.
+                        [_] => {
+                            if let Some(path) = self.current().map(|f| f.path.to_path_buf()) {
+                                return self.scan(path);
+                            }
+                        }
+                        roots => {
+                            let paths: Vec<_> =
+                                roots.iter().map(|f| f.path.to_path_buf()).collect();
+                            return cosmic::Task::batch(
+                                paths.into_iter().map(|p| self.update(Msg::PathChanged(p))),
+                            );
+                        }
+                    }
+                }
+            }
         }
 
         cosmic::Task::none()
     }
 
+    fn subscription(&self) -> cosmic::iced::Subscription<Self::Message> {
+        let watch = if self.watching {
+            cosmic::iced::Subscription::batch(
+                self.roots.iter().filter(|f| is_local(&f.path)).map(|f| {
+                    watch::watch(f.path.to_path_buf(), Msg::PathChanged, Msg::WatchFailed)
+                }),
+            )
+        } else {
+            cosmic::iced::Subscription::none()
+        };
+        let refresh = match self.config.refresh_interval.duration() {
+            Some(d) => cosmic::iced::time::every(d).map(|_| Msg::RefreshTick),
+            None => cosmic::iced::Subscription::none(),
+        };
+
+        let idle = match self.config.idle_timeout.duration() {
+            Some(_) if !self.roots.is_empty() => {
+                cosmic::iced::time::every(IDLE_CHECK_INTERVAL).map(|_| Msg::IdleTick)
+            }
+            _ => cosmic::iced::Subscription::none(),
+        };
+
+        let progress = if self.scan_context.is_some() {
+            cosmic::iced::time::every(Duration::from_millis(250)).map(|_| Msg::ProgressTick)
+        } else {
+            cosmic::iced::Subscription::none()
+        };
+
+        cosmic::iced::Subscription::batch([
+            watch,
+            refresh,
+            idle,
+            progress,
+            cosmic::iced::event::listen_with(key_pressed),
+        ])
+    }
+
     fn dialog(&self) -> Option<cosmic::Element<Self::Message>> {
-        self.error.as_ref().map(|e| {
-            cosmic::widget::dialog()
-                .title(format!("Error: {e}"))
-                .primary_action(cosmic::widget::button::standard("OK").on_press(Msg::ClearError))
-                .into()
-        })
+        if let Some(e) = &self.error {
+            return Some(
+                cosmic::widget::dialog()
+                    .title(format!("Error: {e}"))
+                    .primary_action(
+                        cosmic::widget::button::standard("OK").on_press(Msg::ClearError),
+                    )
+                    .into(),
+            );
+        }
+        if self.confirm_trash_marked {
+            return Some(
+                cosmic::widget::dialog()
+                    .title("Move marked items to the trash?")
+                    .body(format!(
+                        "{} items will be moved to the trash, freeing {}.",
+                        self.format_count(self.outermost_marked().len() as u64),
+                        self.format_size(self.marked_size())
+                    ))
+                    .primary_action(
+                        cosmic::widget::button::destructive("Move to Trash")
+                            .on_press(Msg::TrashMarked),
+                    )
+                    .secondary_action(
+                        cosmic::widget::button::standard("Cancel")
+                            .on_press(Msg::ConfirmTrashMarked(false)),
+                    )
+                    .into(),
+            );
+        }
+        if self.confirm_trash_empty_dirs {
+            return Some(
+                cosmic::widget::dialog()
+                    .title("Move empty directories to the trash?")
+                    .body(format!(
+                        "{} empty directories will be moved to the trash.",
+                        self.format_count(self.empty_dirs.len() as u64)
+                    ))
+                    .primary_action(
+                        cosmic::widget::button::destructive("Move to Trash")
+                            .on_press(Msg::TrashEmptyDirs),
+                    )
+                    .secondary_action(
+                        cosmic::widget::button::standard("Cancel")
+                            .on_press(Msg::ConfirmTrashEmptyDirs(false)),
+                    )
+                    .into(),
+            );
+        }
+        if self.confirm_trash_stale_files {
+            return Some(
+                cosmic::widget::dialog()
+                    .title("Move untouched files to the trash?")
+                    .body(format!(
+                        "{} files will be moved to the trash, freeing {}.",
+                        self.format_count(self.stale_files.len() as u64),
+                        self.format_size(self.stale_size())
+                    ))
+                    .primary_action(
+                        cosmic::widget::button::destructive("Move to Trash")
+                            .on_press(Msg::TrashStaleFiles),
+                    )
+                    .secondary_action(
+                        cosmic::widget::button::standard("Cancel")
+                            .on_press(Msg::ConfirmTrashStaleFiles(false)),
+                    )
+                    .into(),
+            );
+        }
+        self.show_scan_errors.then(|| self.scan_errors_dialog())
     }
 
     fn view(&self) -> cosmic::Element<Self::Message> {
         use cosmic::widget::container;
 
+        if self.show_settings {
+            let settings = container(self.settings_view())
+                .class(cosmic::theme::Container::Card)
+                .height(Length::Fill)
+                .width(Length::Fill);
+            return cosmic::widget::column::with_children(vec![settings.into(), self.status_bar()])
+                .spacing(5.0)
+                .into();
+        }
+
         let grid =
             cosmic::widget::pane_grid::PaneGrid::new(&self.state, move |_pane, t, _maximized| {
                 match t {
@@ -314,16 +3555,33 @@ impl cosmic::Application for App {
                         .height(Length::FillPortion(2))
                         .width(Length::FillPortion(2))
                         .into(),
+                    Panels::Cleanup => container(self.cleanup_view())
+                        .class(cosmic::theme::Container::Card)
+                        .height(Length::FillPortion(2))
+                        .width(Length::FillPortion(2))
+                        .into(),
                     Panels::Partioned => container(self.partition_view())
                         .class(cosmic::theme::Container::Card)
                         .height(Length::FillPortion(3))
                         .width(Length::Fill)
                         .into(),
+                    Panels::Inspector => container(self.inspector_view())
+                        .class(cosmic::theme::Container::Card)
+                        .height(Length::FillPortion(3))
+                        .width(Length::Fill)
+                        .into(),
+                    Panels::Summary => container(self.summary_view())
+                        .class(cosmic::theme::Container::Card)
+                        .height(Length::FillPortion(3))
+                        .width(Length::Fill)
+                        .into(),
                 }
             })
             .on_resize(10.0, Msg::PaneResize)
             .spacing(10.0);
 
-        grid.into()
+        cosmic::widget::column::with_children(vec![grid.into(), self.status_bar()])
+            .spacing(5.0)
+            .into()
     }
 }
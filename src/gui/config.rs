@@ -0,0 +1,469 @@
+use std::{cmp::Ordering, collections::BTreeMap, path::PathBuf, time::Duration};
+
+use cosmic::cosmic_config::{self, cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analyze::{AnalyzedItem, HiddenMode, Metric},
+    choice::Choice,
+    format::{DigitGrouping, SizeUnits},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, CosmicConfigEntry, Serialize, Deserialize)]
+#[version = 1]
+pub struct Config {
+    pub refresh_interval: RefreshInterval,
+    /// Let go of the scanned tree after this long without any interaction.
+    pub idle_timeout: IdleTimeout,
+    pub sort_key: SortKey,
+    pub sort_ascending: bool,
+    pub size_units: SizeUnits,
+    pub digit_grouping: DigitGrouping,
+    /// Root of the last completed scan, pre-filled on startup.
+    pub last_path: Option<PathBuf>,
+    /// Scan `last_path` straight away on startup instead of only pre-filling it.
+    pub scan_last_on_startup: bool,
+    /// Draw the treemap with solid fills and borders between tiles.
+    pub high_contrast: bool,
+    /// Tint folder tiles so they stand apart from the files in them.
+    pub distinct_dirs: bool,
+    /// Show the tree, legend and inspector as tabs of one panel, leaving the treemap more room.
+    pub tabbed_panels: bool,
+    /// Tab of the tabbed panel last shown.
+    pub panel_tab: PanelTab,
+    /// What the treemap sizes tiles by.
+    pub metric: Metric,
+    pub tile_borders: TileBorders,
+    pub tile_detail: TileDetail,
+    pub label_length: LabelLength,
+    pub header_height: HeaderHeight,
+    /// Label of the tile standing for items too small to show, or `None` for
+    /// [`DEFAULT_AGGREGATE_LABEL`]. See [`crate::format::format_aggregate`].
+    pub aggregate_label: Option<String>,
+    /// Colors the treemap's extensions are drawn in.
+    pub palette: Palette,
+    pub color_mode: ColorMode,
+    /// Sizes at which each band of [`ColorMode::SizeBand`] starts, smallest first, or `None` for
+    /// [`DEFAULT_SIZE_BANDS`].
+    pub size_bands: Option<Vec<u64>>,
+    /// How the scanned folder is named in the heading and window title.
+    pub root_label: RootLabel,
+    /// What the scanned folder is called with [`RootLabel::Custom`].
+    pub custom_root_label: String,
+    pub rounded_tiles: bool,
+    /// Animate the treemap when navigating between directories.
+    pub animate_navigation: bool,
+    /// Leave out the overview of the scanned root drawn while inside a subdirectory.
+    pub hide_overview: bool,
+    /// Show what's hovered in the treemap in the status bar instead of a popup.
+    pub compact_hover: bool,
+    pub hover_delay: HoverDelay,
+    /// What the hover popup shows, or `None` for [`DEFAULT_HOVER_TEMPLATE`].
+    pub hover_template: Option<String>,
+    /// Files smaller than this many bytes are left out of scans, but still counted in sizes.
+    pub min_file_size: u64,
+    /// Leave files with no contents out of scans.
+    pub skip_empty_files: bool,
+    /// Directories with more entries than this keep only the largest ones, lumping the rest.
+    pub max_entries_per_dir: Option<usize>,
+    /// Folders smaller than this many bytes are only added up, without keeping what's in them.
+    pub detail_threshold: Option<u64>,
+    /// List the contents of zip and tar archives as if they were directories.
+    pub scan_archives: bool,
+    /// Count folders found at several paths, such as through bind mounts, only once.
+    pub dedup_dirs: bool,
+    pub hidden: HiddenMode,
+    /// Profiles saved by the user, listed after [`ScanProfile::builtin`].
+    pub profiles: Vec<ScanProfile>,
+    /// Bytes each directory is allowed to take up, shown as a gauge from inside it.
+    pub quotas: BTreeMap<PathBuf, u64>,
+    pub quota_warning: QuotaWarning,
+}
+impl Config {
+    /// Loads the persisted config, falling back to defaults for anything missing or invalid.
+    pub fn load(app_id: &str) -> (Option<cosmic_config::Config>, Self) {
+        match cosmic_config::Config::new(app_id, Self::VERSION) {
+            Ok(handler) => {
+                let config = Self::get_entry(&handler).unwrap_or_else(|(errs, config)| {
+                    for e in errs {
+                        eprintln!("Error: {e}");
+                    }
+                    config
+                });
+                (Some(handler), config)
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                (None, Self::default())
+            }
+        }
+    }
+}
+
+pub const DEFAULT_AGGREGATE_LABEL: &str = "{count} smaller items ({size})";
+
+/// Fills in `{name}`, `{size}`, `{percent}` (of the folder), `{total_percent}`, `{count}` (of
+/// files), `{modified}` and `{path}`, with `\n` starting a new line.
+pub const DEFAULT_HOVER_TEMPLATE: &str =
+    "{name}\\n{size}\\n{percent} of folder, {total_percent} of total\\n{path}";
+
+/// How long the cursor has to rest on a tile for the hover popup to show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HoverDelay {
+    None,
+    #[default]
+    Short,
+    Medium,
+    Long,
+}
+impl Choice for HoverDelay {
+    const ALL: &'static [Self] = &[Self::None, Self::Short, Self::Medium, Self::Long];
+    const LABELS: &'static [&'static str] = &["None", "Short", "Medium", "Long"];
+}
+impl HoverDelay {
+    pub const fn duration(self) -> Duration {
+        Duration::from_millis(match self {
+            Self::None => 0,
+            Self::Short => 250,
+            Self::Medium => 600,
+            Self::Long => 1200,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileBorders {
+    #[default]
+    None,
+    Thin,
+    Thick,
+}
+impl Choice for TileBorders {
+    const ALL: &'static [Self] = &[Self::None, Self::Thin, Self::Thick];
+    const LABELS: &'static [&'static str] = &["None", "Thin", "Thick"];
+}
+impl TileBorders {
+    pub const fn width(self) -> f32 {
+        match self {
+            Self::None => 0.0,
+            Self::Thin => 1.0,
+            Self::Thick => 2.0,
+        }
+    }
+}
+
+/// How much of its quota a directory has to take up for its tile to be tinted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaWarning {
+    Half,
+    ThreeQuarters,
+    #[default]
+    Most,
+    Full,
+}
+impl Choice for QuotaWarning {
+    const ALL: &'static [Self] = &[Self::Half, Self::ThreeQuarters, Self::Most, Self::Full];
+    const LABELS: &'static [&'static str] = &["50%", "75%", "90%", "100%"];
+}
+impl QuotaWarning {
+    pub const fn fraction(self) -> f64 {
+        match self {
+            Self::Half => 0.5,
+            Self::ThreeQuarters => 0.75,
+            Self::Most => 0.9,
+            Self::Full => 1.0,
+        }
+    }
+}
+
+/// Most characters of a name shown in treemap labels and the hover popup, beyond what fits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LabelLength {
+    /// As much as fits in the tile, and all of it in the popup.
+    #[default]
+    Fit,
+    Short,
+    Medium,
+    Long,
+}
+impl Choice for LabelLength {
+    const ALL: &'static [Self] = &[Self::Fit, Self::Short, Self::Medium, Self::Long];
+    const LABELS: &'static [&'static str] = &[
+        "Fit the tile",
+        "12 characters",
+        "24 characters",
+        "48 characters",
+    ];
+}
+impl LabelLength {
+    pub const fn max_chars(self) -> Option<usize> {
+        match self {
+            Self::Fit => None,
+            Self::Short => Some(12),
+            Self::Medium => Some(24),
+            Self::Long => Some(48),
+        }
+    }
+}
+
+/// How small tiles get before they're rolled into the aggregate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileDetail {
+    /// The same smallest tile whatever the size of the window.
+    #[default]
+    Fixed,
+    /// About as many tiles whatever the size of the window, with fewer or more of them.
+    AutoCoarse,
+    AutoFine,
+}
+impl Choice for TileDetail {
+    const ALL: &'static [Self] = &[Self::Fixed, Self::AutoCoarse, Self::AutoFine];
+    const LABELS: &'static [&'static str] = &["Fixed", "Fit window, coarse", "Fit window, fine"];
+}
+impl TileDetail {
+    /// How many tiles the treemap aims for, or `None` for a fixed smallest tile.
+    pub const fn target_tiles(self) -> Option<u32> {
+        match self {
+            Self::Fixed => None,
+            Self::AutoCoarse => Some(1000),
+            Self::AutoFine => Some(4000),
+        }
+    }
+}
+
+/// How much room is left above each directory's children in the treemap, for its label.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HeaderHeight {
+    Thin,
+    #[default]
+    Normal,
+    Thick,
+}
+impl Choice for HeaderHeight {
+    const ALL: &'static [Self] = &[Self::Thin, Self::Normal, Self::Thick];
+    const LABELS: &'static [&'static str] = &["Thin", "Normal", "Thick"];
+}
+impl HeaderHeight {
+    /// The height for labels of `text_size`. Thin headers cut into the labels to leave more
+    /// room for the children, and thick ones are easier to hit.
+    pub fn height(self, text_size: f32) -> f32 {
+        text_size
+            * match self {
+                Self::Thin => 0.75,
+                Self::Normal => 1.4,
+                Self::Thick => 2.5,
+            }
+    }
+}
+
+pub const DEFAULT_SIZE_BANDS: [u64; 3] = [1_000_000, 100_000_000, 1_000_000_000];
+
+/// What the colors of files in the treemap stand for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    #[default]
+    Extension,
+    /// Bands of size, so the biggest files stand out whatever their type.
+    SizeBand,
+}
+impl Choice for ColorMode {
+    const ALL: &'static [Self] = &[Self::Extension, Self::SizeBand];
+    const LABELS: &'static [&'static str] = &["File type", "Size"];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PanelTab {
+    #[default]
+    Tree,
+    Legend,
+    Inspector,
+}
+impl Choice for PanelTab {
+    const ALL: &'static [Self] = &[Self::Tree, Self::Legend, Self::Inspector];
+    const LABELS: &'static [&'static str] = &["Tree", "Legend", "Inspector"];
+}
+
+/// How the scanned folder is shown at the start of paths, for keeping it out of screenshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RootLabel {
+    #[default]
+    FullPath,
+    /// Only the last component of its path.
+    Name,
+    /// The home directory is shortened to `~`, like in a shell.
+    Home,
+    /// [`Config::custom_root_label`], or its name while that's empty.
+    Custom,
+}
+impl Choice for RootLabel {
+    const ALL: &'static [Self] = &[Self::FullPath, Self::Name, Self::Home, Self::Custom];
+    const LABELS: &'static [&'static str] = &["Full path", "Folder name", "~ for home", "Custom"];
+}
+
+/// How extensions are assigned colors in the treemap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    /// Hues rotated away from the accent color by an amount picked from the extension, so each
+    /// extension always gets the same one.
+    #[default]
+    Accent,
+    /// A fixed set of hues that stay distinguishable with color vision deficiencies.
+    Colorblind,
+    /// Shades of the accent color, brighter for extensions taking up more space.
+    Monochrome,
+}
+impl Choice for Palette {
+    const ALL: &'static [Self] = &[Self::Accent, Self::Colorblind, Self::Monochrome];
+    const LABELS: &'static [&'static str] = &["Accent", "Colorblind-safe", "Monochrome"];
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RefreshInterval {
+    #[default]
+    Off,
+    Secs30,
+    Mins1,
+    Mins5,
+    Mins15,
+}
+impl Choice for RefreshInterval {
+    const ALL: &'static [Self] = &[
+        Self::Off,
+        Self::Secs30,
+        Self::Mins1,
+        Self::Mins5,
+        Self::Mins15,
+    ];
+    const LABELS: &'static [&'static str] =
+        &["Off", "30 seconds", "1 minute", "5 minutes", "15 minutes"];
+}
+impl RefreshInterval {
+    pub const fn duration(self) -> Option<Duration> {
+        match self {
+            Self::Off => None,
+            Self::Secs30 => Some(Duration::from_secs(30)),
+            Self::Mins1 => Some(Duration::from_secs(60)),
+            Self::Mins5 => Some(Duration::from_secs(5 * 60)),
+            Self::Mins15 => Some(Duration::from_secs(15 * 60)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdleTimeout {
+    #[default]
+    Off,
+    Mins15,
+    Hour1,
+    Hours4,
+}
+impl Choice for IdleTimeout {
+    const ALL: &'static [Self] = &[Self::Off, Self::Mins15, Self::Hour1, Self::Hours4];
+    const LABELS: &'static [&'static str] = &["Never", "15 minutes", "1 hour", "4 hours"];
+}
+impl IdleTimeout {
+    pub const fn duration(self) -> Option<Duration> {
+        match self {
+            Self::Off => None,
+            Self::Mins15 => Some(Duration::from_secs(15 * 60)),
+            Self::Hour1 => Some(Duration::from_secs(60 * 60)),
+            Self::Hours4 => Some(Duration::from_secs(4 * 60 * 60)),
+        }
+    }
+}
+
+/// How the outline orders the children of each directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortKey {
+    #[default]
+    Size,
+    Name,
+    Count,
+    Modified,
+}
+impl Choice for SortKey {
+    const ALL: &'static [Self] = &[Self::Size, Self::Name, Self::Count, Self::Modified];
+    const LABELS: &'static [&'static str] = &["Size", "Name", "Count", "Modified"];
+}
+impl SortKey {
+    pub fn cmp(self, a: &AnalyzedItem, b: &AnalyzedItem) -> Ordering {
+        match self {
+            Self::Size => a.size().cmp(&b.size()),
+            Self::Name => a.name().cmp(&b.name()),
+            Self::Count => a.num_files().cmp(&b.num_files()),
+            Self::Modified => a.modified().cmp(&b.modified()),
+        }
+    }
+}
+
+/// A named set of scan settings, applied to the config all at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanProfile {
+    pub name: String,
+    pub min_file_size: u64,
+    pub skip_empty_files: bool,
+    pub max_entries_per_dir: Option<usize>,
+    pub detail_threshold: Option<u64>,
+    pub scan_archives: bool,
+    pub dedup_dirs: bool,
+    pub hidden: HiddenMode,
+}
+impl ScanProfile {
+    /// Profiles that are always available, ahead of the user's.
+    pub fn builtin() -> [Self; 2] {
+        [
+            // Fast to scan and draw, for getting a feel of where space goes.
+            Self {
+                name: "Overview".into(),
+                min_file_size: 64 * 1024,
+                skip_empty_files: true,
+                max_entries_per_dir: Some(10_000),
+                detail_threshold: Some(16 * 1024 * 1024),
+                scan_archives: false,
+                dedup_dirs: true,
+                hidden: HiddenMode::Collapse,
+            },
+            // Everything, including what's tucked away, for finding what to delete.
+            Self {
+                name: "Cleanup".into(),
+                min_file_size: 0,
+                skip_empty_files: false,
+                max_entries_per_dir: None,
+                detail_threshold: None,
+                scan_archives: true,
+                dedup_dirs: true,
+                hidden: HiddenMode::Show,
+            },
+        ]
+    }
+
+    /// The scan settings currently in `config`, under `name`.
+    pub fn from_config(name: String, config: &Config) -> Self {
+        Self {
+            name,
+            min_file_size: config.min_file_size,
+            skip_empty_files: config.skip_empty_files,
+            max_entries_per_dir: config.max_entries_per_dir,
+            detail_threshold: config.detail_threshold,
+            scan_archives: config.scan_archives,
+            dedup_dirs: config.dedup_dirs,
+            hidden: config.hidden,
+        }
+    }
+
+    /// Whether `config` has exactly this profile's scan settings.
+    pub fn matches(&self, config: &Config) -> bool {
+        *self == Self::from_config(self.name.clone(), config)
+    }
+
+    /// Sets the scan settings in `config` to this profile's, so the next scan uses them.
+    pub fn apply(&self, config: &mut Config) {
+        config.min_file_size = self.min_file_size;
+        config.skip_empty_files = self.skip_empty_files;
+        config.max_entries_per_dir = self.max_entries_per_dir;
+        config.detail_threshold = self.detail_threshold;
+        config.scan_archives = self.scan_archives;
+        config.dedup_dirs = self.dedup_dirs;
+        config.hidden = self.hidden;
+    }
+}
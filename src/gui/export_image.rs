@@ -0,0 +1,90 @@
+use std::{collections::HashMap, path::Path};
+
+use cosmic::{iced::Color, prelude::ColorExt};
+use tiny_skia::{GradientStop, LinearGradient, Paint, Pixmap, Point, Rect, SpreadMode, Transform};
+
+use super::{config::Palette, partition_view};
+use crate::analyze::AnalyzedDir;
+
+/// Height of the optional legend strip, as a fraction of the image height.
+const LEGEND_FRACTION: f32 = 0.05;
+
+fn paint(col: Color, x: f32, y: f32, w: f32, h: f32) -> Paint<'static> {
+    let dark = col.blend_alpha(Color::BLACK, 0.5);
+    let mut paint = Paint::default();
+    match LinearGradient::new(
+        Point::from_xy(x, y),
+        Point::from_xy(x + w, y + h),
+        vec![
+            GradientStop::new(
+                0.0,
+                tiny_skia::Color::from_rgba(col.r, col.g, col.b, 1.0).unwrap(),
+            ),
+            GradientStop::new(
+                1.0,
+                tiny_skia::Color::from_rgba(dark.r, dark.g, dark.b, 1.0).unwrap(),
+            ),
+        ],
+        SpreadMode::Pad,
+        Transform::identity(),
+    ) {
+        Some(shader) => paint.shader = shader,
+        None => paint.set_color(tiny_skia::Color::from_rgba(col.r, col.g, col.b, 1.0).unwrap()),
+    }
+    paint
+}
+
+/// Renders the treemap of `dir` to a PNG at `path`, laid out like [`partition_view::PartitionView`].
+///
+/// With `legend`, a strip along the bottom shows each extension's share of the bytes.
+pub fn export_png(
+    dir: &AnalyzedDir,
+    path: &Path,
+    (width, height): (u32, u32),
+    params: partition_view::LayoutParams,
+    palette: Palette,
+    legend: bool,
+) -> Result<(), String> {
+    let mut pixmap = Pixmap::new(width, height).ok_or("Invalid image size")?;
+    pixmap.fill(tiny_skia::Color::BLACK);
+
+    let legend_height = if legend {
+        (height as f32 * LEGEND_FRACTION).ceil()
+    } else {
+        0.0
+    };
+    let map_height = height as f32 - legend_height;
+
+    let boxes =
+        partition_view::recursive_box((f64::from(width), f64::from(map_height)), &params, dir);
+    let shares = dir.extension_stats();
+    let ordered = partition_view::extension_colors(&shares, palette);
+    let colors = ordered.iter().cloned().collect::<HashMap<_, _>>();
+
+    for ele in &boxes {
+        ele.for_each_tile((0.0, 0.0), 0, &mut |bounds, ext, _depth| {
+            let col = ext
+                .and_then(|f| colors.get(f).copied())
+                .unwrap_or(Color::from_rgb8(100, 100, 100));
+            if let Some(rect) = Rect::from_xywh(bounds.x, bounds.y, bounds.width, bounds.height) {
+                let paint = paint(col, bounds.x, bounds.y, bounds.width, bounds.height);
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        });
+    }
+
+    if legend {
+        let total = shares.values().map(|f| f.bytes).sum::<u64>().max(1) as f32;
+        let mut x = 0.0;
+        for (ext, col) in &ordered {
+            let w = shares[ext].bytes as f32 / total * width as f32;
+            if let Some(rect) = Rect::from_xywh(x, map_height, w, legend_height) {
+                let paint = paint(*col, x, map_height, w, legend_height);
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+            x += w;
+        }
+    }
+
+    pixmap.save_png(path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,279 @@
+use cosmic::{
+    iced::{keyboard, mouse, Background, Border, Color, Event, Length, Rectangle, Size, Vector},
+    iced_core::{
+        event, layout, overlay, renderer,
+        widget::{operation::focusable::Focusable, tree, Operation, Tree},
+        window, Clipboard, Layout, Renderer as _, Shadow, Shell, Widget,
+    },
+    Element,
+};
+
+/// A column of rows that takes keyboard focus as a whole. While focused, the arrow keys move
+/// between the rows, drawn with a ring around the active one, and Enter, Space or a row's number
+/// activates it.
+pub struct FocusList<'a, Message> {
+    content: Element<'a, Message>,
+    rows: usize,
+    on_activate: fn(usize) -> Message,
+    on_clear: Option<Message>,
+}
+impl<'a, Message> FocusList<'a, Message> {
+    /// `content` lays out its first `rows` children one under the other, like a column.
+    pub fn new(
+        content: impl Into<Element<'a, Message>>,
+        rows: usize,
+        on_activate: fn(usize) -> Message,
+    ) -> Self {
+        Self {
+            content: content.into(),
+            rows,
+            on_activate,
+            on_clear: None,
+        }
+    }
+
+    /// Published when 0 is pressed.
+    pub fn on_clear(mut self, message: Message) -> Self {
+        self.on_clear = Some(message);
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    focused: bool,
+    active: usize,
+}
+impl Focusable for State {
+    fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    fn focus(&mut self) {
+        self.focused = true;
+    }
+
+    fn unfocus(&mut self) {
+        self.focused = false;
+    }
+}
+
+impl<Message: Clone> Widget<Message, cosmic::Theme, cosmic::Renderer> for FocusList<'_, Message> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&mut self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_mut(&mut self.content));
+    }
+
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &cosmic::Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let content = self
+            .content
+            .as_widget()
+            .layout(&mut tree.children[0], renderer, limits);
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &cosmic::Renderer,
+        operation: &mut dyn Operation<()>,
+    ) {
+        let state: &mut State = tree.state.downcast_mut();
+        operation.focusable(state, None);
+        operation.container(None, layout.bounds(), &mut |operation| {
+            self.content.as_widget().operate(
+                &mut tree.children[0],
+                layout.children().next().unwrap(),
+                renderer,
+                operation,
+            );
+        });
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &cosmic::Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        let content = layout.children().next().unwrap();
+        let state: &mut State = tree.state.downcast_mut();
+        // Clicking a row makes it the active one, so the keyboard carries on from there.
+        if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event {
+            let row = content
+                .children()
+                .take(self.rows)
+                .position(|f| cursor.is_over(f.bounds()));
+            state.focused = row.is_some();
+            if let Some(row) = row {
+                state.active = row;
+            }
+        }
+
+        let status = self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event.clone(),
+            content,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+        let state: &mut State = tree.state.downcast_mut();
+        if status == event::Status::Captured || !state.focused || self.rows == 0 {
+            return status;
+        }
+        let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else {
+            return status;
+        };
+        if !modifiers.is_empty() {
+            return status;
+        }
+
+        use keyboard::{key::Named, Key};
+        let last = self.rows - 1;
+        match key {
+            Key::Named(Named::ArrowUp) => state.active = state.active.min(last).saturating_sub(1),
+            Key::Named(Named::ArrowDown) => state.active = (state.active + 1).min(last),
+            Key::Named(Named::Enter | Named::Space) => {
+                shell.publish((self.on_activate)(state.active.min(last)));
+            }
+            Key::Character(c) => match c.parse::<usize>() {
+                Ok(0) => {
+                    let Some(clear) = &self.on_clear else {
+                        return status;
+                    };
+                    shell.publish(clear.clone());
+                }
+                Ok(n @ 1..) if n <= self.rows => {
+                    state.active = n - 1;
+                    shell.publish((self.on_activate)(n - 1));
+                }
+                _ => return status,
+            },
+            _ => return status,
+        }
+        shell.request_redraw(window::RedrawRequest::NextFrame);
+        event::Status::Captured
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &cosmic::Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut cosmic::Renderer,
+        theme: &cosmic::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let content = layout.children().next().unwrap();
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            content,
+            cursor,
+            viewport,
+        );
+
+        let state: &State = tree.state.downcast_ref();
+        let active = content
+            .children()
+            .take(self.rows)
+            .nth(state.active.min(self.rows.saturating_sub(1)));
+        if let Some(row) = active.filter(|_| state.focused) {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: row.bounds(),
+                    border: Border {
+                        color: theme.cosmic().accent_color().into(),
+                        width: 2.0,
+                        radius: 4.0.into(),
+                    },
+                    shadow: Shadow::default(),
+                },
+                Background::Color(Color::TRANSPARENT),
+            );
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &cosmic::Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, cosmic::Theme, cosmic::Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            translation,
+        )
+    }
+
+    #[cfg(feature = "a11y")]
+    fn a11y_nodes(
+        &self,
+        layout: Layout<'_>,
+        tree: &Tree,
+        cursor: mouse::Cursor,
+    ) -> cosmic::iced_accessibility::A11yTree {
+        self.content.as_widget().a11y_nodes(
+            layout.children().next().unwrap(),
+            &tree.children[0],
+            cursor,
+        )
+    }
+}
+impl<'a, Message: Clone + 'static> From<FocusList<'a, Message>> for Element<'a, Message> {
+    fn from(value: FocusList<'a, Message>) -> Self {
+        Self::new(value)
+    }
+}
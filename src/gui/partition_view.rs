@@ -1,17 +1,81 @@
-use std::{collections::HashMap, ffi::OsString, path::PathBuf, sync::atomic::AtomicUsize};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicUsize,
+    time::Duration,
+};
 
 use cosmic::{
     cosmic_theme::palette::{Darken, FromColor, Okhsl, ShiftHue},
     iced::{
         mouse::Button, Background, Border, Color, Length, Point, Radius, Rectangle, Size, Vector,
     },
-    iced_core::{layout, text, Layout, Renderer, Shadow},
+    iced_core::{keyboard, layout, text, time::Instant, window, Layout, Renderer, Shadow},
     prelude::ColorExt,
     widget::Widget,
 };
 use treemap::Mappable;
 
-use crate::analyze::{self, AnalyzedDir, AnalyzedItem};
+/// How long the cursor has to rest on a tile before its popup is shown, by default.
+const HOVER_DELAY: Duration = Duration::from_millis(250);
+/// Layouts of previously shown directories kept for navigating back to them.
+const LAYOUT_CACHE_SIZE: usize = 8;
+const TRANSITION_DURATION: Duration = Duration::from_millis(300);
+/// Fewest characters of a name a tile has to have room for to get a label.
+const MIN_LABEL_CHARS: f32 = 3.0;
+/// Smallest area in pixels a tile is built with, however many tiles are asked for. Anything
+/// smaller couldn't be seen, so it's lumped into the aggregate of its directory instead.
+const MIN_TILE_PIXELS: f32 = 1.0;
+/// Width of the stripes the aggregate tile is drawn with, and the distance between their starts.
+const AGGREGATE_STRIPE_WIDTH: f32 = 2.0;
+const AGGREGATE_STRIPE_SPACING: f32 = 6.0;
+/// Most tiles exposed to screen readers, largest and outermost first, so huge trees don't flood
+/// the accessibility tree.
+#[cfg(feature = "a11y")]
+const A11Y_MAX_TILES: usize = 500;
+/// Mixed into directories when they're drawn apart from files.
+const DIRECTORY_TINT: Color = Color::from_rgb(0.25, 0.3, 0.45);
+/// Mixed into directories taking up too much of their quota.
+const QUOTA_WARNING_COLOR: Color = Color::from_rgb(0.85, 0.1, 0.1);
+
+use super::config::{Palette, DEFAULT_AGGREGATE_LABEL};
+use crate::{
+    analyze::{self, AnalyzedDir, AnalyzedItem, ExtStat, Metric, TreeDiff},
+    format::{DigitGrouping, SizeUnits},
+};
+
+/// Colors of the size bands from the smallest up. Sizes past the last band share its color.
+pub const SIZE_BAND_COLORS: [Color; 6] = [
+    Color::from_rgb8(0x3B, 0x6E, 0xA8),
+    Color::from_rgb8(0x2E, 0x9E, 0x8F),
+    Color::from_rgb8(0x8C, 0xB8, 0x3C),
+    Color::from_rgb8(0xE8, 0xB7, 0x2E),
+    Color::from_rgb8(0xE0, 0x6C, 0x1F),
+    Color::from_rgb8(0xC8, 0x2A, 0x2A),
+];
+
+/// Which band `size` falls in when bands start at each of `thresholds`, smallest first.
+pub fn size_band(size: u64, thresholds: &[u64]) -> usize {
+    thresholds
+        .iter()
+        .take_while(|f| size >= **f)
+        .count()
+        .min(SIZE_BAND_COLORS.len() - 1)
+}
+
+/// The Okabe-Ito palette, repeated when there are more extensions than colors.
+const COLORBLIND_COLORS: [Color; 8] = [
+    Color::from_rgb8(0xE6, 0x9F, 0x00),
+    Color::from_rgb8(0x56, 0xB4, 0xE9),
+    Color::from_rgb8(0x00, 0x9E, 0x73),
+    Color::from_rgb8(0xF0, 0xE4, 0x42),
+    Color::from_rgb8(0x00, 0x72, 0xB2),
+    Color::from_rgb8(0xD5, 0x5E, 0x00),
+    Color::from_rgb8(0xCC, 0x79, 0xA7),
+    Color::from_rgb8(0x99, 0x99, 0x99),
+];
 
 pub enum StateBoxD {
     Branched(Vec<StateBox>),
@@ -24,11 +88,22 @@ pub struct StateBox {
     size: u64,
     name: String,
     extension: Option<OsString>,
-    analyzed_item: Option<analyze::AnalyzedItem>,
+    /// `None` for the aggregate of items too small to show.
+    path: Option<PathBuf>,
+    /// Number of items rolled into the aggregate, or 1.
+    count: u64,
+    /// Files in this box, or the number of items for the aggregate.
+    files: u64,
+    modified: Option<std::time::SystemTime>,
+    /// Whether this box is a directory, even one too small to have its children built.
+    is_dir: bool,
     idx: usize,
 }
 impl StateBox {
-    pub fn recurse_find(&self, at: (f32, f32), p: (f32, f32)) -> Option<(&Self, Option<&Self>)> {
+    /// Returns the innermost box containing `p`, preceded by every box it is nested in. A
+    /// directory too small to have its children built is a box of its own, so it still resolves
+    /// to itself.
+    pub fn recurse_find(&self, at: (f32, f32), p: (f32, f32)) -> Option<Vec<&Self>> {
         let bounds = self.placement;
 
         let quad_bounds = Rectangle::new(
@@ -36,60 +111,293 @@ impl StateBox {
             Size::new(bounds.w as f32, bounds.h as f32),
         );
 
-        if quad_bounds.contains(Point::new(p.0, p.1)) {
+        // Half-open, so a point on the edge shared by two neighbours only hits one of them.
+        let contains = p.0 >= quad_bounds.x
+            && p.0 < quad_bounds.x + quad_bounds.width
+            && p.1 >= quad_bounds.y
+            && p.1 < quad_bounds.y + quad_bounds.height;
+        if contains {
             if let StateBoxD::Branched(d) = &self.d {
                 for ele in d {
-                    if let Some(p) = ele.recurse_find((quad_bounds.x, quad_bounds.y), p) {
-                        return Some((p.0, Some(p.1.unwrap_or(self))));
+                    if let Some(mut chain) = ele.recurse_find((quad_bounds.x, quad_bounds.y), p) {
+                        chain.insert(0, self);
+                        return Some(chain);
                     }
                 }
             }
-            Some((self, None))
+            Some(vec![self])
         } else {
             None
         }
     }
 
-    pub fn draw<R: Renderer + cosmic::iced_core::text::Renderer>(
+    /// The box numbered `idx`, if it's this one or inside it.
+    #[cfg(feature = "a11y")]
+    fn find_idx(&self, idx: usize) -> Option<&Self> {
+        if self.idx == idx {
+            return Some(self);
+        }
+        match &self.d {
+            StateBoxD::Branched(d) => d.iter().find_map(|f| f.find_idx(idx)),
+            StateBoxD::Leaf => None,
+        }
+    }
+
+    /// Collects the bounds of this box and every box inside it, by path.
+    fn collect_bounds(&self, at: (f32, f32), out: &mut HashMap<PathBuf, Rectangle>) {
+        let bounds = self.placement;
+        let quad_bounds = Rectangle::new(
+            Point::new(bounds.x as f32 + at.0, bounds.y as f32 + at.1),
+            Size::new(bounds.w as f32, bounds.h as f32),
+        );
+        if let Some(path) = &self.path {
+            out.insert(path.clone(), quad_bounds);
+        }
+        if let StateBoxD::Branched(d) = &self.d {
+            for ele in d {
+                ele.collect_bounds((quad_bounds.x, quad_bounds.y), out);
+            }
+        }
+    }
+
+    /// Calls `f` with the absolute bounds, extension and depth of this box and every box inside it.
+    pub fn for_each_tile(
         &self,
         at: (f32, f32),
-        renderer: &mut R,
-        // level: usize,
-        to_highlight: usize,
-        text_size: f32,
-        colors: &HashMap<OsString, Color>,
-    ) -> Option<cosmic::iced_core::renderer::Quad> {
+        depth: usize,
+        f: &mut impl FnMut(Rectangle, Option<&OsString>, usize),
+    ) {
         let bounds = self.placement;
+        let quad_bounds = Rectangle::new(
+            Point::new(bounds.x as f32 + at.0, bounds.y as f32 + at.1),
+            Size::new(bounds.w as f32, bounds.h as f32),
+        );
 
+        f(quad_bounds, self.extension.as_ref(), depth);
+        if let StateBoxD::Branched(d) = &self.d {
+            for ele in d {
+                ele.for_each_tile((quad_bounds.x, quad_bounds.y), depth + 1, f);
+            }
+        }
+    }
+
+    /// Describes this box and what's inside it to screen readers, spending one of `budget` on
+    /// each box and leaving out the rest once it runs out.
+    #[cfg(feature = "a11y")]
+    fn a11y_tree(
+        &self,
+        at: (f32, f32),
+        view: &A11yView,
+        budget: &mut usize,
+    ) -> cosmic::iced_accessibility::A11yTree {
+        use cosmic::iced_accessibility::{
+            accesskit::{NodeBuilder, Role},
+            A11yNode, A11yTree,
+        };
+
+        *budget = budget.saturating_sub(1);
+        let bounds = self.placement;
         let quad_bounds = Rectangle::new(
             Point::new(bounds.x as f32 + at.0, bounds.y as f32 + at.1),
             Size::new(bounds.w as f32, bounds.h as f32),
         );
 
-        let col = self
-            .extension
-            .as_ref()
-            .and_then(|f| colors.get(f).copied())
-            .unwrap_or(Color::from_rgb8(100, 100, 100));
+        let mut node = NodeBuilder::new(if self.is_dir {
+            Role::Group
+        } else {
+            Role::TreeItem
+        });
+        node.set_name(view.describe(self));
+        node.set_bounds(a11y_rect(quad_bounds));
+        if self.path.is_some() && self.path.as_deref() == view.selected {
+            node.set_selected(true);
+        }
+        if self.idx == view.highlighted {
+            node.set_hovered();
+        }
+        let node = A11yNode::new(
+            node,
+            cosmic::iced_core::id::Id::new(format!("tile-{}", self.idx)),
+        );
 
-        renderer.fill_quad(
-            cosmic::iced_core::renderer::Quad {
-                bounds: quad_bounds,
-                border: Border::default(),
-                shadow: Default::default(),
+        let mut children = Vec::new();
+        if let StateBoxD::Branched(d) = &self.d {
+            for ele in d {
+                if *budget == 0 {
+                    break;
+                }
+                children.push(ele.a11y_tree((quad_bounds.x, quad_bounds.y), view, budget));
+            }
+        }
+        A11yTree::node_with_child_tree(node, A11yTree::join(children.into_iter()))
+    }
+
+    /// Draws this box and its children, collecting outlines to draw on top of every tile.
+    /// `header` is where the label of the nearest labelled directory around this box went.
+    pub fn draw<R: Renderer + cosmic::iced_core::text::Renderer>(
+        &self,
+        at: (f32, f32),
+        renderer: &mut R,
+        level: usize,
+        header: Option<Rectangle>,
+        params: &DrawParams,
+        overlays: &mut Vec<cosmic::iced_core::renderer::Quad>,
+    ) {
+        let DrawParams {
+            to_highlight,
+            ancestors,
+            selected,
+            marked,
+            text_size,
+            labels,
+            colors,
+            diff,
+            units,
+            grouping,
+            metric,
+            aggregate_label,
+            max_label_chars,
+            warn_above,
+            size_bands,
+            distinct_dirs,
+            high_contrast,
+            tile_border,
+            transition,
+        } = *params;
+        let bounds = self.placement;
+
+        let target = Rectangle::new(
+            Point::new(bounds.x as f32 + at.0, bounds.y as f32 + at.1),
+            Size::new(bounds.w as f32, bounds.h as f32),
+        );
+        // Tiles that were also in the previous layout move from where they were.
+        let quad_bounds = match transition {
+            Some((transition, t)) => {
+                match self.path.as_ref().and_then(|f| transition.from.get(f)) {
+                    Some(from) => lerp_rect(
+                        *from + Vector::new(params.origin.x, params.origin.y),
+                        target,
+                        t,
+                    ),
+                    None => target,
+                }
+            }
+            None => target,
+        };
+        // Slivers thinner than a pixel, and everything inside them, would only be overdrawn by
+        // their neighbours, so the fill of their directory stands in for them.
+        if quad_bounds.width < 1.0 || quad_bounds.height < 1.0 {
+            return;
+        }
+
+        let col = match diff {
+            Some(diff) => match self.path.as_ref().and_then(|f| diff.delta(f)) {
+                Some(d) if d > 0 => Color::from_rgb8(60, 160, 80),
+                Some(d) if d < 0 => Color::from_rgb8(190, 60, 60),
+                _ => Color::from_rgb8(100, 100, 100),
+            },
+            // The aggregate isn't one thing of any size, so it keeps the plain color.
+            None => match size_bands.filter(|_| !self.is_dir && self.path.is_some()) {
+                Some(thresholds) => SIZE_BAND_COLORS[size_band(self.size, thresholds)],
+                None => self
+                    .extension
+                    .as_ref()
+                    .and_then(|f| colors.get(f).copied())
+                    .unwrap_or(Color::from_rgb8(100, 100, 100)),
             },
+        };
+        let col = if self.is_dir && distinct_dirs {
+            col.blend_alpha(DIRECTORY_TINT, 0.35)
+        } else {
+            col
+        };
+        let col = if self.is_dir && warn_above.is_some_and(|f| self.size > f) {
+            col.blend_alpha(QUOTA_WARNING_COLOR, 0.5)
+        } else {
+            col
+        };
+
+        // Each level of nesting gets a thinner border than the one around it.
+        let width = tile_border.width * 0.75f32.powi(level as i32);
+        let border = if high_contrast {
+            Border {
+                color: Color::BLACK,
+                width: width.max(1.0),
+                radius: tile_border.radius.into(),
+            }
+        } else {
+            Border {
+                color: Color::BLACK.scale_alpha(0.6),
+                width,
+                radius: tile_border.radius.into(),
+            }
+        };
+        let background = if high_contrast {
+            Background::Color(col)
+        } else {
             Background::Gradient(cosmic::iced::Gradient::Linear(
                 cosmic::iced::gradient::Linear::new(std::f32::consts::PI / 4.0)
                     .add_stop(0.0, col)
                     .add_stop(1.0, col.blend_alpha(Color::BLACK, 0.5)),
-            )),
+            ))
+        };
+        renderer.fill_quad(
+            cosmic::iced_core::renderer::Quad {
+                bounds: quad_bounds,
+                border,
+                shadow: Shadow::default(),
+            },
+            background,
         );
 
-        let mut maybe_highlight = None;
+        // The aggregate is striped and says what it stands for, so it isn't taken for a file.
+        if self.path.is_none() {
+            let stripe = contrasting_text_color(col).scale_alpha(0.15);
+            let mut x = quad_bounds.x + AGGREGATE_STRIPE_SPACING / 2.0;
+            while x < quad_bounds.x + quad_bounds.width {
+                let width = AGGREGATE_STRIPE_WIDTH.min(quad_bounds.x + quad_bounds.width - x);
+                renderer.fill_quad(
+                    cosmic::iced_core::renderer::Quad {
+                        bounds: Rectangle::new(
+                            Point::new(x, quad_bounds.y),
+                            Size::new(width, quad_bounds.height),
+                        ),
+                        border: Border::default(),
+                        shadow: Shadow::default(),
+                    },
+                    Background::Color(stripe),
+                );
+                x += AGGREGATE_STRIPE_SPACING;
+            }
+
+            let fits = labels
+                && quad_bounds.height > text_size
+                && quad_bounds.width >= text_size * 0.6 * MIN_LABEL_CHARS;
+            if fits {
+                let content = crate::format::format_aggregate(
+                    aggregate_label,
+                    self.count,
+                    self.size,
+                    metric,
+                    units,
+                    grouping,
+                );
+                fill_label(renderer, content, quad_bounds, text_size, col);
+            }
+        }
+
         if let StateBoxD::Branched(d) = &self.d {
-            if quad_bounds.height > text_size {
-                let mut bounds = quad_bounds.size();
-                bounds.height = text_size;
+            let label = Rectangle::new(
+                quad_bounds.position(),
+                Size::new(quad_bounds.width, text_size),
+            );
+            // Labels too cramped to show a few characters, or that would run into the one above
+            // during a transition, are only a smear of text.
+            let fits = labels
+                && quad_bounds.height > text_size
+                && quad_bounds.width >= text_size * 0.6 * MIN_LABEL_CHARS
+                && !header.is_some_and(|f| f.intersects(&label));
+            if fits {
                 // renderer.fill_text(
                 //     cosmic::iced_core::text::Text {
                 //         content: &self.name,
@@ -109,45 +417,75 @@ impl StateBox {
                 //     Color::BLACK,
                 //     quad_bounds,
                 // );
+                let size = crate::format::format_metric(self.size, metric, units, grouping);
+                // Long names are cut in the middle to what's left of the tile after the size,
+                // rather than at its edge where the extension is.
+                let fit = (quad_bounds.width / (text_size * 0.6)) as usize;
+                let room = fit
+                    .saturating_sub(size.chars().count() + 3)
+                    .max(MIN_LABEL_CHARS as usize);
+                let max = max_label_chars.map_or(room, |f| f.min(room));
                 let f = format!(
-                    "{} - {}",
-                    &self.name,
-                    humansize::format_size(self.size, humansize::FormatSizeOptions::default())
-                );
-                renderer.fill_text(
-                    cosmic::iced_core::Text {
-                        content: f,
-                        bounds,
-                        size: text_size.into(),
-                        font: renderer.default_font(),
-                        horizontal_alignment: cosmic::iced::alignment::Horizontal::Left,
-                        vertical_alignment: cosmic::iced::alignment::Vertical::Top,
-                        line_height: text::LineHeight::default(),
-                        shaping: text::Shaping::Advanced,
-                        wrapping: text::Wrapping::WordOrGlyph,
-                    },
-                    Point::new(quad_bounds.x, quad_bounds.y /* + text_size / 2.0*/),
-                    Color::WHITE.blend_alpha(Color::BLACK, 0.8),
-                    quad_bounds,
+                    "{} - {size}",
+                    crate::format::ellipsize_middle(&self.name, max)
                 );
+                fill_label(renderer, f, quad_bounds, text_size, col);
             }
 
+            let header = if fits { Some(label) } else { header };
             for ele in d {
-                if let Some(r) = ele.draw(
-                    (quad_bounds.x, quad_bounds.y),
+                ele.draw(
+                    (target.x, target.y),
                     renderer,
-                    // level + 1,
-                    to_highlight,
-                    text_size,
-                    colors,
-                ) {
-                    maybe_highlight = Some(r);
-                }
+                    level + 1,
+                    header,
+                    params,
+                    overlays,
+                );
             }
         }
 
+        if selected.is_some() && self.path.as_deref() == selected {
+            overlays.push(cosmic::iced_core::renderer::Quad {
+                bounds: quad_bounds,
+                border: Border {
+                    color: cosmic::theme::active().cosmic().accent_color().into(),
+                    width: 2.0,
+                    radius: Radius::default(),
+                },
+                shadow: Shadow::default(),
+            });
+        }
+
+        if marked
+            .zip(self.path.as_ref())
+            .is_some_and(|(m, p)| m.contains(p))
+        {
+            overlays.push(cosmic::iced_core::renderer::Quad {
+                bounds: quad_bounds,
+                border: Border {
+                    color: cosmic::theme::active().cosmic().destructive_color().into(),
+                    width: 2.0,
+                    radius: Radius::default(),
+                },
+                shadow: Shadow::default(),
+            });
+        }
+
+        if ancestors.contains(&self.idx) {
+            overlays.push(cosmic::iced_core::renderer::Quad {
+                bounds: quad_bounds,
+                border: Border {
+                    color: Color::WHITE.scale_alpha(0.4),
+                    width: 1.0,
+                    radius: Radius::default(),
+                },
+                shadow: Shadow::default(),
+            });
+        }
+
         if self.idx == to_highlight {
-            maybe_highlight = Some(cosmic::iced_core::renderer::Quad {
+            overlays.push(cosmic::iced_core::renderer::Quad {
                 bounds: quad_bounds,
                 border: Border {
                     color: Color::WHITE,
@@ -161,31 +499,404 @@ impl StateBox {
                 },
             });
         }
+    }
+}
+
+/// Everything [`StateBox::draw`] needs besides the box itself.
+#[derive(Clone, Copy)]
+pub struct DrawParams<'a> {
+    pub to_highlight: usize,
+    /// Boxes containing `to_highlight`, outlined faintly.
+    pub ancestors: &'a [usize],
+    pub selected: Option<&'a Path>,
+    pub marked: Option<&'a BTreeSet<PathBuf>>,
+    pub text_size: f32,
+    pub labels: bool,
+    pub colors: &'a HashMap<OsString, Color>,
+    pub diff: Option<&'a TreeDiff>,
+    pub units: SizeUnits,
+    pub grouping: DigitGrouping,
+    pub metric: Metric,
+    /// Template for the label of aggregate tiles.
+    pub aggregate_label: &'a str,
+    pub max_label_chars: Option<usize>,
+    /// Directories taking up more than this are tinted.
+    pub warn_above: Option<u64>,
+    /// Colors files by the band of size they fall in instead of by extension.
+    pub size_bands: Option<&'a [u64]>,
+    pub distinct_dirs: bool,
+    pub high_contrast: bool,
+    pub tile_border: TileBorder,
+    /// Where the view is, since the previous layout is relative to it.
+    pub origin: Point,
+    /// The transition from the previous layout, and how far along it is from 0 to 1.
+    pub transition: Option<(&'a Transition, f32)>,
+}
 
-        maybe_highlight
+/// What [`StateBox::a11y_tree`] needs to describe boxes.
+#[cfg(feature = "a11y")]
+struct A11yView<'a> {
+    highlighted: usize,
+    selected: Option<&'a Path>,
+    units: SizeUnits,
+    grouping: DigitGrouping,
+    metric: Metric,
+    aggregate_label: &'a str,
+}
+#[cfg(feature = "a11y")]
+impl A11yView<'_> {
+    /// The name and size of `b`, as its label would say them.
+    fn describe(&self, b: &StateBox) -> String {
+        if b.path.is_none() {
+            return crate::format::format_aggregate(
+                self.aggregate_label,
+                b.count,
+                b.size,
+                self.metric,
+                self.units,
+                self.grouping,
+            );
+        }
+        let size = crate::format::format_metric(b.size, self.metric, self.units, self.grouping);
+        if b.is_dir {
+            format!("Folder {}, {size}", b.name)
+        } else {
+            format!("{}, {size}", b.name)
+        }
+    }
+}
+
+#[cfg(feature = "a11y")]
+fn a11y_rect(r: Rectangle) -> cosmic::iced_accessibility::accesskit::Rect {
+    cosmic::iced_accessibility::accesskit::Rect::new(
+        f64::from(r.x),
+        f64::from(r.y),
+        f64::from(r.x + r.width),
+        f64::from(r.y + r.height),
+    )
+}
+
+/// Tiles of the layout navigated away from, so the new one can animate from them.
+pub struct Transition {
+    started: Instant,
+    /// Bounds of every tile relative to the view, by path.
+    from: HashMap<PathBuf, Rectangle>,
+}
+impl Transition {
+    /// Eased progress from 0 to 1.
+    fn progress(&self, now: Instant) -> f32 {
+        let t = (now.duration_since(self.started).as_secs_f32()
+            / TRANSITION_DURATION.as_secs_f32())
+        .min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+fn lerp_rect(from: Rectangle, to: Rectangle, t: f32) -> Rectangle {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    Rectangle {
+        x: lerp(from.x, to.x),
+        y: lerp(from.y, to.y),
+        width: lerp(from.width, to.width),
+        height: lerp(from.height, to.height),
+    }
+}
+
+/// Border drawn around every tile. Nested tiles get progressively thinner borders.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TileBorder {
+    /// Width around top-level tiles.
+    pub width: f32,
+    pub radius: f32,
+}
+
+/// Writes `content` along the top of a tile of color `bg`, cut off at the tile's edges.
+fn fill_label<R: cosmic::iced_core::text::Renderer>(
+    renderer: &mut R,
+    content: String,
+    tile: Rectangle,
+    text_size: f32,
+    bg: Color,
+) {
+    renderer.fill_text(
+        cosmic::iced_core::Text {
+            content,
+            bounds: Size::new(tile.width, text_size),
+            size: text_size.into(),
+            font: renderer.default_font(),
+            horizontal_alignment: cosmic::iced::alignment::Horizontal::Left,
+            vertical_alignment: cosmic::iced::alignment::Vertical::Top,
+            line_height: text::LineHeight::default(),
+            shaping: text::Shaping::Advanced,
+            wrapping: text::Wrapping::WordOrGlyph,
+        },
+        Point::new(tile.x, tile.y /* + text_size / 2.0*/),
+        contrasting_text_color(bg),
+        tile,
+    );
+}
+
+/// Black or white, whichever contrasts more with `bg`.
+fn contrasting_text_color(bg: Color) -> Color {
+    // WCAG relative luminance; both colours contrast equally with a background at this level.
+    let luminance = bg.into_linear();
+    let luminance = 0.2126 * luminance[0] + 0.7152 * luminance[1] + 0.0722 * luminance[2];
+    if luminance > 0.179 {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
+/// Inputs to [`recursive_box`] that stay the same at every depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutParams {
+    pub minimum_area: f64,
+    /// Tiles narrower than this (short side over long side) are rolled into the aggregate.
+    pub minimum_aspect: f64,
+    /// Room left above the children of each directory for its label.
+    pub header_height: f64,
+    pub metric: Metric,
+}
+
+/// Lays out the children of `dir` in a tile of `space`, below a header of
+/// [`LayoutParams::header_height`] for its label, and their children in turn. Each child takes its
+/// own header out of its own tile, so what's passed down is the child's whole tile and the header
+/// is only ever taken off once.
+pub fn recursive_box(
+    space: (f64, f64),
+    params: &LayoutParams,
+    dir: &AnalyzedDir,
+    // text_size: f32,
+) -> Vec<StateBox> {
+    static IDX: AtomicUsize = AtomicUsize::new(0);
+
+    let min = params.minimum_area;
+    // The header never takes more than half the tile, so thick ones still leave room below.
+    let header = params.header_height.clamp(0.0, space.1 / 2.0);
+    // Below this size every child would be lumped into the aggregate tile, so the children are
+    // only built once the directory itself is zoomed into. What's left under the header is all
+    // that's checked, so a tile with room for even one child below its label gets it.
+    if space.0 * (space.1 - header) < min {
+        return vec![];
     }
+
+    let partitioned = analyze::partition(
+        (space.0, space.1 - header),
+        min,
+        params.minimum_aspect,
+        params.metric,
+        dir,
+    );
+
+    partitioned
+        .into_iter()
+        .map(|mut item| {
+            let mut bounds_ = *item.bounds();
+            bounds_.y += header;
+            item.set_bounds(bounds_);
+            // dbg!(opt_dir);
+            let d = match item.item {
+                Some(analyze::AnalyzedItem::Dir(d)) => {
+                    StateBoxD::Branched(recursive_box(
+                        (item.bounds().w, item.bounds().h),
+                        params,
+                        d,
+                        // text_size,
+                    ))
+                }
+                _ => StateBoxD::Leaf,
+            };
+
+            let ext = item.item.and_then(|f| {
+                if let AnalyzedItem::File(f) = f {
+                    f.extension()
+                } else {
+                    None
+                }
+            });
+            StateBox {
+                d,
+                name: item.item.map_or("<files>".into(), |f| {
+                    f.name()
+                        .map(|f| f.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                }),
+                idx: IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+                path: item.item.map(AnalyzedItem::path),
+                is_dir: matches!(item.item, Some(AnalyzedItem::Dir(_))),
+                placement: item.placement,
+                size: item.size,
+                count: item.count,
+                files: item.item.map_or(item.count, AnalyzedItem::num_files),
+                modified: item.item.and_then(AnalyzedItem::modified),
+                extension: ext.map(std::ffi::OsStr::to_os_string),
+            }
+        })
+        .collect()
+}
+
+/// Assigns each extension a color from `palette`, ordered from the most to the least bytes.
+pub fn extension_colors(
+    stats: &HashMap<OsString, ExtStat>,
+    palette: Palette,
+) -> Vec<(OsString, Color)> {
+    let len = stats.len();
+
+    let base_col = Okhsl::from_color(cosmic::theme::active().cosmic().accent.base.color);
+    let to_color = |col: Okhsl| {
+        let rgba = cosmic::cosmic_theme::palette::Srgb::from_color(col);
+        cosmic::iced::Color::from_linear_rgba(rgba.red, rgba.green, rgba.blue, 1.0)
+    };
+    let mut ext = stats.iter().collect::<Vec<_>>();
+    ext.sort_by_key(|f| f.1.bytes);
+
+    ext.into_iter()
+        .rev()
+        .enumerate()
+        .map(|(index, f)| {
+            let col = match palette {
+                Palette::Accent => {
+                    let shifted = extension_hue(f.0);
+                    to_color(ShiftHue::shift_hue(base_col, shifted * 360.0).darken(0.5))
+                }
+                Palette::Colorblind => COLORBLIND_COLORS[index % COLORBLIND_COLORS.len()],
+                Palette::Monochrome => {
+                    let rank = index as f32 / len.saturating_sub(1).max(1) as f32;
+                    to_color(Okhsl {
+                        lightness: 0.7 - 0.45 * rank,
+                        ..base_col
+                    })
+                }
+            };
+            (f.0.clone(), col)
+        })
+        .collect()
+}
+
+/// A position on the hue wheel from 0 to 1 that only depends on `ext`, so an extension keeps its
+/// color between directories and scans.
+fn extension_hue(ext: &OsStr) -> f32 {
+    // FNV-1a, since the std hasher may change between Rust releases.
+    let hash = ext
+        .as_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ u64::from(b.to_ascii_lowercase())).wrapping_mul(0x0100_0000_01b3)
+        });
+    (hash >> 40) as f32 / (1 << 24) as f32
 }
 
 pub struct State {
     boxes: Vec<StateBox>,
     highlighted: usize,
-    // highlighted_popup: Option<(Point, String, u64, PathBuf)>,
+    highlighted_ancestors: Vec<usize>,
+    /// Popup for `highlighted`, waiting to be published once the cursor has rested long enough.
+    highlighted_popup: Option<(Instant, HoverInfo)>,
+    popup_shown: bool,
     /// Extension -> Number of Files
     ordered_extension_map: Vec<(OsString, Color)>,
     extension_map: HashMap<OsString, Color>,
-    contructed_for: Size<f32>,
-    constructed_for_path: PathBuf,
+    /// Directory and key `boxes` were built for.
+    constructed_for: Option<(PathBuf, LayoutKey)>,
+    /// Layouts of recently shown directories, most recent last.
+    cache: Vec<CachedLayout>,
+    transition: Option<Transition>,
     should_broadcast_ordered: bool,
+    /// Held while clicking, to mark tiles instead of opening them.
+    modifiers: keyboard::Modifiers,
+}
+
+/// Everything a layout depends on besides the directory's path. A rescan can change the tree in
+/// place without changing its path, so its size, counts and modification time are included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LayoutKey {
+    bounds: Size<f32>,
+    params: LayoutParams,
+    palette: Palette,
+    size: u64,
+    num_files: u64,
+    num_dirs: u64,
+    modified: Option<std::time::SystemTime>,
+}
+
+struct CachedLayout {
+    path: PathBuf,
+    key: LayoutKey,
+    boxes: Vec<StateBox>,
+    ordered_extension_map: Vec<(OsString, Color)>,
+}
+
+/// Details of the tile under the cursor.
+#[derive(Debug, Clone)]
+pub struct HoverInfo {
+    pub position: Point,
+    pub name: String,
+    /// How much of `metric` the tile takes up, as do the sizes below.
+    pub size: u64,
+    pub metric: Metric,
+    pub path: PathBuf,
+    /// Size of the directory containing the tile.
+    pub parent_size: u64,
+    /// Size of the directory the view is showing.
+    pub total_size: u64,
+    /// Number of items rolled into the aggregate tile, if that's what is hovered.
+    pub aggregated: Option<u64>,
+    /// Files in the tile, counting itself if it's a file.
+    pub files: u64,
+    pub modified: Option<std::time::SystemTime>,
+}
+impl HoverInfo {
+    pub fn percent_of_parent(&self) -> f64 {
+        percent(self.size, self.parent_size)
+    }
+
+    pub fn percent_of_total(&self) -> f64 {
+        percent(self.size, self.total_size)
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64 * 100.0
+    }
 }
 
 #[allow(clippy::type_complexity)]
 pub struct PartitionView<'a, Msg> {
     items: &'a AnalyzedDir,
+    diff: Option<&'a TreeDiff>,
+    selected: Option<&'a Path>,
+    marked: Option<&'a BTreeSet<PathBuf>>,
+    units: SizeUnits,
+    grouping: DigitGrouping,
+    metric: Metric,
+    aggregate_label: &'a str,
+    max_label_chars: Option<usize>,
+    warn_above: Option<u64>,
+    size_bands: Option<&'a [u64]>,
+    distinct_dirs: bool,
+    high_contrast: bool,
+    tile_border: TileBorder,
+    palette: Palette,
+    animate: bool,
+    hover_delay: Duration,
+    extension_stats: Option<&'a HashMap<OsString, ExtStat>>,
     text_size: f32,
+    header_height: f32,
+    labels: bool,
     minimum_area: f32,
+    /// Overrides `minimum_area` to fit about this many tiles.
+    target_tiles: Option<u32>,
+    minimum_aspect: f32,
     on_click: Box<dyn FnMut(PathBuf) -> Msg>,
+    on_aggregate_click: Option<Box<dyn FnMut(PathBuf, u64) -> Msg>>,
+    on_select: Option<Box<dyn FnMut(PathBuf) -> Msg>>,
+    on_mark: Option<Box<dyn FnMut(PathBuf) -> Msg>>,
     on_colors: Box<dyn FnMut(Vec<(OsString, Color)>) -> Msg>,
-    on_item_hovered: Box<dyn FnMut(Option<(Point, String, u64, PathBuf)>) -> Msg>, // extension_map: Arc<Mutex<Vec<(OsString, Color)>>>,
+    on_item_hovered: Box<dyn FnMut(Option<HoverInfo>) -> Msg>, // extension_map: Arc<Mutex<Vec<(OsString, Color)>>>,
 }
 impl<'a, Msg> PartitionView<'a, Msg> {
     pub fn new(
@@ -194,18 +905,189 @@ impl<'a, Msg> PartitionView<'a, Msg> {
         minimum_area: f32,
         on_click: impl FnMut(PathBuf) -> Msg + 'static,
         on_colors: impl FnMut(Vec<(OsString, Color)>) -> Msg + 'static,
-        on_item_hovered: impl FnMut(Option<(Point, String, u64, PathBuf)>) -> Msg + 'static, // extension_map: Arc<Mutex<Vec<(OsString, Color)>>>,
+        on_item_hovered: impl FnMut(Option<HoverInfo>) -> Msg + 'static, // extension_map: Arc<Mutex<Vec<(OsString, Color)>>>,
     ) -> Self {
         Self {
             items,
+            diff: None,
+            selected: None,
+            marked: None,
+            units: SizeUnits::default(),
+            grouping: DigitGrouping::default(),
+            metric: Metric::default(),
+            aggregate_label: DEFAULT_AGGREGATE_LABEL,
+            max_label_chars: None,
+            warn_above: None,
+            size_bands: None,
+            distinct_dirs: false,
+            high_contrast: false,
+            tile_border: TileBorder::default(),
+            palette: Palette::default(),
+            animate: false,
+            hover_delay: HOVER_DELAY,
+            extension_stats: None,
             text_size,
+            header_height: text_size * 1.4,
+            labels: true,
             minimum_area,
+            target_tiles: None,
+            minimum_aspect: 0.0,
             on_click: Box::new(on_click),
+            on_aggregate_click: None,
+            on_select: None,
+            on_mark: None,
             // extension_map,
             on_colors: Box::new(on_colors),
             on_item_hovered: Box::new(on_item_hovered),
         }
     }
+
+    /// Colors tiles by how much they grew or shrank instead of by extension.
+    pub fn diff(mut self, diff: Option<&'a TreeDiff>) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    /// Called with the directory and number of items when the aggregate tile is clicked, instead
+    /// of opening the directory.
+    pub fn on_aggregate_click(mut self, f: impl FnMut(PathBuf, u64) -> Msg + 'static) -> Self {
+        self.on_aggregate_click = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the path of a file or symlink when its tile is clicked, instead of `on_click`.
+    /// Directories are still opened.
+    pub fn on_select(mut self, f: impl FnMut(PathBuf) -> Msg + 'static) -> Self {
+        self.on_select = Some(Box::new(f));
+        self
+    }
+
+    /// Called with the path of a tile when it's clicked while holding Ctrl, instead of opening or
+    /// selecting it.
+    pub fn on_mark(mut self, f: impl FnMut(PathBuf) -> Msg + 'static) -> Self {
+        self.on_mark = Some(Box::new(f));
+        self
+    }
+
+    /// Picks the minimum area from the size of the view so about `tiles` tiles fit, keeping the
+    /// treemap about as busy whatever its size. `None` keeps the one it was created with.
+    pub fn target_tiles(mut self, tiles: Option<u32>) -> Self {
+        self.target_tiles = tiles;
+        self
+    }
+
+    /// Rolls tiles narrower than `aspect` (short side over long side) into the aggregate tile.
+    pub fn minimum_aspect(mut self, aspect: f32) -> Self {
+        self.minimum_aspect = aspect;
+        self
+    }
+
+    /// What tiles are sized by, and so what their labels show.
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Label of the tile standing for items too small to show, filled in by
+    /// [`crate::format::format_aggregate`].
+    pub fn aggregate_label(mut self, template: &'a str) -> Self {
+        self.aggregate_label = template;
+        self
+    }
+
+    /// Shortens names in labels to at most `max` characters, as well as to what fits in their
+    /// tile.
+    pub fn max_label_chars(mut self, max: Option<usize>) -> Self {
+        self.max_label_chars = max;
+        self
+    }
+
+    /// Tints directories taking up more than `warn_above` of what tiles are sized by, such as
+    /// part of a quota.
+    pub fn warn_above(mut self, warn_above: Option<u64>) -> Self {
+        self.warn_above = warn_above;
+        self
+    }
+
+    /// Room left above the children of each directory for its label, which is otherwise a bit
+    /// more than the text size.
+    pub fn header_height(mut self, height: f32) -> Self {
+        self.header_height = height;
+        self
+    }
+
+    /// Whether tiles are labeled with their name and size. Without labels, directories don't
+    /// leave room for one either.
+    pub fn labels(mut self, labels: bool) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Colors files by which of the bands starting at `thresholds` their size falls in, instead of
+    /// by extension.
+    pub fn size_bands(mut self, thresholds: Option<&'a [u64]>) -> Self {
+        self.size_bands = thresholds;
+        self
+    }
+
+    /// Tints directories, shading and all, so they can be told apart from files.
+    pub fn distinct_dirs(mut self, distinct_dirs: bool) -> Self {
+        self.distinct_dirs = distinct_dirs;
+        self
+    }
+
+    pub fn high_contrast(mut self, high_contrast: bool) -> Self {
+        self.high_contrast = high_contrast;
+        self
+    }
+
+    pub fn tile_border(mut self, tile_border: TileBorder) -> Self {
+        self.tile_border = tile_border;
+        self
+    }
+
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Extension totals of `items`, when they're already known. Otherwise they're computed on
+    /// every relayout.
+    pub fn extension_stats(mut self, stats: &'a HashMap<OsString, ExtStat>) -> Self {
+        self.extension_stats = Some(stats);
+        self
+    }
+
+    /// Animates tiles from the previous layout when navigating to another directory.
+    pub fn animate(mut self, animate: bool) -> Self {
+        self.animate = animate;
+        self
+    }
+
+    /// How long the cursor has to rest on a tile before it's reported as hovered.
+    pub fn hover_delay(mut self, delay: Duration) -> Self {
+        self.hover_delay = delay;
+        self
+    }
+
+    /// How the sizes in labels are formatted.
+    pub fn units(mut self, units: SizeUnits, grouping: DigitGrouping) -> Self {
+        self.units = units;
+        self.grouping = grouping;
+        self
+    }
+
+    /// Outlines the tiles for `marked`, if they're shown.
+    pub fn marked(mut self, marked: &'a BTreeSet<PathBuf>) -> Self {
+        self.marked = Some(marked);
+        self
+    }
+
+    /// Outlines the tile for `selected`, if it's shown.
+    pub fn selected(mut self, selected: Option<&'a Path>) -> Self {
+        self.selected = selected;
+        self
+    }
 }
 impl<
         Message,
@@ -217,12 +1099,16 @@ impl<
         cosmic::iced_core::widget::tree::State::Some(Box::new(State {
             boxes: vec![],
             highlighted: usize::MAX,
-            // highlighted_popup: None,
+            highlighted_ancestors: Vec::new(),
+            highlighted_popup: None,
+            popup_shown: false,
             extension_map: Default::default(),
-            contructed_for: Size::ZERO,
-            constructed_for_path: Default::default(),
+            constructed_for: None,
+            cache: Vec::new(),
+            transition: None,
             ordered_extension_map: Vec::new(),
             should_broadcast_ordered: false,
+            modifiers: keyboard::Modifiers::default(),
         }))
     }
 
@@ -243,118 +1129,91 @@ impl<
 
         let state: &mut State = tree.state.downcast_mut();
 
-        if layout.bounds().size() != state.contructed_for
-            || self.items.path != state.constructed_for_path
-        {
-            fn recursive_box(
-                space: (f64, f64),
-                min: f64,
-                dir: &AnalyzedDir,
-                text_offset: f64,
-                // text_size: f32,
-                extension_map: &mut HashMap<OsString, usize>,
-            ) -> Vec<StateBox> {
-                static IDX: AtomicUsize = AtomicUsize::new(0);
-
-                if space.1 < text_offset * 1.4 {
-                    return vec![];
+        let minimum_area = match self.target_tiles {
+            Some(tiles) => layout.bounds().width * layout.bounds().height / tiles.max(1) as f32,
+            None => self.minimum_area,
+        }
+        .max(MIN_TILE_PIXELS);
+        let params = LayoutParams {
+            minimum_area: f64::from(minimum_area),
+            minimum_aspect: f64::from(self.minimum_aspect),
+            header_height: if self.labels {
+                f64::from(self.header_height)
+            } else {
+                0.0
+            },
+            metric: self.metric,
+        };
+        let key = LayoutKey {
+            bounds: layout.bounds().size(),
+            params,
+            palette: self.palette,
+            size: self.items.size,
+            num_files: self.items.num_files,
+            num_dirs: self.items.num_dirs,
+            modified: self.items.modified,
+        };
+        let up_to_date = state
+            .constructed_for
+            .as_ref()
+            .is_some_and(|(path, k)| **path == *self.items.path && *k == key);
+        if !up_to_date {
+            let navigated = state
+                .constructed_for
+                .as_ref()
+                .is_some_and(|(path, _)| **path != *self.items.path);
+            state.transition = (self.animate && navigated).then(|| {
+                let mut from = HashMap::new();
+                for b in &state.boxes {
+                    b.collect_bounds((0.0, 0.0), &mut from);
                 }
+                Transition {
+                    started: Instant::now(),
+                    from,
+                }
+            });
 
-                let partitioned =
-                    analyze::partition((space.0, text_offset.mul_add(-1.4, space.1)), min, dir);
-
-                partitioned
-                    .into_iter()
-                    .map(|mut item| {
-                        let mut bounds_ = *item.bounds();
-                        bounds_.y += text_offset * 1.4;
-                        item.set_bounds(bounds_);
-                        // dbg!(opt_dir);
-                        let d = match item.item {
-                            Some(analyze::AnalyzedItem::Dir(d)) => {
-                                StateBoxD::Branched(recursive_box(
-                                    (item.bounds().w, item.bounds().h),
-                                    min,
-                                    d,
-                                    text_offset,
-                                    // text_size,
-                                    extension_map,
-                                ))
-                            }
-                            _ => StateBoxD::Leaf,
-                        };
-
-                        let ext = item.item.and_then(|f| {
-                            if let AnalyzedItem::File(f) = f {
-                                f.path.extension()
-                            } else {
-                                None
-                            }
-                        });
-                        if let Some(ext) = ext {
-                            if extension_map.contains_key(ext) {
-                                *extension_map.get_mut(ext).unwrap() += item.size as usize;
-                            } else {
-                                extension_map.insert(ext.to_owned(), item.size as _);
-                            }
-                        }
-
-                        StateBox {
-                            d,
-                            name: item.item.map_or("<files>".into(), |f| {
-                                f.name()
-                                    .map(|f| f.to_string_lossy().into_owned())
-                                    .unwrap_or_default()
-                            }),
-                            idx: IDX.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
-                            analyzed_item: item.item.cloned(),
-                            placement: item.placement,
-                            size: item.size,
-                            extension: ext.map(std::ffi::OsStr::to_os_string),
-                        }
-                    })
-                    .collect()
+            // Keep the current layout around in case it's navigated back to.
+            if let Some((path, key)) = state.constructed_for.take() {
+                if state.cache.len() == LAYOUT_CACHE_SIZE {
+                    state.cache.remove(0);
+                }
+                state.cache.push(CachedLayout {
+                    path,
+                    key,
+                    boxes: std::mem::take(&mut state.boxes),
+                    ordered_extension_map: std::mem::take(&mut state.ordered_extension_map),
+                });
             }
 
-            let mut extension_map = Default::default();
-            state.boxes = recursive_box(
-                (
-                    f64::from(layout.bounds().width),
-                    f64::from(layout.bounds().height),
-                ),
-                f64::from(self.minimum_area),
-                self.items,
-                f64::from(self.text_size),
-                // self.text_size,
-                &mut extension_map,
-            );
-
-            let len = extension_map.len();
-
-            let base_col = cosmic::theme::active().cosmic().accent.base;
-            let cols = Vec::from_iter((0usize..).take(extension_map.len()).map(|f| {
-                let shifted = (f as f32 * 1.618).rem_euclid(1.0);
-
-                let new = ShiftHue::shift_hue(Okhsl::from_color(base_col.color), shifted * 360.0)
-                    .darken(0.5);
-                let rgba = cosmic::cosmic_theme::palette::Srgb::from_color(new);
-                cosmic::iced::Color::from_linear_rgba(rgba.red, rgba.green, rgba.blue, 1.0)
-            }));
-            let mut ext = extension_map.into_iter().collect::<Vec<_>>();
-            ext.sort_by_key(|f| f.1);
+            let cached = state
+                .cache
+                .iter()
+                .position(|f| *f.path == *self.items.path && f.key == key);
+            if let Some(i) = cached {
+                let cached = state.cache.remove(i);
+                state.boxes = cached.boxes;
+                state.ordered_extension_map = cached.ordered_extension_map;
+            } else {
+                state.boxes = recursive_box(
+                    (
+                        f64::from(layout.bounds().width),
+                        f64::from(layout.bounds().height),
+                    ),
+                    &params,
+                    self.items,
+                    // self.text_size,
+                );
+                state.ordered_extension_map = match self.extension_stats {
+                    Some(stats) => extension_colors(stats, self.palette),
+                    None => extension_colors(&self.items.extension_stats(), self.palette),
+                };
+            }
 
             state.should_broadcast_ordered = true;
 
-            state.ordered_extension_map = ext
-                .into_iter()
-                .rev()
-                .enumerate()
-                .take(len)
-                .map(|(index, f)| (f.0, cols[index]))
-                .collect();
             state.extension_map = state.ordered_extension_map.clone().into_iter().collect();
-            state.contructed_for = layout.bounds().size();
-            state.constructed_for_path = self.items.path.clone();
+            state.constructed_for = Some((self.items.path.to_path_buf(), key));
         }
 
         layout
@@ -378,51 +1237,114 @@ impl<
             shell.publish((self.on_colors)(state.ordered_extension_map.clone()));
         }
 
+        if let cosmic::iced::Event::Window(window::Event::RedrawRequested(now)) = event {
+            if let Some((at, _)) = &state.highlighted_popup {
+                if now >= *at {
+                    let (_, popup) = state.highlighted_popup.take().unwrap();
+                    state.popup_shown = true;
+                    shell.publish((self.on_item_hovered)(Some(popup)));
+                } else {
+                    shell.request_redraw(window::RedrawRequest::At(*at));
+                }
+            }
+            if let Some(transition) = &state.transition {
+                if transition.progress(now) < 1.0 {
+                    shell.request_redraw(window::RedrawRequest::NextFrame);
+                } else {
+                    state.transition = None;
+                }
+            }
+        }
+
+        if let cosmic::iced::Event::Keyboard(keyboard::Event::ModifiersChanged(m)) = event {
+            state.modifiers = m;
+        }
+
+        let mut status = cosmic::iced_core::event::Status::Ignored;
         if let cosmic::iced::Event::Mouse(mev) = event {
             let pos = cursor.position().unwrap_or_default();
 
-            let highlighted = state.boxes.iter().find_map(|b| {
-                b.recurse_find((layout.bounds().x, layout.bounds().y), (pos.x, pos.y))
+            // Nothing is hit while the cursor is outside the view, or outside the window.
+            let chain = cursor.position_over(layout.bounds()).and_then(|pos| {
+                state.boxes.iter().find_map(|b| {
+                    b.recurse_find((layout.bounds().x, layout.bounds().y), (pos.x, pos.y))
+                })
+            });
+            let highlighted = chain.as_deref().and_then(|c| match c {
+                [ancestors @ .., f] => Some((*f, ancestors.last().copied())),
+                [] => None,
             });
             match mev {
                 cosmic::iced::mouse::Event::CursorMoved { position: _ } => {
-                    shell.publish((self.on_item_hovered)(highlighted.map(|(f, _)| {
-                        (
-                            pos,
-                            f.name.clone(),
-                            f.size,
-                            f.analyzed_item
-                                .as_ref()
-                                .map(|f| f.path().to_owned())
-                                .unwrap_or_default(),
-                        )
-                    })));
-                    // state.highlighted_popup = highlighted.map(|(f, _)| ());
-                    state.highlighted = highlighted.map_or(usize::MAX, |(f, _)| f.idx);
+                    let idx = highlighted.map_or(usize::MAX, |(f, _)| f.idx);
+                    if idx != state.highlighted {
+                        state.highlighted = idx;
+                        state.highlighted_ancestors = chain
+                            .as_deref()
+                            .map(|c| c[..c.len() - 1].iter().map(|f| f.idx).collect())
+                            .unwrap_or_default();
+                        if std::mem::take(&mut state.popup_shown) {
+                            shell.publish((self.on_item_hovered)(None));
+                        }
+
+                        let at = Instant::now() + self.hover_delay;
+                        state.highlighted_popup = highlighted.map(|(f, parent)| {
+                            (
+                                at,
+                                HoverInfo {
+                                    position: pos,
+                                    name: f.name.clone(),
+                                    size: f.size,
+                                    metric: self.metric,
+                                    path: f.path.clone().unwrap_or_default(),
+                                    parent_size: parent
+                                        .map_or(self.metric.of_dir(self.items), |p| p.size),
+                                    aggregated: f.path.is_none().then_some(f.count),
+                                    files: f.files,
+                                    modified: f.modified,
+                                    total_size: self.metric.of_dir(self.items),
+                                },
+                            )
+                        });
+                        if state.highlighted_popup.is_some() {
+                            shell.request_redraw(window::RedrawRequest::At(at));
+                        }
+                    }
                 }
                 cosmic::iced::mouse::Event::ButtonPressed(Button::Left) => {
+                    // A handled click doesn't go on to whatever is beneath the view.
                     if let Some((f, parent)) = highlighted {
-                        shell.publish((self.on_click)(
-                            f.analyzed_item
-                                .as_ref()
-                                .map(|f| f.path().to_owned())
-                                .unwrap_or_else(|| {
-                                    parent
-                                        .map(|f| {
-                                            f.analyzed_item.as_ref().unwrap().path().to_owned()
-                                        })
-                                        .unwrap_or_else(|| {
-                                            f.analyzed_item.as_ref().unwrap().path().to_owned()
-                                        })
-                                }),
-                        ));
+                        status = cosmic::iced_core::event::Status::Captured;
+                        if let Some(path) = &f.path {
+                            let msg = match (&mut self.on_mark, &mut self.on_select) {
+                                (Some(on_mark), _) if state.modifiers.control() => {
+                                    on_mark(path.clone())
+                                }
+                                (_, Some(on_select)) if !f.is_dir => on_select(path.clone()),
+                                _ => (self.on_click)(path.clone()),
+                            };
+                            shell.publish(msg);
+                        } else {
+                            // The aggregate tile has no path of its own, so it stands for the
+                            // small items of its directory.
+                            let dir = parent
+                                .map_or(Some(&*self.items.path), |p| p.path.as_deref())
+                                .map(Path::to_path_buf);
+                            if let Some(dir) = dir {
+                                let msg = match &mut self.on_aggregate_click {
+                                    Some(on_aggregate_click) => on_aggregate_click(dir, f.count),
+                                    None => (self.on_click)(dir),
+                                };
+                                shell.publish(msg);
+                            }
+                        }
                     }
                 }
                 _ => {}
             }
         }
 
-        cosmic::iced_core::event::Status::Ignored
+        status
     }
 
     fn draw(
@@ -437,26 +1359,281 @@ impl<
     ) {
         let state: &State = tree.state.downcast_ref();
 
-        let mut highlight = None;
+        let params = DrawParams {
+            to_highlight: state.highlighted,
+            ancestors: &state.highlighted_ancestors,
+            selected: self.selected,
+            marked: self.marked,
+            text_size: self.text_size,
+            labels: self.labels,
+            colors: &state.extension_map,
+            diff: self.diff,
+            units: self.units,
+            grouping: self.grouping,
+            metric: self.metric,
+            aggregate_label: self.aggregate_label,
+            max_label_chars: self.max_label_chars,
+            warn_above: self.warn_above,
+            size_bands: self.size_bands,
+            distinct_dirs: self.distinct_dirs,
+            high_contrast: self.high_contrast,
+            tile_border: self.tile_border,
+            origin: layout.bounds().position(),
+            transition: state
+                .transition
+                .as_ref()
+                .map(|f| (f, f.progress(Instant::now()))),
+        };
+        let mut overlays = Vec::new();
         for ele in &state.boxes {
-            if let Some(r) = ele.draw(
+            ele.draw(
                 (layout.bounds().x, layout.bounds().y),
                 renderer,
-                // 0,
-                state.highlighted,
-                self.text_size,
-                &state.extension_map,
-            ) {
-                highlight = Some(r);
-            }
+                0,
+                None,
+                &params,
+                &mut overlays,
+            );
         }
-        if let Some(r) = highlight {
+        for r in overlays {
             renderer.fill_quad(r, Background::Color(Color::TRANSPARENT));
         }
     }
+
+    /// The tiles as a tree of named items, plus a live region announcing the hovered one.
+    #[cfg(feature = "a11y")]
+    fn a11y_nodes(
+        &self,
+        layout: Layout<'_>,
+        tree: &cosmic::iced_core::widget::Tree,
+        _cursor: cosmic::iced_core::mouse::Cursor,
+    ) -> cosmic::iced_accessibility::A11yTree {
+        use cosmic::iced_accessibility::{
+            accesskit::{Live, NodeBuilder, Role},
+            A11yNode, A11yTree,
+        };
+
+        let state: &State = tree.state.downcast_ref();
+        let view = A11yView {
+            highlighted: state.highlighted,
+            selected: self.selected,
+            units: self.units,
+            grouping: self.grouping,
+            metric: self.metric,
+            aggregate_label: self.aggregate_label,
+        };
+        let bounds = layout.bounds();
+        let id = |name: &str| {
+            cosmic::iced_core::id::Id::new(format!("treemap-{name}-{}", self.items.path.display()))
+        };
+
+        let mut budget = A11Y_MAX_TILES;
+        let mut children = Vec::new();
+        for b in &state.boxes {
+            if budget == 0 {
+                break;
+            }
+            children.push(b.a11y_tree((bounds.x, bounds.y), &view, &mut budget));
+        }
+
+        // Screen readers read this out whenever the hovered tile changes.
+        let mut status = NodeBuilder::new(Role::Label);
+        status.set_live(Live::Polite);
+        let hovered = state
+            .boxes
+            .iter()
+            .find_map(|b| b.find_idx(state.highlighted));
+        if let Some(hovered) = hovered {
+            status.set_name(view.describe(hovered));
+        }
+        children.push(A11yTree::leaf(status, id("status")));
+
+        let mut node = NodeBuilder::new(Role::Tree);
+        node.set_name(format!("Treemap of {}", self.items.path.display()));
+        node.set_bounds(a11y_rect(bounds));
+        A11yTree::node_with_child_tree(
+            A11yNode::new(node, id("root")),
+            A11yTree::join(children.into_iter()),
+        )
+    }
 }
 impl<'a, Message: 'static> From<PartitionView<'a, Message>> for cosmic::Element<'a, Message> {
     fn from(value: PartitionView<'a, Message>) -> Self {
         Self::new(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze::{analyze_dir_with, mem_fs::MemFs, Context};
+
+    fn scan(fs: &MemFs) -> AnalyzedDir {
+        analyze_dir_with(fs, Path::new("/r"), &Context::default()).unwrap()
+    }
+
+    fn tree() -> AnalyzedDir {
+        scan(
+            &MemFs::new()
+                .file("/r/big", 400_000)
+                .file("/r/d/a", 200_000)
+                .file("/r/d/b", 100_000)
+                .file("/r/d/e/c", 50_000)
+                .file("/r/small", 4_000),
+        )
+    }
+
+    #[test]
+    fn text_contrasts_with_background() {
+        assert_eq!(contrasting_text_color(Color::WHITE), Color::BLACK);
+        assert_eq!(contrasting_text_color(Color::BLACK), Color::WHITE);
+        // Yellow is nearly as light as white, and blue nearly as dark as black.
+        assert_eq!(
+            contrasting_text_color(Color::from_rgb(1.0, 1.0, 0.0)),
+            Color::BLACK
+        );
+        assert_eq!(
+            contrasting_text_color(Color::from_rgb(0.0, 0.0, 1.0)),
+            Color::WHITE
+        );
+        // Mid grey is past the point where both contrast equally, toward white.
+        assert_eq!(
+            contrasting_text_color(Color::from_rgb(0.5, 0.5, 0.5)),
+            Color::BLACK
+        );
+    }
+
+    fn params(minimum_area: f64, header_height: f64) -> LayoutParams {
+        LayoutParams {
+            minimum_area,
+            minimum_aspect: 0.0,
+            header_height,
+            metric: Metric::Bytes,
+        }
+    }
+
+    /// Checks that every box lies in the part of its parent's tile below the header, and that
+    /// the boxes of each directory fill that part.
+    fn assert_nested(boxes: &[StateBox], space: (f64, f64), header_height: f64) {
+        let header = header_height.clamp(0.0, space.1 / 2.0);
+        let eps = 1e-6;
+        for b in boxes {
+            let r = b.placement;
+            assert!(r.x >= -eps && r.y >= header - eps, "{}", b.name);
+            assert!(r.x + r.w <= space.0 + eps && r.y + r.h <= space.1 + eps);
+            if let StateBoxD::Branched(children) = &b.d {
+                assert_nested(children, (r.w, r.h), header_height);
+            }
+        }
+        if !boxes.is_empty() {
+            let area = boxes
+                .iter()
+                .map(|f| f.placement.w * f.placement.h)
+                .sum::<f64>();
+            let room = space.0 * (space.1 - header);
+            assert!((area - room).abs() < 1e-6 * room);
+        }
+    }
+
+    #[test]
+    fn recursive_box_nests_tiles_below_headers() {
+        let boxes = recursive_box((400.0, 300.0), &params(0.0, 20.0), &tree());
+
+        assert_eq!(boxes.len(), 3);
+        assert_nested(&boxes, (400.0, 300.0), 20.0);
+        let d = boxes.iter().find(|f| f.name == "d").unwrap();
+        assert!(d.is_dir);
+        assert!(matches!(&d.d, StateBoxD::Branched(c) if c.len() == 3));
+    }
+
+    #[test]
+    fn recursive_box_room_below_header() {
+        let dir = tree();
+
+        // No header leaves the whole tile to the children.
+        let boxes = recursive_box((100.0, 40.0), &params(0.0, 0.0), &dir);
+        assert_nested(&boxes, (100.0, 40.0), 0.0);
+
+        // A header taller than half the tile is cut down to half.
+        let boxes = recursive_box((100.0, 40.0), &params(0.0, 30.0), &dir);
+        assert!(boxes.iter().all(|f| f.placement.y >= 20.0 - 1e-6));
+        assert_nested(&boxes, (100.0, 40.0), 30.0);
+
+        // Only the room under the header has to fit a tile of the minimum area.
+        assert!(recursive_box((40.0, 40.0), &params(1000.0, 20.0), &dir).is_empty());
+        assert!(!recursive_box((40.0, 50.0), &params(1000.0, 20.0), &dir).is_empty());
+        assert!(!recursive_box((40.0, 40.0), &params(1000.0, 0.0), &dir).is_empty());
+    }
+
+    /// A point in the lower half of `r` placed at `at`, where its children are.
+    fn below_header(r: treemap::Rect, at: (f64, f64)) -> (f32, f32) {
+        (
+            (at.0 + r.x + r.w / 2.0) as f32,
+            (at.1 + r.y + r.h * 0.75) as f32,
+        )
+    }
+
+    fn hit(boxes: &[StateBox], p: (f32, f32)) -> Option<Vec<&StateBox>> {
+        boxes.iter().find_map(|f| f.recurse_find((0.0, 0.0), p))
+    }
+
+    fn tile(name: &str, rect: (f64, f64, f64, f64), d: StateBoxD) -> StateBox {
+        let is_dir = matches!(d, StateBoxD::Branched(_));
+        StateBox {
+            d,
+            placement: treemap::Rect::from_points(rect.0, rect.1, rect.0 + rect.2, rect.1 + rect.3),
+            size: 1,
+            name: name.into(),
+            extension: None,
+            path: Some(Path::new("/r").join(name)),
+            count: 1,
+            files: 1,
+            modified: None,
+            is_dir,
+            idx: 0,
+        }
+    }
+
+    #[test]
+    fn hit_resolves_unlabeled_directory() {
+        // `d` was too small to have its children built, so it's drawn as one flat tile.
+        let boxes = [
+            tile("big", (0.0, 0.0, 300.0, 100.0), StateBoxD::Leaf),
+            tile(
+                "d",
+                (300.0, 0.0, 10.0, 100.0),
+                StateBoxD::Branched(Vec::new()),
+            ),
+        ];
+
+        for p in [(305.0, 50.0), (300.0, 0.0), (309.9, 99.9)] {
+            let chain = hit(&boxes, p).unwrap();
+            assert_eq!(chain.len(), 1);
+            assert!(chain[0].is_dir);
+            assert_eq!(chain[0].path.as_deref(), Some(Path::new("/r/d")));
+        }
+        assert_eq!(hit(&boxes, (299.9, 50.0)).unwrap()[0].name, "big");
+        assert!(hit(&boxes, (310.0, 50.0)).is_none());
+        assert!(hit(&boxes, (-1.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn hit_resolves_innermost_tile() {
+        let boxes = recursive_box((400.0, 300.0), &params(0.0, 20.0), &tree());
+        let d = boxes.iter().find(|f| f.name == "d").unwrap();
+        let StateBoxD::Branched(children) = &d.d else {
+            panic!("d has no children");
+        };
+        let e = children.iter().find(|f| f.name == "e").unwrap();
+
+        let chain = hit(
+            &boxes,
+            below_header(e.placement, (d.placement.x, d.placement.y)),
+        )
+        .unwrap();
+        let names = chain.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        // `e` holds one file, which fills all of it below its header.
+        assert_eq!(names, ["d", "e", "c"]);
+        assert_eq!(chain[2].path.as_deref(), Some(Path::new("/r/d/e/c")));
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use cosmic::iced::{
+    futures::{channel::mpsc, SinkExt, StreamExt},
+    Subscription,
+};
+use notify::Watcher;
+
+/// How long to wait for a burst of filesystem events to settle before rescanning.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches `root` recursively, emitting the directories that need rescanning after each burst of
+/// changes, and `on_error` when watching fails. The subscription is keyed on `root`, so watching
+/// a new root drops the old watcher.
+pub fn watch<Msg: Send + 'static>(
+    root: PathBuf,
+    on_change: fn(PathBuf) -> Msg,
+    on_error: fn(String) -> Msg,
+) -> Subscription<Msg> {
+    Subscription::run_with_id(
+        root.clone(),
+        cosmic::iced::stream::channel(16, move |mut output| async move {
+            let (tx, mut rx) = mpsc::unbounded();
+            let watcher = notify::recommended_watcher(move |ev| {
+                _ = tx.unbounded_send(ev);
+            });
+            let mut watcher = match watcher {
+                Ok(w) => w,
+                Err(e) => {
+                    _ = output
+                        .send(on_error(format!("Couldn't watch for changes: {e}")))
+                        .await;
+                    return;
+                }
+            };
+            if let Err(e) = watcher.watch(&root, notify::RecursiveMode::Recursive) {
+                let message = format!("Couldn't watch {} for changes: {e}", root.display());
+                _ = output.send(on_error(message)).await;
+                return;
+            }
+
+            while let Some(first) = rx.next().await {
+                tokio::time::sleep(DEBOUNCE).await;
+
+                let mut changed = Vec::new();
+                let mut errors = Vec::new();
+                let burst = std::iter::once(first)
+                    .chain(std::iter::from_fn(|| rx.try_next().ok().flatten()));
+                for ev in burst {
+                    match ev {
+                        Ok(ev) => changed.extend(ev.paths),
+                        Err(e) => errors.push(e),
+                    }
+                }
+
+                // One burst can fail many times over, and only the first is worth showing.
+                if let Some(e) = errors.first() {
+                    let message = format!("Missed changes under {}: {e}", root.display());
+                    if output.send(on_error(message)).await.is_err() {
+                        return;
+                    }
+                }
+
+                for dir in changed_dirs(&root, changed) {
+                    if output.send(on_change(dir)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }),
+    )
+}
+
+/// Maps changed paths to the smallest set of existing directories under `root` that cover them.
+fn changed_dirs(root: &Path, changed: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs = changed
+        .into_iter()
+        .map(|path| {
+            let mut dir = path
+                .parent()
+                .map_or_else(|| root.to_owned(), Path::to_owned);
+            while !dir.is_dir() && dir.starts_with(root) && dir != root {
+                dir.pop();
+            }
+            if dir.starts_with(root) {
+                dir
+            } else {
+                root.to_owned()
+            }
+        })
+        .collect::<Vec<_>>();
+    dirs.sort_unstable();
+    dirs.dedup();
+
+    let mut covering: Vec<PathBuf> = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !covering.iter().any(|f| dir.starts_with(f)) {
+            covering.push(dir);
+        }
+    }
+
+    covering
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_dirs_cover_changes() {
+        // The directories have to exist, and the repository's own do.
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let changed = |paths: &[&str]| {
+            let paths = paths.iter().map(|f| root.join(f)).collect();
+            changed_dirs(root, paths)
+                .into_iter()
+                .map(|f| f.strip_prefix(root).unwrap().to_path_buf())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            changed(&[
+                "tests/data/export.du.txt",
+                "src/gui/watch.rs",
+                "src/gui/config.rs"
+            ]),
+            [Path::new("src/gui"), Path::new("tests/data")]
+        );
+        // A directory covers the changes in the ones under it.
+        assert_eq!(
+            changed(&["src/gui/watch.rs", "src/format.rs"]),
+            [Path::new("src")]
+        );
+        // A change in a directory that's gone is a change in the nearest one left.
+        assert_eq!(changed(&["src/gone/deeper/file"]), [Path::new("src")]);
+        assert_eq!(changed(&["gone/file"]), [Path::new("")]);
+        assert_eq!(
+            changed_dirs(root, vec![PathBuf::from("/elsewhere/file")]),
+            [root]
+        );
+    }
+}
@@ -1,6 +1,126 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::Parser;
+
 mod analyze;
+#[cfg(feature = "archives")]
+mod archive;
+mod choice;
+mod cli;
+mod export;
+mod format;
 mod gui;
+#[cfg(feature = "sftp")]
+mod sftp;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Scan and print the result to stdout instead of launching the GUI
+    #[arg(long)]
+    no_gui: bool,
+    /// How many levels below the root to print
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+    /// Print sizes in human readable units
+    #[arg(long)]
+    human: bool,
+    /// Use binary (KiB, MiB) rather than decimal (kB, MB) units with --human
+    #[arg(long)]
+    binary: bool,
+    /// Print the tree as JSON
+    #[arg(long, conflicts_with = "metrics")]
+    json: bool,
+    /// Print the tree indented under each directory, like `tree --du`
+    #[arg(long, conflicts_with_all = ["json", "metrics"])]
+    outline: bool,
+    /// Print totals and the sizes of the top-level items as Prometheus metrics
+    #[arg(long)]
+    metrics: bool,
+    /// Leave files smaller than this many bytes out of the tree, still counting their size
+    #[arg(long, default_value_t = 0)]
+    min_file_size: u64,
+    /// Leave files with no contents out of the tree
+    #[arg(long)]
+    skip_empty_files: bool,
+    /// Keep only this many of the largest entries of each directory, lumping the rest together
+    #[arg(long)]
+    max_entries_per_dir: Option<usize>,
+    /// Only keep what's in directories over this size, like 1G, adding up the rest without
+    /// listing them
+    #[arg(long, value_parser = parse_limit)]
+    detail_threshold: Option<u64>,
+    /// List the contents of zip and tar archives as if they were directories
+    #[cfg(feature = "archives")]
+    #[arg(long)]
+    scan_archives: bool,
+    /// Count directories found at several paths, such as through bind mounts, only once
+    #[arg(long)]
+    dedup_dirs: bool,
+    /// What to do with entries whose name starts with a dot
+    #[arg(long, value_enum, default_value_t)]
+    hidden: analyze::HiddenMode,
+    /// Keep rescanning without the GUI, showing a notification when the size goes over this, like
+    /// 10G or 500 MB
+    #[arg(long, value_parser = parse_limit)]
+    watch_limit: Option<u64>,
+    /// Seconds between the scans of --watch-limit
+    #[arg(long, default_value_t = 300, requires = "watch_limit")]
+    watch_interval: u64,
+    /// Directory to analyze, scanned immediately when launching the GUI
+    path: Option<PathBuf>,
+}
+
+fn parse_limit(s: &str) -> Result<u64, String> {
+    format::parse_size(s).ok_or_else(|| format!("{s:?} isn't a size"))
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let ctx = analyze::Context::default()
+        .with_min_file_size(args.min_file_size)
+        .with_include_empty_files(!args.skip_empty_files)
+        .with_max_entries_per_dir(args.max_entries_per_dir)
+        .with_detail_threshold(args.detail_threshold)
+        .with_dedup_dirs(args.dedup_dirs)
+        .with_hidden(args.hidden);
+    #[cfg(feature = "archives")]
+    let ctx = ctx.with_scan_archives(args.scan_archives);
+    let units = if args.binary {
+        format::SizeUnits::Binary
+    } else {
+        format::SizeUnits::Decimal
+    };
+
+    if let Some(limit) = args.watch_limit {
+        cli::watch(
+            &args.path.unwrap_or_else(|| PathBuf::from(".")),
+            &ctx,
+            limit,
+            Duration::from_secs(args.watch_interval),
+            units,
+        );
+    }
+
+    if args.no_gui {
+        return cli::run(
+            &args.path.unwrap_or_else(|| PathBuf::from(".")),
+            &ctx,
+            args.depth,
+            args.human.then_some(units),
+            if args.json {
+                cli::Output::Json
+            } else if args.metrics {
+                cli::Output::Metrics
+            } else if args.outline {
+                cli::Output::Outline
+            } else {
+                cli::Output::Tree
+            },
+        );
+    }
 
-fn main() {
-    gui::run();
+    gui::run(args.path);
+    ExitCode::SUCCESS
 }
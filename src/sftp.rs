@@ -0,0 +1,231 @@
+use std::{
+    hash::{Hash, Hasher},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::analyze::{FileKind, FileSystem, Metadata};
+
+const DEFAULT_PORT: u16 = 22;
+
+/// A server to scan, named by a path like `sftp://user@host:port/dir`, with an IPv6 address in
+/// brackets like `sftp://[::1]/dir`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    user: Option<String>,
+    host: String,
+    port: u16,
+    /// Everything before the path on the server, which every scanned path starts with.
+    prefix: String,
+}
+impl Remote {
+    /// The server `path` is on, or `None` if it isn't an `sftp://` URL.
+    pub fn parse(path: &Path) -> Option<Self> {
+        let rest = path.to_str()?.strip_prefix("sftp://")?;
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (user, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_owned()), host),
+            None => (None, authority),
+        };
+        // The colons of an IPv6 address aren't the one before the port.
+        let (host, port) = match host.strip_prefix('[') {
+            Some(bracketed) => match bracketed.split_once(']')? {
+                (host, "") => (host, DEFAULT_PORT),
+                (host, port) => (host, port.strip_prefix(':')?.parse().ok()?),
+            },
+            None => match host.rsplit_once(':') {
+                Some((host, _)) if host.contains(':') => return None,
+                Some((host, port)) => (host, port.parse().ok()?),
+                None => (host, DEFAULT_PORT),
+            },
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(Self {
+            user,
+            host: host.to_owned(),
+            port,
+            prefix: format!("sftp://{authority}"),
+        })
+    }
+}
+
+/// A server's filesystem, read over SFTP. Paths given to and returned from it are `sftp://` URLs,
+/// so scanned trees say where they came from.
+///
+/// SFTP doesn't report allocated blocks, hard links or inodes, so sizes are apparent sizes, every
+/// file has one link, and directories are told apart by their resolved path.
+pub struct SftpFs {
+    remote: Remote,
+    sftp: ssh2::Sftp,
+    // The channel `sftp` runs on closes with the session.
+    _session: ssh2::Session,
+}
+impl SftpFs {
+    /// Connects to `remote`, which has to be in `~/.ssh/known_hosts`, and logs in with the user's
+    /// SSH agent.
+    pub fn connect(remote: Remote) -> std::io::Result<Self> {
+        let tcp = TcpStream::connect((remote.host.as_str(), remote.port))?;
+        let mut session = ssh2::Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake()?;
+
+        let mut known_hosts = session.known_hosts()?;
+        if let Some(home) = std::env::var_os("HOME") {
+            let file = Path::new(&home).join(".ssh/known_hosts");
+            if file.exists() {
+                known_hosts.read_file(&file, ssh2::KnownHostFileKind::OpenSSH)?;
+            }
+        }
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| std::io::Error::other("the server sent no host key"))?;
+        match known_hosts.check_port(&remote.host, remote.port, key) {
+            ssh2::CheckResult::Match => {}
+            ssh2::CheckResult::Mismatch => {
+                return Err(std::io::Error::other(format!(
+                    "the host key of {} doesn't match the one in ~/.ssh/known_hosts",
+                    remote.host
+                )));
+            }
+            _ => {
+                return Err(std::io::Error::other(format!(
+                    "{} isn't in ~/.ssh/known_hosts; connect with ssh once to add it",
+                    remote.host
+                )));
+            }
+        }
+
+        let user = remote
+            .user
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_default();
+        session.userauth_agent(&user)?;
+        let sftp = session.sftp()?;
+        Ok(Self {
+            remote,
+            sftp,
+            _session: session,
+        })
+    }
+
+    /// The path on the server `path` stands for.
+    fn remote_path(&self, path: &Path) -> std::io::Result<PathBuf> {
+        path.to_str()
+            .and_then(|f| f.strip_prefix(&self.remote.prefix))
+            .map(|f| PathBuf::from(if f.is_empty() { "/" } else { f }))
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} isn't on {}", path.display(), self.remote.host),
+                )
+            })
+    }
+
+    /// Converts `stat`, telling directories apart by the path `resolved` returns.
+    fn to_metadata(&self, stat: &ssh2::FileStat, resolved: impl FnOnce() -> PathBuf) -> Metadata {
+        let file_type = stat.file_type();
+        let kind = if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_file() {
+            FileKind::File
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::Other
+        };
+        // Only directories need telling apart, to notice symlinks back to an ancestor.
+        let ino = if kind == FileKind::Dir {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            resolved().hash(&mut hasher);
+            hasher.finish()
+        } else {
+            0
+        };
+        let len = stat.size.unwrap_or_default();
+        Metadata {
+            kind,
+            dev: 0,
+            ino,
+            blocks: None,
+            nlink: 1,
+            len,
+            modified: stat.mtime.map(|f| UNIX_EPOCH + Duration::from_secs(f)),
+        }
+    }
+
+    fn resolve(&self, remote: &Path) -> PathBuf {
+        self.sftp
+            .realpath(remote)
+            .unwrap_or_else(|_| remote.to_path_buf())
+    }
+}
+impl FileSystem for SftpFs {
+    fn read_dir(
+        &self,
+        dir: &Path,
+    ) -> std::io::Result<impl Iterator<Item = std::io::Result<PathBuf>>> {
+        let entries = self.sftp.readdir(&self.remote_path(dir)?)?;
+        let prefix = self.remote.prefix.clone();
+        Ok(entries
+            .into_iter()
+            .map(move |(path, _)| Ok(PathBuf::from(format!("{prefix}{}", path.display())))))
+    }
+
+    fn metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        let remote = self.remote_path(path)?;
+        let stat = self.sftp.stat(&remote)?;
+        Ok(self.to_metadata(&stat, || self.resolve(&remote)))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> std::io::Result<Metadata> {
+        let remote = self.remote_path(path)?;
+        let stat = self.sftp.lstat(&remote)?;
+        Ok(self.to_metadata(&stat, || self.resolve(&remote)))
+    }
+
+    fn read_link(&self, path: &Path) -> std::io::Result<PathBuf> {
+        Ok(self.sftp.readlink(&self.remote_path(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(path: &str) -> Option<(Option<String>, String, u16)> {
+        Remote::parse(Path::new(path)).map(|f| (f.user, f.host, f.port))
+    }
+
+    #[test]
+    fn parse_remotes() {
+        assert_eq!(parse("sftp://host/dir"), Some((None, "host".into(), 22)));
+        assert_eq!(
+            parse("sftp://me@host:2222/dir"),
+            Some((Some("me".into()), "host".into(), 2222))
+        );
+        assert_eq!(parse("sftp://[::1]/dir"), Some((None, "::1".into(), 22)));
+        assert_eq!(
+            parse("sftp://me@[fe80::1]:2222"),
+            Some((Some("me".into()), "fe80::1".into(), 2222))
+        );
+        assert_eq!(
+            Remote::parse(Path::new("sftp://[::1]:2222/dir"))
+                .unwrap()
+                .prefix,
+            "sftp://[::1]:2222"
+        );
+
+        assert_eq!(parse("/home/me"), None);
+        assert_eq!(parse("sftp:///dir"), None);
+        assert_eq!(parse("sftp://host:port/dir"), None);
+        // Without brackets, there's no telling the port from the address.
+        assert_eq!(parse("sftp://::1/dir"), None);
+        assert_eq!(parse("sftp://[::1/dir"), None);
+        assert_eq!(parse("sftp://[::1]2222/dir"), None);
+        assert_eq!(parse("sftp://[]/dir"), None);
+    }
+}